@@ -0,0 +1,284 @@
+//! Adapters for building pipelines over streams of reader events.
+//!
+//! The adapters work with any iterator over `Result<Event, Error>`, like [`Reader`](crate::Reader)
+//! or [`NsReader`](crate::NsReader), and pass errors through unchanged.
+//!
+//! # Examples
+//!
+//! ```
+//! # use speedy_xml::{adapter::EventIteratorExt, reader::Event, Reader, Writer};
+//! let mut writer = Writer::new(Vec::new());
+//! let events = Reader::new("<a><!-- comment --><b/></a>")
+//!     .filter_events(|event| !matches!(event, Event::Comment(_)));
+//!
+//! for event in events {
+//!     writer.write_event(&event.unwrap()).unwrap();
+//! }
+//! assert_eq!(writer.finish().unwrap(), b"<a><b/></a>");
+//! ```
+
+use std::{
+    borrow::Cow,
+    fmt::{Debug, Display},
+    iter::FusedIterator,
+};
+
+use crate::reader::{Error, Event};
+
+/// An iterator that only yields the events matching a predicate, created by
+/// [`EventIteratorExt::filter_events`].
+#[derive(Debug, Clone)]
+pub struct Filtered<I, F> {
+    inner: I,
+    predicate: F,
+}
+
+impl<'a, I, F> Iterator for Filtered<I, F>
+where
+    I: Iterator<Item = Result<Event<'a>, Error>>,
+    F: FnMut(&Event<'a>) -> bool,
+{
+    type Item = Result<Event<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(event) if !(self.predicate)(&event) => continue,
+                result => return Some(result),
+            }
+        }
+    }
+}
+
+impl<'a, I, F> FusedIterator for Filtered<I, F>
+where
+    I: FusedIterator<Item = Result<Event<'a>, Error>>,
+    F: FnMut(&Event<'a>) -> bool,
+{
+}
+
+/// An iterator that transforms every event with a function, created by
+/// [`EventIteratorExt::map_events`].
+#[derive(Debug, Clone)]
+pub struct Mapped<I, F> {
+    inner: I,
+    map: F,
+}
+
+impl<'a, I, F, T> Iterator for Mapped<I, F>
+where
+    I: Iterator<Item = Result<Event<'a>, Error>>,
+    F: FnMut(Event<'a>) -> T,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|result| result.map(&mut self.map))
+    }
+}
+
+impl<'a, I, F, T> FusedIterator for Mapped<I, F>
+where
+    I: FusedIterator<Item = Result<Event<'a>, Error>>,
+    F: FnMut(Event<'a>) -> T,
+{
+}
+
+/// The prefixed names of the elements enclosing a piece of text, yielded by [`Leaves`].
+///
+/// The names are stored separated by `/`, which can never be part of a name.
+#[derive(Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ElementPath(String);
+
+impl ElementPath {
+    /// Returns the names separated by `/`, like `root/item/title`.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the names of the enclosing elements, outermost first.
+    pub fn names(&self) -> impl DoubleEndedIterator<Item = &str> + '_ {
+        self.0.split('/').filter(|name| !name.is_empty())
+    }
+
+    /// Returns the number of enclosing elements.
+    pub fn depth(&self) -> usize {
+        self.names().count()
+    }
+}
+
+impl Debug for ElementPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <str as Debug>::fmt(&self.0, f)
+    }
+}
+
+impl Display for ElementPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// An iterator over every piece of text in a document together with the path of its
+/// enclosing elements, created by [`EventIteratorExt::leaves`].
+#[derive(Debug, Clone)]
+pub struct Leaves<I> {
+    inner: I,
+    path: String,
+    /// The length of `path` before each open element was appended.
+    lengths: Vec<usize>,
+}
+
+impl<'a, I> Iterator for Leaves<I>
+where
+    I: Iterator<Item = Result<Event<'a>, Error>>,
+{
+    type Item = Result<(ElementPath, Cow<'a, str>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let content = match self.inner.next()? {
+                Ok(Event::Start(start)) => {
+                    self.lengths.push(self.path.len());
+                    if !self.path.is_empty() {
+                        self.path.push('/');
+                    }
+                    if let Some(prefix) = start.prefix() {
+                        self.path.push_str(prefix);
+                        self.path.push(':');
+                    }
+                    self.path.push_str(start.name());
+                    continue;
+                }
+                Ok(Event::End(_)) => {
+                    if let Some(len) = self.lengths.pop() {
+                        self.path.truncate(len);
+                    }
+                    continue;
+                }
+                Ok(Event::Text(text)) if !text.is_whitespace() => text.content(),
+                Ok(Event::CData(cdata)) => cdata.content().into(),
+                Ok(_) => continue,
+                Err(error) => return Some(Err(error)),
+            };
+
+            return Some(Ok((ElementPath(self.path.clone()), content)));
+        }
+    }
+}
+
+impl<'a, I> FusedIterator for Leaves<I> where I: FusedIterator<Item = Result<Event<'a>, Error>> {}
+
+/// Extension methods for iterators over reader events.
+pub trait EventIteratorExt<'a>: Iterator<Item = Result<Event<'a>, Error>> + Sized {
+    /// Drops all events for which `predicate` returns `false`, errors are always kept.
+    fn filter_events<F>(self, predicate: F) -> Filtered<Self, F>
+    where
+        F: FnMut(&Event<'a>) -> bool,
+    {
+        Filtered {
+            inner: self,
+            predicate,
+        }
+    }
+
+    /// Transforms every event with `map`, errors are passed through.
+    ///
+    /// Events can be rewritten into [`OwnedEvent`](crate::reader::OwnedEvent)s that are
+    /// accepted by [`Writer::write_event`](crate::Writer::write_event), for example with
+    /// [`Event::with_name`] to rename elements.
+    fn map_events<F, T>(self, map: F) -> Mapped<Self, F>
+    where
+        F: FnMut(Event<'a>) -> T,
+    {
+        Mapped { inner: self, map }
+    }
+
+    /// Flattens the document into pairs of the path of the enclosing elements and the
+    /// unescaped content of every text or cdata event, skipping text that consists only of
+    /// whitespace.
+    ///
+    /// Attributes are not included, and text in mixed content is yielded piece by piece.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::{adapter::EventIteratorExt, Reader};
+    /// let leaves = Reader::new("<book>\n  <title>Dune</title>\n  <author>F. Herbert</author>\n</book>")
+    ///     .leaves()
+    ///     .map(|leaf| leaf.map(|(path, text)| format!("{path} = {text}")))
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(leaves, ["book/title = Dune", "book/author = F. Herbert"]);
+    /// ```
+    fn leaves(self) -> Leaves<Self> {
+        Leaves {
+            inner: self,
+            path: String::new(),
+            lengths: Vec::new(),
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = Result<Event<'a>, Error>>> EventIteratorExt<'a> for I {}
+
+#[cfg(test)]
+mod test {
+    use super::EventIteratorExt;
+    use crate::{
+        reader::{Event, Reader},
+        Writer,
+    };
+
+    #[test]
+    fn pipeline() {
+        let names = Reader::new("<a><!-- x --><b/>text<c></c></a>")
+            .filter_events(|event| matches!(event, Event::Start(_) | Event::Empty(_)))
+            .map_events(|event| match event {
+                Event::Start(start) | Event::Empty(start) => start.name(),
+                _ => unreachable!(),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn rename() {
+        let mut writer = Writer::new(Vec::new());
+        let events =
+            Reader::new("<x:a b='c'>text<x:d />\n</x:a >").map_events(|event| match event {
+                Event::Start(start) | Event::Empty(start) => {
+                    event.with_name(Some("y"), start.name())
+                }
+                Event::End(end) => event.with_name(Some("y"), end.name()),
+                _ => event.into_owned(),
+            });
+
+        for event in events {
+            writer.write_event(&event.unwrap().as_event()).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), b"<y:a b='c'>text<y:d />\n</y:a>");
+    }
+
+    #[test]
+    fn leaves() {
+        let leaves = Reader::new("<a><x:b>1 &amp; 2</x:b>mixed<![CDATA[<c>]]><c/></a>")
+            .leaves()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(leaves.len(), 3);
+        assert_eq!(leaves[0].0.as_str(), "a/x:b");
+        assert_eq!(leaves[0].0.names().collect::<Vec<_>>(), ["a", "x:b"]);
+        assert_eq!(leaves[0].1, "1 & 2");
+        assert_eq!(leaves[1].0.depth(), 1);
+        assert_eq!(leaves[1].1, "mixed");
+        assert_eq!(leaves[2].1, "<c>");
+
+        let mut leaves = Reader::new("<a>text").leaves();
+        assert!(leaves.next().unwrap().is_ok());
+        assert!(leaves.next().unwrap().is_err());
+    }
+}
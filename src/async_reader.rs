@@ -0,0 +1,182 @@
+//! An asynchronous reader that pulls its input from a [`tokio`] [`AsyncRead`] source.
+//!
+//! This module is only available with the `async-tokio` feature.
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{
+    push::{Driver, Next, ReadError},
+    reader::{Event, Options},
+};
+
+/// An XML reader that reads its input from an [`AsyncRead`] source.
+///
+/// This is the asynchronous counterpart of [`StreamReader`](crate::stream::StreamReader)
+/// and behaves the same way, including the transparent gzip decompression.
+///
+/// # Examples
+///
+/// ```
+/// # use speedy_xml::{async_reader::AsyncReader, reader::Event};
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut reader = AsyncReader::new(&b"<root><child/></root>"[..]);
+/// let mut names = Vec::new();
+///
+/// while let Some(event) = reader.read_event().await {
+///     if let Event::Start(start) | Event::Empty(start) = event.unwrap() {
+///         names.push(start.name().to_owned());
+///     }
+/// }
+/// assert_eq!(names, ["root", "child"]);
+/// # });
+/// ```
+pub struct AsyncReader<R> {
+    source: R,
+    driver: Driver,
+}
+
+impl<R: AsyncRead + Unpin> AsyncReader<R> {
+    /// Creates a new asynchronous reader over `source`.
+    pub fn new(source: R) -> Self {
+        Self::with_options(source, Options::default())
+    }
+
+    /// Creates a new asynchronous reader over `source` with the provided [`Options`].
+    pub fn with_options(source: R, options: Options) -> Self {
+        Self {
+            source,
+            driver: Driver::new(options),
+        }
+    }
+
+    /// Sets a callback that is invoked after every chunk read from the source, see
+    /// [`StreamReader::set_progress_callback`](crate::stream::StreamReader::set_progress_callback).
+    pub fn set_progress_callback(
+        &mut self,
+        total: Option<u64>,
+        callback: impl FnMut(u64, Option<u64>) + Send + 'static,
+    ) {
+        self.driver.set_progress_callback(total, callback);
+    }
+
+    /// Returns the next event, reading more input from the source if necessary.
+    ///
+    /// Returns `None` once the end of the document has been reached or after an error other
+    /// than an I/O error has been returned. Spans of parse errors are relative to the start
+    /// of the input.
+    pub async fn read_event(&mut self) -> Option<Result<Event<'_>, ReadError>> {
+        loop {
+            match self.driver.next() {
+                Next::Event(event, range) => return Some(Ok(self.driver.attach(event, range))),
+                Next::Error(error) => return Some(Err(error)),
+                Next::Done => return None,
+                Next::NeedInput => (),
+            }
+
+            let read = self.source.read(self.driver.chunk()).await;
+            if let Err(error) = self.driver.consume(read) {
+                return Some(Err(error));
+            }
+        }
+    }
+
+    /// Returns a reference to the underlying source.
+    pub fn get_ref(&self) -> &R {
+        &self.source
+    }
+
+    /// Consumes this reader and returns the underlying source.
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use tokio::io::{AsyncRead, ReadBuf};
+
+    use super::AsyncReader;
+    use crate::{
+        push::ReadError,
+        reader::{ErrorKind, Event},
+    };
+
+    /// A source that returns its input one byte at a time.
+    struct Trickle(&'static [u8]);
+
+    impl AsyncRead for Trickle {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            if let Some((&first, rest)) = self.0.split_first() {
+                buf.put_slice(&[first]);
+                self.0 = rest;
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn trickle() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let mut reader = AsyncReader::new(Trickle("<a>zażółć<b/></a>".as_bytes()));
+            let mut events = Vec::new();
+            while let Some(event) = reader.read_event().await {
+                events.push(event.unwrap().into_owned());
+            }
+            assert_eq!(events.len(), 4);
+            let Event::Text(text) = events[1].as_event() else {
+                panic!()
+            };
+            assert_eq!(text.content(), "zażółć");
+
+            let mut reader = AsyncReader::new(Trickle(b"<a><b></a>"));
+            assert!(matches!(
+                reader.read_event().await,
+                Some(Ok(Event::Start(_)))
+            ));
+            assert!(matches!(
+                reader.read_event().await,
+                Some(Ok(Event::Start(_)))
+            ));
+            assert!(matches!(reader.read_event().await, Some(Ok(Event::End(_)))));
+            let event = reader.read_event().await;
+            let Some(Err(ReadError::Parse(error))) = event else {
+                panic!()
+            };
+            assert_eq!(error.kind(), ErrorKind::UnclosedElement);
+            assert!(reader.read_event().await.is_none());
+        });
+    }
+    #[test]
+    fn decode_error() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let mut reader = AsyncReader::new(Trickle(b"<a>\xff<b/></a>"));
+            assert!(matches!(
+                reader.read_event().await,
+                Some(Ok(Event::Start(_)))
+            ));
+            assert!(matches!(
+                reader.read_event().await,
+                Some(Err(ReadError::Decode(_)))
+            ));
+            assert!(reader.read_event().await.is_none());
+            assert!(reader.read_event().await.is_none());
+        });
+    }
+}
@@ -0,0 +1,430 @@
+//! Parsing of the markup declarations found in a doctype's internal subset.
+//!
+//! The parser is intentionally lenient, it does not validate declarations beyond what
+//! is required to split them apart and extract their names. Anything it does not
+//! understand is returned as a [`Declaration::Unknown`] item.
+
+use crate::lut::{is_invalid_name, is_whitespace};
+
+/// An external identifier like `SYSTEM "uri"` or `PUBLIC "id" "uri"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExternalId<'a> {
+    /// The public identifier, only present for `PUBLIC` identifiers.
+    pub public_id: Option<&'a str>,
+    /// The system identifier.
+    ///
+    /// May be missing for `PUBLIC` identifiers in notation declarations.
+    pub system_id: Option<&'a str>,
+}
+
+/// An `<!ENTITY ...>` declaration.
+#[derive(Debug, Clone, Copy)]
+pub struct EntityDeclaration<'a> {
+    name: &'a str,
+    parameter: bool,
+    value: Option<&'a str>,
+    external_id: Option<ExternalId<'a>>,
+    notation: Option<&'a str>,
+}
+
+impl<'a> EntityDeclaration<'a> {
+    /// Returns the name of the declared entity.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns `true` if this declares a parameter entity (`<!ENTITY % name ...>`).
+    pub fn is_parameter(&self) -> bool {
+        self.parameter
+    }
+
+    /// Returns the raw replacement text of an internal entity.
+    pub fn value(&self) -> Option<&'a str> {
+        self.value
+    }
+
+    /// Returns the external identifier of an external entity.
+    pub fn external_id(&self) -> Option<ExternalId<'a>> {
+        self.external_id
+    }
+
+    /// Returns the notation name of an unparsed entity (`NDATA name`).
+    pub fn notation(&self) -> Option<&'a str> {
+        self.notation
+    }
+}
+
+/// A single item of a doctype's internal subset.
+#[derive(Debug, Clone, Copy)]
+pub enum Declaration<'a> {
+    /// An `<!ENTITY ...>` declaration.
+    Entity(EntityDeclaration<'a>),
+    /// An `<!ELEMENT name spec>` declaration.
+    Element {
+        /// The name of the declared element.
+        name: &'a str,
+        /// The raw content specification.
+        content_spec: &'a str,
+    },
+    /// An `<!ATTLIST element definitions>` declaration.
+    AttributeList {
+        /// The name of the element the attributes belong to.
+        element: &'a str,
+        /// The raw attribute definitions.
+        definitions: &'a str,
+    },
+    /// A `<!NOTATION name id>` declaration.
+    Notation {
+        /// The name of the declared notation.
+        name: &'a str,
+        /// The external identifier of the notation.
+        external_id: Option<ExternalId<'a>>,
+    },
+    /// A parameter entity reference like `%name;`.
+    ParameterEntityReference(&'a str),
+    /// A comment, without the surrounding `<!--` and `-->`.
+    Comment(&'a str),
+    /// A processing instruction, without the surrounding `<?` and `?>`.
+    ProcessingInstruction(&'a str),
+    /// Markup that could not be parsed.
+    Unknown(&'a str),
+}
+
+/// An iterator over the [`Declaration`]s of a doctype's internal subset, obtained via
+/// [`DoctypeEvent::declarations`](crate::reader::DoctypeEvent::declarations).
+pub struct Declarations<'a> {
+    text: &'a str,
+}
+
+impl<'a> Declarations<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Self { text }
+    }
+}
+
+/// Returns the byte range of the internal subset in the content of a doctype declaration.
+///
+/// This uses the same bracket-counting rules as the reader uses to find the end of the doctype.
+pub(crate) fn find_internal_subset(content: &str) -> Option<std::ops::Range<usize>> {
+    let start = memchr::memchr2(b'[', b'>', content.as_bytes())
+        .filter(|&idx| content.as_bytes()[idx] == b'[')?
+        + 1;
+
+    let mut depth = 1;
+    let mut current = start;
+    while let Some(idx) = memchr::memchr2(b'[', b']', &content.as_bytes()[current..]) {
+        let idx = current + idx;
+        if content.as_bytes()[idx] == b'[' {
+            depth += 1;
+        } else {
+            depth -= 1;
+            if depth == 0 {
+                return Some(start..idx);
+            }
+        }
+        current = idx + 1;
+    }
+
+    None
+}
+
+/// Returns the root element name and external identifier from the content of a doctype
+/// declaration.
+pub(crate) fn parse_doctype(content: &str) -> (Option<&str>, Option<ExternalId<'_>>) {
+    let mut cursor = Cursor {
+        text: content,
+        current: 0,
+    };
+    cursor.skip_whitespace();
+    let Some(name) = cursor.name() else {
+        return (None, None);
+    };
+    cursor.skip_whitespace();
+    (Some(name), cursor.external_id())
+}
+
+struct Cursor<'a> {
+    text: &'a str,
+    current: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .text
+            .as_bytes()
+            .get(self.current)
+            .is_some_and(|&b| is_whitespace(b))
+        {
+            self.current += 1;
+        }
+    }
+
+    fn name(&mut self) -> Option<&'a str> {
+        let start = self.current;
+        while self
+            .text
+            .as_bytes()
+            .get(self.current)
+            .is_some_and(|&b| b == b':' || !(is_invalid_name(b) || b"[]\"'%;()|,<".contains(&b)))
+        {
+            self.current += 1;
+        }
+        (self.current > start).then(|| &self.text[start..self.current])
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let matches = self.text[self.current..].starts_with(keyword);
+        if matches {
+            self.current += keyword.len();
+        }
+        matches
+    }
+
+    fn literal(&mut self) -> Option<&'a str> {
+        let quote = *self.text.as_bytes().get(self.current)?;
+        if quote != b'"' && quote != b'\'' {
+            return None;
+        }
+
+        let end = memchr::memchr(quote, &self.text.as_bytes()[self.current + 1..])?;
+        let value = &self.text[self.current + 1..self.current + 1 + end];
+        self.current += end + 2;
+        Some(value)
+    }
+
+    fn external_id(&mut self) -> Option<ExternalId<'a>> {
+        if self.keyword("SYSTEM") {
+            self.skip_whitespace();
+            let system_id = self.literal()?;
+            Some(ExternalId {
+                public_id: None,
+                system_id: Some(system_id),
+            })
+        } else if self.keyword("PUBLIC") {
+            self.skip_whitespace();
+            let public_id = self.literal()?;
+            self.skip_whitespace();
+            Some(ExternalId {
+                public_id: Some(public_id),
+                system_id: self.literal(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the position of the next `>` not inside a quoted literal.
+    fn find_tag_end(&self) -> Option<usize> {
+        let mut current = self.current;
+        loop {
+            let idx =
+                current + memchr::memchr3(b'>', b'"', b'\'', &self.text.as_bytes()[current..])?;
+            match self.text.as_bytes()[idx] {
+                b'>' => return Some(idx),
+                quote => {
+                    current = idx + 2 + memchr::memchr(quote, &self.text.as_bytes()[idx + 1..])?;
+                }
+            }
+        }
+    }
+
+    fn entity(&mut self) -> Option<EntityDeclaration<'a>> {
+        self.skip_whitespace();
+        let parameter = self.keyword("%");
+        self.skip_whitespace();
+        let name = self.name()?;
+        self.skip_whitespace();
+
+        let mut declaration = EntityDeclaration {
+            name,
+            parameter,
+            value: None,
+            external_id: None,
+            notation: None,
+        };
+
+        if let Some(value) = self.literal() {
+            declaration.value = Some(value);
+        } else {
+            declaration.external_id = Some(self.external_id()?);
+            self.skip_whitespace();
+            if self.keyword("NDATA") {
+                self.skip_whitespace();
+                declaration.notation = Some(self.name()?);
+            }
+        }
+
+        self.skip_whitespace();
+        (self.text.as_bytes().get(self.current) == Some(&b'>')).then_some(declaration)
+    }
+
+    fn markup(&mut self, start: usize) -> Option<Declaration<'a>> {
+        if self.keyword("<!--") {
+            let end =
+                self.current + memchr::memmem::find(&self.text.as_bytes()[self.current..], b"-->")?;
+            self.current = end + 3;
+            return Some(Declaration::Comment(&self.text[start + 4..end]));
+        }
+
+        if self.keyword("<?") {
+            let end =
+                self.current + memchr::memmem::find(&self.text.as_bytes()[self.current..], b"?>")?;
+            self.current = end + 2;
+            return Some(Declaration::ProcessingInstruction(
+                &self.text[start + 2..end],
+            ));
+        }
+
+        let end = self.find_tag_end()?;
+        let declaration = if self.keyword("<!ENTITY") {
+            Declaration::Entity(self.entity()?)
+        } else if self.keyword("<!ELEMENT") {
+            self.skip_whitespace();
+            let name = self.name()?;
+            Declaration::Element {
+                name,
+                content_spec: self.text[self.current..end].trim_matches(is_xml_whitespace),
+            }
+        } else if self.keyword("<!ATTLIST") {
+            self.skip_whitespace();
+            let element = self.name()?;
+            Declaration::AttributeList {
+                element,
+                definitions: self.text[self.current..end].trim_matches(is_xml_whitespace),
+            }
+        } else if self.keyword("<!NOTATION") {
+            self.skip_whitespace();
+            let name = self.name()?;
+            self.skip_whitespace();
+            Declaration::Notation {
+                name,
+                external_id: self.external_id(),
+            }
+        } else {
+            return None;
+        };
+
+        self.current = end + 1;
+        Some(declaration)
+    }
+}
+
+fn is_xml_whitespace(chr: char) -> bool {
+    chr.is_ascii() && is_whitespace(chr as u8)
+}
+
+impl<'a> Iterator for Declarations<'a> {
+    type Item = Declaration<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut cursor = Cursor {
+            text: self.text,
+            current: 0,
+        };
+        cursor.skip_whitespace();
+        let start = cursor.current;
+
+        if start == self.text.len() {
+            return None;
+        }
+
+        let result = if cursor.keyword("%") {
+            cursor
+                .name()
+                .filter(|_| cursor.keyword(";"))
+                .map(Declaration::ParameterEntityReference)
+        } else {
+            cursor.markup(start)
+        };
+
+        let (result, end) = match result {
+            Some(declaration) => (declaration, cursor.current),
+            None => {
+                // Skip to the end of whatever this is and report it verbatim.
+                cursor.current = start + 1;
+                let end = cursor.find_tag_end().map_or(self.text.len(), |end| end + 1);
+                (Declaration::Unknown(&self.text[start..end]), end)
+            }
+        };
+
+        self.text = &self.text[end..];
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Declaration, Declarations, ExternalId};
+
+    #[test]
+    fn internal_subset() {
+        let subset = r#"
+            <!ENTITY nbsp "&#160;">
+            <!ENTITY % params SYSTEM "params.ent">
+            %params;
+            <!-- a comment -->
+            <!ENTITY logo PUBLIC "-//LOGO//EN" "logo.png" NDATA png>
+            <!ELEMENT root (#PCDATA | child)*>
+            <!ATTLIST root id ID #REQUIRED title CDATA "a > b">
+            <!NOTATION png SYSTEM "image/png">
+            <!BOGUS>
+        "#;
+
+        let declarations = Declarations::new(subset).collect::<Vec<_>>();
+        assert_eq!(declarations.len(), 9);
+
+        let Declaration::Entity(nbsp) = declarations[0] else {
+            panic!("{:?}", declarations[0])
+        };
+        assert_eq!(nbsp.name(), "nbsp");
+        assert_eq!(nbsp.value(), Some("&#160;"));
+        assert!(!nbsp.is_parameter());
+
+        let Declaration::Entity(params) = declarations[1] else {
+            panic!("{:?}", declarations[1])
+        };
+        assert!(params.is_parameter());
+        assert_eq!(
+            params.external_id(),
+            Some(ExternalId {
+                public_id: None,
+                system_id: Some("params.ent")
+            })
+        );
+
+        assert!(matches!(
+            declarations[2],
+            Declaration::ParameterEntityReference("params")
+        ));
+        assert!(matches!(
+            declarations[3],
+            Declaration::Comment(" a comment ")
+        ));
+
+        let Declaration::Entity(logo) = declarations[4] else {
+            panic!("{:?}", declarations[4])
+        };
+        assert_eq!(logo.notation(), Some("png"));
+
+        assert!(matches!(
+            declarations[5],
+            Declaration::Element {
+                name: "root",
+                content_spec: "(#PCDATA | child)*"
+            }
+        ));
+        assert!(matches!(
+            declarations[6],
+            Declaration::AttributeList {
+                element: "root",
+                definitions: r#"id ID #REQUIRED title CDATA "a > b""#
+            }
+        ));
+        assert!(matches!(
+            declarations[7],
+            Declaration::Notation { name: "png", .. }
+        ));
+        assert!(matches!(declarations[8], Declaration::Unknown("<!BOGUS>")));
+    }
+}
@@ -0,0 +1,376 @@
+//! Detection and decoding of the encoding of XML documents.
+//!
+//! The reader itself only works with UTF-8, documents in other encodings have to be
+//! decoded first using [`decode`] or [`Reader::from_bytes`](crate::Reader::from_bytes).
+//!
+//! Only UTF-8 and UTF-16 are supported by default, enabling the `encoding` feature adds
+//! support for all encodings supported by [`encoding_rs`](https://docs.rs/encoding_rs).
+
+use std::{
+    borrow::Cow,
+    fmt::{Debug, Display},
+    ops::Range,
+};
+
+use crate::{lut::is_whitespace, reader::ErrorCategory};
+
+/// An encoding detected by [`detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8, with or without a byte order mark.
+    Utf8,
+    /// Little-endian UTF-16.
+    Utf16Le,
+    /// Big-endian UTF-16.
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Returns the canonical name of this encoding.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+        }
+    }
+
+    /// Returns whether the encoding name `label` from an XML declaration refers to this encoding.
+    fn matches_label(self, label: &str) -> bool {
+        let labels: &[&str] = match self {
+            Self::Utf8 => &["utf-8", "utf8"],
+            Self::Utf16Le => &["utf-16", "utf16", "utf-16le"],
+            Self::Utf16Be => &["utf-16", "utf16", "utf-16be"],
+        };
+        labels.iter().any(|name| name.eq_ignore_ascii_case(label))
+    }
+}
+
+/// An error that can occur while decoding a document.
+#[derive(Clone)]
+pub enum DecodeError {
+    /// The document is not valid UTF-8.
+    InvalidUtf8(std::str::Utf8Error),
+    /// The document is not valid UTF-16, the offset of the first invalid code unit is included.
+    InvalidUtf16(usize),
+    /// The document is not valid in the encoding with the included name.
+    Malformed(&'static str),
+    /// The encoding named in the XML declaration contradicts the byte order mark or the
+    /// byte pattern of the document.
+    EncodingMismatch {
+        /// The encoding name from the XML declaration.
+        declared: Box<str>,
+        /// The encoding detected by [`detect`].
+        detected: Encoding,
+        /// The byte range of the encoding name in the original input.
+        span: Range<usize>,
+    },
+}
+
+impl DecodeError {
+    /// Returns a short identifier for this error, like `"encoding-mismatch"`.
+    ///
+    /// Like [`ErrorKind::code`](crate::reader::ErrorKind::code), codes are guaranteed to stay
+    /// the same across releases.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DecodeError::InvalidUtf8(_) => "invalid-utf8",
+            DecodeError::InvalidUtf16(_) => "invalid-utf16",
+            DecodeError::Malformed(_) => "malformed-input",
+            DecodeError::EncodingMismatch { .. } => "encoding-mismatch",
+        }
+    }
+
+    /// Returns the broad category of this error, which is always [`ErrorCategory::Encoding`].
+    pub fn category(&self) -> ErrorCategory {
+        ErrorCategory::Encoding
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::InvalidUtf8(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Debug for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as Display>::fmt(self, f)
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidUtf8(error) => <std::str::Utf8Error as Display>::fmt(error, f),
+            DecodeError::InvalidUtf16(offset) => {
+                write!(f, "invalid utf-16 code unit at byte offset {offset}")
+            }
+            DecodeError::Malformed(encoding) => write!(f, "malformed {encoding} input"),
+            DecodeError::EncodingMismatch {
+                declared,
+                detected,
+                span,
+            } => write!(
+                f,
+                "declared encoding {declared} at byte offset {} does not match detected encoding {}",
+                span.start,
+                detected.name()
+            ),
+        }
+    }
+}
+
+/// Detects the encoding of `bytes` and returns it along with the length of the byte order mark.
+///
+/// Detection is done by looking for a byte order mark or, if there is none, by looking at
+/// the byte pattern of a leading `<` as described in appendix F of the XML specification.
+/// Documents are assumed to be UTF-8 if neither is present.
+pub fn detect(bytes: &[u8]) -> (Encoding, usize) {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, ..] => (Encoding::Utf8, 3),
+        [0xFF, 0xFE, ..] => (Encoding::Utf16Le, 2),
+        [0xFE, 0xFF, ..] => (Encoding::Utf16Be, 2),
+        [b'<', 0, b'?' | b'!' | b'a'..=b'z' | b'A'..=b'Z', 0, ..] => (Encoding::Utf16Le, 0),
+        [0, b'<', 0, b'?' | b'!' | b'a'..=b'z' | b'A'..=b'Z', ..] => (Encoding::Utf16Be, 0),
+        _ => (Encoding::Utf8, 0),
+    }
+}
+
+fn decode_utf16(
+    bytes: &[u8],
+    mut offset: usize,
+    from_bytes: fn([u8; 2]) -> u16,
+) -> Result<String, DecodeError> {
+    let chunks = bytes.chunks_exact(2);
+    if !chunks.remainder().is_empty() {
+        return Err(DecodeError::InvalidUtf16(offset + bytes.len() - 1));
+    }
+
+    let mut result = String::with_capacity(bytes.len() / 2);
+    for chr in char::decode_utf16(chunks.map(|unit| from_bytes([unit[0], unit[1]]))) {
+        match chr {
+            Ok(chr) => {
+                result.push(chr);
+                offset += chr.len_utf16() * 2;
+            }
+            Err(_) => return Err(DecodeError::InvalidUtf16(offset)),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Returns the value of the `encoding` pseudo-attribute of the XML declaration at the
+/// start of `bytes`, if there is one.
+///
+/// Only works for ASCII-compatible encodings.
+pub fn declared_encoding(bytes: &[u8]) -> Option<&str> {
+    let declaration = bytes.strip_prefix(b"<?xml")?;
+    let declaration = &declaration[..memchr::memmem::find(declaration, b"?>")?];
+
+    let trim = |bytes: &[u8]| -> usize {
+        bytes
+            .iter()
+            .position(|&b| !is_whitespace(b))
+            .unwrap_or(bytes.len())
+    };
+
+    let rest = &declaration[memchr::memmem::find(declaration, b"encoding")? + 8..];
+    let rest = &rest[trim(rest)..];
+    let rest = rest.strip_prefix(b"=")?;
+    let rest = &rest[trim(rest)..];
+
+    let (&quote, rest) = rest.split_first()?;
+    if quote != b'"' && quote != b'\'' {
+        return None;
+    }
+
+    std::str::from_utf8(&rest[..memchr::memchr(quote, rest)?]).ok()
+}
+
+/// Decodes `bytes` using `encoding`, ignoring both the byte order mark and the XML declaration.
+///
+/// A byte order mark is only stripped if it matches `encoding`.
+///
+/// # Errors
+///
+/// Returns an error if the document is not valid in `encoding`.
+#[cfg(feature = "encoding")]
+pub fn decode_with<'b>(
+    bytes: &'b [u8],
+    encoding: &'static encoding_rs::Encoding,
+) -> Result<Cow<'b, str>, DecodeError> {
+    let bytes = match encoding_rs::Encoding::for_bom(bytes) {
+        Some((bom_encoding, bom_length)) if bom_encoding == encoding => &bytes[bom_length..],
+        _ => bytes,
+    };
+
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .ok_or(DecodeError::Malformed(encoding.name()))
+}
+
+/// Checks that the encoding declared in `text`, which was decoded as `encoding`, does not
+/// contradict it.
+fn check_declared_encoding(
+    text: &str,
+    encoding: Encoding,
+    bom_length: usize,
+) -> Result<(), DecodeError> {
+    let Some(declared) = declared_encoding(text.as_bytes()) else {
+        return Ok(());
+    };
+
+    let mismatch = match (encoding, bom_length) {
+        // Without a byte order mark any ASCII-compatible encoding may have been declared.
+        (Encoding::Utf8, 0) => {
+            Encoding::Utf16Le.matches_label(declared) || Encoding::Utf16Be.matches_label(declared)
+        }
+        _ => !encoding.matches_label(declared),
+    };
+    if !mismatch {
+        return Ok(());
+    }
+
+    let offset = declared.as_ptr().addr() - text.as_ptr().addr();
+    let span = match encoding {
+        Encoding::Utf8 => bom_length + offset..bom_length + offset + declared.len(),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let start = bom_length + text[..offset].encode_utf16().count() * 2;
+            start..start + declared.encode_utf16().count() * 2
+        }
+    };
+    Err(DecodeError::EncodingMismatch {
+        declared: declared.into(),
+        detected: encoding,
+        span,
+    })
+}
+
+/// Decodes `bytes` into a string after detecting its encoding using [`detect`].
+///
+/// Any byte order mark is stripped, UTF-8 documents are returned without copying.
+///
+/// If the `encoding` feature is enabled, documents without a byte order mark are decoded
+/// using the encoding specified in their XML declaration.
+///
+/// # Errors
+///
+/// Returns an error if the document is not valid in its detected encoding or if its XML
+/// declaration names an encoding that contradicts the detected one, like a UTF-16 document
+/// declaring `encoding="UTF-8"`.
+pub fn decode(bytes: &[u8]) -> Result<Cow<'_, str>, DecodeError> {
+    let (encoding, bom_length) = detect(bytes);
+
+    #[cfg(feature = "encoding")]
+    if encoding == Encoding::Utf8 && bom_length == 0 {
+        if let Some(declared) = declared_encoding(bytes)
+            .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+            .map(encoding_rs::Encoding::output_encoding)
+            .filter(|&declared| declared != encoding_rs::UTF_8)
+        {
+            return decode_with(bytes, declared);
+        }
+    }
+
+    let content = &bytes[bom_length..];
+
+    let text = match encoding {
+        Encoding::Utf8 => std::str::from_utf8(content)
+            .map(Cow::Borrowed)
+            .map_err(DecodeError::InvalidUtf8),
+        Encoding::Utf16Le => decode_utf16(content, bom_length, u16::from_le_bytes).map(Cow::Owned),
+        Encoding::Utf16Be => decode_utf16(content, bom_length, u16::from_be_bytes).map(Cow::Owned),
+    }?;
+
+    check_declared_encoding(&text, encoding, bom_length)?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, detect, DecodeError, Encoding};
+
+    #[test]
+    fn utf16() {
+        let text = "<a>zażółć</a>";
+        let le = [0xFF, 0xFE]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect::<Vec<_>>();
+        let be = text
+            .encode_utf16()
+            .flat_map(u16::to_be_bytes)
+            .collect::<Vec<_>>();
+
+        assert_eq!(detect(&le), (Encoding::Utf16Le, 2));
+        assert_eq!(detect(&be), (Encoding::Utf16Be, 0));
+        assert_eq!(decode(&le).unwrap(), text);
+        assert_eq!(decode(&be).unwrap(), text);
+        assert!(decode(&be[..be.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn utf8_bom() {
+        assert_eq!(decode(b"\xEF\xBB\xBF<a/>").unwrap(), "<a/>");
+    }
+
+    #[test]
+    fn encoding_mismatch() {
+        let utf16 = |text: &str| {
+            [0xFF, 0xFE]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(decode(&utf16("<?xml version='1.0' encoding='utf-16'?><a/>")).is_ok());
+        let Err(DecodeError::EncodingMismatch {
+            declared,
+            detected,
+            span,
+        }) = decode(&utf16("<?xml version='1.0' encoding='UTF-8'?><a/>"))
+        else {
+            panic!()
+        };
+        assert_eq!(
+            (&*declared, detected, span),
+            ("UTF-8", Encoding::Utf16Le, 62..72)
+        );
+
+        let Err(DecodeError::EncodingMismatch { span, .. }) =
+            decode(b"<?xml version='1.0' encoding='UTF-16'?><a/>")
+        else {
+            panic!()
+        };
+        assert_eq!(span, 30..36);
+        assert!(decode(b"\xEF\xBB\xBF<?xml version='1.0' encoding='UTF-16BE'?>").is_err());
+        assert!(decode(b"\xEF\xBB\xBF<?xml version='1.0' encoding='utf-8'?>").is_ok());
+    }
+
+    #[test]
+    fn declared_encoding() {
+        assert_eq!(
+            super::declared_encoding(b"<?xml version='1.0' encoding = \"latin1\"?><a/>"),
+            Some("latin1")
+        );
+        assert_eq!(super::declared_encoding(b"<?xml version='1.0'?>"), None);
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn legacy_encoding() {
+        assert_eq!(
+            decode(b"<?xml version='1.0' encoding='ISO-8859-1'?><a>caf\xE9</a>").unwrap(),
+            "<?xml version='1.0' encoding='ISO-8859-1'?><a>caf\u{e9}</a>"
+        );
+        assert_eq!(
+            super::decode_with(b"<a>\x82\xA0</a>", encoding_rs::SHIFT_JIS).unwrap(),
+            "<a>\u{3042}</a>"
+        );
+    }
+}
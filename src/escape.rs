@@ -1,9 +1,16 @@
 //! Functions for escaping and unescaping text in a RapidXML-compliant way.
 
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    hash::BuildHasher,
+};
 
 use memchr::memchr2;
 
+use crate::lut::is_whitespace;
+
 fn resolve_entity(text: &str) -> Option<(char, &str)> {
     let mut peek = text.chars();
 
@@ -46,35 +53,77 @@ fn resolve_entity(text: &str) -> Option<(char, &str)> {
     }
 }
 
-/// Unescapes an XML escaped string. Keeps all unresolved entities unexpanded.
-///
-/// # Notes
+/// A source of replacement text for named entity references like `&nbsp;`.
 ///
-/// Unlike RapidXML this will not insert invalid codepoints into the string and will keep
-/// character references that would expand to them unexpanded.
-pub fn unescape(string: &str) -> Cow<str> {
+/// Character references like `&#x20;` are always expanded and never passed to the resolver.
+pub trait EntityResolver {
+    /// Returns the replacement text of the entity called `name`, or `None` if it is unknown.
+    ///
+    /// The replacement text is inserted verbatim, it is not unescaped again.
+    fn resolve(&self, name: &str) -> Option<&str>;
+}
+
+/// An [`EntityResolver`] that only knows the five entities predefined by the XML specification.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PredefinedEntities;
+
+impl EntityResolver for PredefinedEntities {
+    fn resolve(&self, name: &str) -> Option<&str> {
+        Some(match name {
+            "lt" => "<",
+            "gt" => ">",
+            "amp" => "&",
+            "apos" => "'",
+            "quot" => "\"",
+            _ => return None,
+        })
+    }
+}
+
+/// Resolves entities from the map, falling back to [`PredefinedEntities`].
+impl<S: BuildHasher> EntityResolver for HashMap<String, String, S> {
+    fn resolve(&self, name: &str) -> Option<&str> {
+        self.get(name)
+            .map(String::as_str)
+            .or_else(|| PredefinedEntities.resolve(name))
+    }
+}
+
+enum Expansion<'r> {
+    Char(char),
+    Text(&'r str),
+}
+
+fn unescape_impl<'s, 'r>(
+    string: &'s str,
+    mut resolve: impl FnMut(&'s str) -> Option<(Expansion<'r>, &'s str)>,
+) -> Cow<'s, str> {
     let mut replaced = String::new();
 
     let mut current = string;
-    while let Some(next) = memchr2(b'&', b'\0', current.as_bytes()) {
+    let mut searched = 0;
+    while let Some(next) = memchr2(b'&', b'\0', &current.as_bytes()[searched..]) {
+        let next = searched + next;
         match current.as_bytes()[next] {
             b'&' => {
-                if let Some((chr, rest)) = resolve_entity(&current[next + 1..]) {
+                if let Some((expansion, rest)) = resolve(&current[next + 1..]) {
                     replaced.push_str(&current[..next]);
 
-                    if chr == '\0' {
-                        return Cow::Owned(replaced);
+                    match expansion {
+                        Expansion::Char('\0') => return Cow::Owned(replaced),
+                        Expansion::Char(chr) => replaced.push(chr),
+                        Expansion::Text(text) => replaced.push_str(text),
                     }
 
-                    replaced.push(chr);
                     current = rest;
+                    searched = 0;
                 } else {
-                    current = &current[1..];
+                    searched = next + 1;
                 }
             }
             _ => {
-                return if replaced.is_empty() {
-                    Cow::Borrowed(string)
+                return if replaced.is_empty() && current.len() == string.len() {
+                    Cow::Borrowed(&string[..next])
                 } else {
                     replaced.push_str(&current[..next]);
                     Cow::Owned(replaced)
@@ -83,7 +132,7 @@ pub fn unescape(string: &str) -> Cow<str> {
         }
     }
 
-    if replaced.is_empty() {
+    if replaced.is_empty() && current.len() == string.len() {
         Cow::Borrowed(string)
     } else {
         replaced.push_str(current);
@@ -91,6 +140,167 @@ pub fn unescape(string: &str) -> Cow<str> {
     }
 }
 
+/// Unescapes an XML escaped string. Keeps all unresolved entities unexpanded.
+///
+/// # Notes
+///
+/// Unlike RapidXML this will not insert invalid codepoints into the string and will keep
+/// character references that would expand to them unexpanded.
+pub fn unescape(string: &str) -> Cow<'_, str> {
+    unescape_impl(string, |text| {
+        resolve_entity(text).map(|(chr, rest)| (Expansion::Char(chr), rest))
+    })
+}
+
+/// Unescapes an XML escaped string, resolving named entities using `resolver`.
+/// Keeps all unresolved entities unexpanded.
+///
+/// # Notes
+///
+/// See [`unescape`] for notes regarding character references.
+pub fn unescape_with<'s>(string: &'s str, resolver: &dyn EntityResolver) -> Cow<'s, str> {
+    unescape_impl(string, |text| {
+        if text.starts_with('#') {
+            return resolve_entity(text).map(|(chr, rest)| (Expansion::Char(chr), rest));
+        }
+
+        let (name, rest) = text.split_once(';')?;
+        if name.is_empty() || name.bytes().any(|b| is_whitespace(b) || b == b'&') {
+            return None;
+        }
+
+        resolver
+            .resolve(name)
+            .map(|replacement| (Expansion::Text(replacement), rest))
+    })
+}
+
+/// Unescapes `normalized`, a normalized version of `text`, using `unescape`.
+///
+/// An owned normalized string is reused if it contains nothing to unescape. On failure `text`
+/// itself is unescaped so that the returned error points into it.
+pub(crate) fn unescape_normalized<'s, E>(
+    text: &'s str,
+    normalized: Cow<'s, str>,
+    unescape: impl Fn(&str) -> Result<Cow<'_, str>, E>,
+) -> Result<Cow<'s, str>, E> {
+    match normalized {
+        Cow::Borrowed(normalized) => unescape(normalized),
+        Cow::Owned(normalized) => match unescape(&normalized) {
+            Ok(Cow::Borrowed(_)) => Ok(Cow::Owned(normalized)),
+            Ok(Cow::Owned(unescaped)) => Ok(Cow::Owned(unescaped)),
+            Err(error) => Err(unescape(text).err().unwrap_or(error)),
+        },
+    }
+}
+
+/// An error returned by [`try_unescape`] when a string contains an invalid or unknown
+/// entity reference.
+#[derive(Clone, PartialEq, Eq)]
+pub struct UnescapeError {
+    pub(crate) offset: usize,
+}
+
+impl UnescapeError {
+    /// Returns the byte offset of the `&` starting the offending entity reference.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl std::error::Error for UnescapeError {}
+
+impl Debug for UnescapeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as Display>::fmt(self, f)
+    }
+}
+
+impl Display for UnescapeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid entity reference at byte offset {}", self.offset)
+    }
+}
+
+/// Unescapes an XML escaped string, failing on any entity reference that is not one of
+/// the predefined entities or a valid character reference.
+///
+/// # Errors
+///
+/// Returns an error pointing at the first `&` that does not start a valid entity reference.
+pub fn try_unescape(string: &str) -> Result<Cow<'_, str>, UnescapeError> {
+    let mut error = None;
+
+    let result = unescape_impl(string, |text| {
+        if error.is_some() {
+            return None;
+        }
+
+        let result = resolve_entity(text).map(|(chr, rest)| (Expansion::Char(chr), rest));
+        if result.is_none() {
+            error = Some(UnescapeError {
+                offset: text.as_ptr().addr() - string.as_ptr().addr() - 1,
+            });
+        }
+        result
+    });
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(result),
+    }
+}
+
+/// Like [`unescape_with`] but fails once more than `max_expansions` entities have been
+/// expanded or the replacement texts expanded so far exceed `max_size` bytes in total.
+///
+/// Only entities obtained from `resolver` count towards the limits, the predefined
+/// entities and character references are always expanded.
+///
+/// On failure the byte offset of the offending entity reference in `string` is returned.
+pub(crate) fn unescape_with_limits<'s>(
+    string: &'s str,
+    resolver: &dyn EntityResolver,
+    max_expansions: Option<usize>,
+    max_size: Option<usize>,
+) -> Result<Cow<'s, str>, usize> {
+    let mut expansions = 0;
+    let mut size = 0;
+    let mut exceeded = None;
+
+    let result = unescape_impl(string, |text| {
+        if exceeded.is_some() {
+            return None;
+        }
+
+        if let Some((chr, rest)) = resolve_entity(text) {
+            return Some((Expansion::Char(chr), rest));
+        }
+
+        let (name, rest) = text.split_once(';')?;
+        if name.is_empty() || name.bytes().any(|b| is_whitespace(b) || b == b'&') {
+            return None;
+        }
+
+        let replacement = resolver.resolve(name)?;
+        expansions += 1;
+        size += replacement.len();
+        if max_expansions.is_some_and(|max| expansions > max)
+            || max_size.is_some_and(|max| size > max)
+        {
+            exceeded = Some(text.as_ptr().addr() - string.as_ptr().addr() - 1);
+            return None;
+        }
+
+        Some((Expansion::Text(replacement), rest))
+    });
+
+    match exceeded {
+        Some(offset) => Err(offset),
+        None => Ok(result),
+    }
+}
+
 fn escape(string: &str, next: impl Fn(&str) -> Option<usize>) -> Cow<'_, str> {
     let mut replaced = String::new();
 
@@ -102,6 +312,10 @@ fn escape(string: &str, next: impl Fn(&str) -> Option<usize>) -> Cow<'_, str> {
             b'>' => replaced.push_str("&gt;"),
             b'&' => replaced.push_str("&amp;"),
             b'\"' => replaced.push_str("&quot;"),
+            b'\'' => replaced.push_str("&apos;"),
+            b'\t' => replaced.push_str("&#x9;"),
+            b'\n' => replaced.push_str("&#xA;"),
+            b'\r' => replaced.push_str("&#xD;"),
             _ => unreachable!(),
         };
         current = &current[escaped + 1..]
@@ -116,25 +330,259 @@ fn escape(string: &str, next: impl Fn(&str) -> Option<usize>) -> Cow<'_, str> {
 }
 
 /// Escapes the string so that it is a valid `"`-quoted attribute value.
-pub fn attribute_value_escape(string: &str) -> Cow<str> {
+pub fn attribute_value_escape(string: &str) -> Cow<'_, str> {
     escape(string, |text| {
         memchr::memchr3(b'<', b'&', b'"', text.as_bytes())
     })
 }
 
+/// Escapes the string so that it is a valid `'`-quoted attribute value.
+pub fn single_quoted_attribute_value_escape(string: &str) -> Cow<'_, str> {
+    escape(string, |text| {
+        memchr::memchr3(b'<', b'&', b'\'', text.as_bytes())
+    })
+}
+
 /// Escapes the string so that it is valid as a text node.
-pub fn content_escape(string: &str) -> Cow<str> {
+pub fn content_escape(string: &str) -> Cow<'_, str> {
     escape(string, |text| memchr::memchr2(b'<', b'&', text.as_bytes()))
 }
 
+/// Escapes the string as required for text nodes in Canonical XML, replacing `&`, `<`, `>`
+/// and carriage returns.
+pub fn canonical_content_escape(string: &str) -> Cow<'_, str> {
+    escape(string, |text| {
+        text.bytes()
+            .position(|b| matches!(b, b'&' | b'<' | b'>' | b'\r'))
+    })
+}
+
+/// Escapes the string as required for attribute values in Canonical XML, replacing `&`,
+/// `<`, `"`, tabs, line feeds and carriage returns.
+pub fn canonical_attribute_value_escape(string: &str) -> Cow<'_, str> {
+    escape(string, |text| {
+        text.bytes()
+            .position(|b| matches!(b, b'&' | b'<' | b'"' | b'\t' | b'\n' | b'\r'))
+    })
+}
+
+/// Escapes control characters as character references like `&#x1;`, as required for text
+/// and attribute values in XML 1.1 documents.
+///
+/// This covers all characters below `U+0020` except for tab, line feed, carriage return and
+/// the null character, as well as the characters from `U+007F` to `U+009F` except for
+/// `U+0085`.
+///
+/// # Errors
+///
+/// Returns an error if `string` contains a null character, which cannot be represented at all.
+pub fn control_escape(string: &str) -> Result<Cow<'_, str>, NullCharacterError> {
+    if let Some(offset) = string.find('\0') {
+        return Err(NullCharacterError { offset });
+    }
+
+    let is_restricted = |chr: char| matches!(chr, '\x01'..='\x08' | '\x0B' | '\x0C' | '\x0E'..='\x1F' | '\x7F'..='\u{84}' | '\u{86}'..='\u{9F}');
+
+    if !string.contains(is_restricted) {
+        return Ok(Cow::Borrowed(string));
+    }
+
+    let mut escaped = String::with_capacity(string.len() + 8);
+    for chr in string.chars() {
+        if is_restricted(chr) {
+            escaped.push_str(&format!("&#x{:X};", chr as u32));
+        } else {
+            escaped.push(chr);
+        }
+    }
+    Ok(Cow::Owned(escaped))
+}
+
+/// An error returned by [`control_escape`] when a string contains a null character.
+#[derive(Clone, PartialEq, Eq)]
+pub struct NullCharacterError {
+    offset: usize,
+}
+
+impl NullCharacterError {
+    /// Returns the byte offset of the null character.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl std::error::Error for NullCharacterError {}
+
+impl Debug for NullCharacterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as Display>::fmt(self, f)
+    }
+}
+
+impl Display for NullCharacterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "null character at byte offset {}", self.offset)
+    }
+}
+
+/// Escapes all characters above `U+007F` as character references like `&#xE9;`, so that the
+/// string only consists of ASCII characters.
+pub fn non_ascii_escape(string: &str) -> Cow<'_, str> {
+    if string.is_ascii() {
+        return Cow::Borrowed(string);
+    }
+
+    let mut escaped = String::with_capacity(string.len() + 8);
+    for chr in string.chars() {
+        if chr.is_ascii() {
+            escaped.push(chr);
+        } else {
+            escaped.push_str(&format!("&#x{:X};", chr as u32));
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+fn normalize_impl(string: &str, xml_1_1: bool, attribute: bool) -> Cow<'_, str> {
+    // NEL and LS start with 0xC2 and 0xE2 respectively.
+    let next = |text: &str| match (attribute, xml_1_1) {
+        (false, false) => memchr::memchr(b'\r', text.as_bytes()),
+        (false, true) => memchr::memchr3(b'\r', 0xC2, 0xE2, text.as_bytes()),
+        (true, _) => text.bytes().position(|b| match b {
+            b'\t' | b'\n' | b'\r' => true,
+            0xC2 | 0xE2 => xml_1_1,
+            _ => false,
+        }),
+    };
+    let replacement = if attribute { ' ' } else { '\n' };
+
+    let Some(first) = next(string) else {
+        return Cow::Borrowed(string);
+    };
+
+    let mut normalized = String::with_capacity(string.len());
+    normalized.push_str(&string[..first]);
+    let mut current = &string[first..];
+    loop {
+        let (newline, len) = if current.starts_with("\r\n") {
+            (true, 2)
+        } else if xml_1_1 && current.starts_with("\r\u{85}") {
+            (true, 3)
+        } else if current.starts_with('\r') {
+            (true, 1)
+        } else if current.starts_with('\u{85}') {
+            (true, 2)
+        } else if current.starts_with('\u{2028}') {
+            (true, 3)
+        } else if current.starts_with(['\t', '\n']) {
+            (true, 1)
+        } else {
+            (false, current.chars().next().unwrap().len_utf8())
+        };
+
+        if newline {
+            normalized.push(replacement);
+        } else {
+            normalized.push_str(&current[..len]);
+        }
+        current = &current[len..];
+
+        match next(current) {
+            Some(idx) => {
+                normalized.push_str(&current[..idx]);
+                current = &current[idx..];
+            }
+            None => break,
+        }
+    }
+    normalized.push_str(current);
+
+    Cow::Owned(normalized)
+}
+
+/// Normalizes line endings as required by the XML 1.0 specification, replacing `\r\n` and
+/// any `\r` not followed by `\n` with a single `\n`.
+pub fn normalize_line_endings(string: &str) -> Cow<'_, str> {
+    normalize_impl(string, false, false)
+}
+
+/// Normalizes line endings as required by the XML 1.1 specification.
+///
+/// In addition to the replacements done by [`normalize_line_endings`], `\r` followed by
+/// `U+0085`, a lone `U+0085` and `U+2028` are replaced with a single `\n`.
+pub fn normalize_line_endings_1_1(string: &str) -> Cow<'_, str> {
+    normalize_impl(string, true, false)
+}
+
+/// Normalizes an attribute value as required by the XML 1.0 specification for attributes
+/// without a declared type.
+///
+/// Line endings are normalized like with [`normalize_line_endings`] and every tab or line
+/// feed is then replaced with a space. This must be applied before unescaping, characters
+/// written as character references like `&#10;` are kept.
+pub fn normalize_attribute_value(string: &str) -> Cow<'_, str> {
+    normalize_impl(string, false, true)
+}
+
+/// Normalizes an attribute value as required by the XML 1.1 specification for attributes
+/// without a declared type.
+///
+/// Same as [`normalize_attribute_value`] but normalizes line endings like
+/// [`normalize_line_endings_1_1`].
+pub fn normalize_attribute_value_1_1(string: &str) -> Cow<'_, str> {
+    normalize_impl(string, true, true)
+}
+
 /// Escapes the string so that it is valid inside a comment.
-pub fn comment_escape(string: &str) -> Cow<str> {
+pub fn comment_escape(string: &str) -> Cow<'_, str> {
     escape(string, |text| memchr::memchr(b'>', text.as_bytes()))
 }
 
 #[cfg(test)]
 mod test {
-    use super::{content_escape, unescape};
+    use std::collections::HashMap;
+
+    use super::{
+        attribute_value_escape, canonical_attribute_value_escape, canonical_content_escape,
+        content_escape, control_escape, non_ascii_escape, normalize_attribute_value,
+        normalize_attribute_value_1_1, normalize_line_endings, normalize_line_endings_1_1,
+        single_quoted_attribute_value_escape, try_unescape, unescape, unescape_normalized,
+        unescape_with, unescape_with_limits,
+    };
+
+    #[test]
+    fn line_endings() {
+        const STRINGS: &[(&str, &str, &str)] = &[
+            ("a\r\nb\rc\n", "a\nb\nc\n", "a\nb\nc\n"),
+            ("\r\r\n\n\r", "\n\n\n\n", "\n\n\n\n"),
+            ("x\r\u{85}y\u{85}", "x\n\u{85}y\u{85}", "x\ny\n"),
+            ("\u{2028}é€\u{2029}", "\u{2028}é€\u{2029}", "\né€\u{2029}"),
+        ];
+
+        for (string, expected, expected_1_1) in STRINGS {
+            assert_eq!(normalize_line_endings(string), *expected);
+            assert_eq!(normalize_line_endings_1_1(string), *expected_1_1);
+        }
+
+        assert!(matches!(
+            normalize_line_endings("a\u{85}\nb"),
+            std::borrow::Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn attribute_values() {
+        const STRINGS: &[(&str, &str, &str)] = &[
+            ("a\tb\nc  d", "a b c  d", "a b c  d"),
+            ("\r\n\r\t&#10;", "   &#10;", "   &#10;"),
+            ("x\r\u{85}y\u{2028}", "x \u{85}y\u{2028}", "x y "),
+        ];
+
+        for (string, expected, expected_1_1) in STRINGS {
+            assert_eq!(normalize_attribute_value(string), *expected);
+            assert_eq!(normalize_attribute_value_1_1(string), *expected_1_1);
+        }
+    }
 
     #[test]
     fn simple_unescape_escape() {
@@ -157,5 +605,101 @@ mod test {
             assert_eq!(&unescaped, expected_unescaped);
             assert_eq!(&content_escape(&unescaped), expected_escaped);
         }
+
+        assert_eq!(
+            attribute_value_escape("\"a\" & 'b' <c>"),
+            "&quot;a&quot; &amp; 'b' &lt;c>"
+        );
+        assert_eq!(
+            single_quoted_attribute_value_escape("\"a\" & 'b' <c>"),
+            "\"a\" &amp; &apos;b&apos; &lt;c>"
+        );
+        assert_eq!(
+            canonical_content_escape("\"a\" & <b>\r\n\t"),
+            "\"a\" &amp; &lt;b&gt;&#xD;\n\t"
+        );
+        assert_eq!(
+            canonical_attribute_value_escape("\"a\" & <b>\r\n\t"),
+            "&quot;a&quot; &amp; &lt;b>&#xD;&#xA;&#x9;"
+        );
+    }
+
+    #[test]
+    fn control_characters() {
+        assert!(matches!(
+            control_escape("a\tb\u{85}"),
+            Ok(std::borrow::Cow::Borrowed(_))
+        ));
+        assert_eq!(control_escape("a\x01\u{9F}b").unwrap(), "a&#x1;&#x9F;b");
+        assert_eq!(unescape(&control_escape("\x1B[0m").unwrap()), "\x1B[0m");
+        assert_eq!(control_escape("a\x01\0").unwrap_err().offset(), 2);
+    }
+
+    #[test]
+    fn non_ascii_characters() {
+        assert!(matches!(
+            non_ascii_escape("a\x7F<b>"),
+            std::borrow::Cow::Borrowed(_)
+        ));
+        assert_eq!(non_ascii_escape("café ⭐!"), "caf&#xE9; &#x2B50;!");
+        assert_eq!(unescape(&non_ascii_escape("\u{80}😀")), "\u{80}😀");
+    }
+
+    #[test]
+    fn unescape_custom_entities() {
+        let mut entities = HashMap::new();
+        entities.insert("nbsp".to_owned(), "\u{a0}".to_owned());
+
+        assert_eq!(
+            unescape_with("a&nbsp;&amp;&#x62;&unknown; &nbsp", &entities),
+            "a\u{a0}&b&unknown; &nbsp"
+        );
+        assert_eq!(unescape("a&unknown;&lt;b"), "a&unknown;<b");
+    }
+
+    #[test]
+    fn expansion_limits() {
+        let mut entities = HashMap::new();
+        entities.insert("a".to_owned(), "aaaa".to_owned());
+
+        let text = "&a;&lt;&a;&#x20;&a;";
+        assert_eq!(
+            unescape_with_limits(text, &entities, Some(3), Some(12)).unwrap(),
+            "aaaa<aaaa aaaa"
+        );
+        assert_eq!(
+            unescape_with_limits(text, &entities, Some(2), None),
+            Err(16)
+        );
+        assert_eq!(unescape_with_limits(text, &entities, None, Some(7)), Err(7));
+    }
+
+    #[test]
+    fn strict_unescape() {
+        assert_eq!(try_unescape("a &amp; b").unwrap(), "a & b");
+        assert!(matches!(
+            try_unescape("plain"),
+            Ok(std::borrow::Cow::Borrowed("plain"))
+        ));
+        assert_eq!(try_unescape("&lt;&nbsp;").unwrap_err().offset(), 4);
+        assert_eq!(try_unescape("a & b").unwrap_err().offset(), 2);
+        assert_eq!(try_unescape("&#xD800;").unwrap_err().offset(), 0);
+    }
+
+    #[test]
+    fn normalized_unescape() {
+        let text = "a\r\n&lt;";
+        let unescaped = unescape_normalized(text, normalize_line_endings(text), try_unescape);
+        assert_eq!(unescaped.unwrap(), "a\n<");
+
+        let text = "a\r\nb";
+        let normalized = normalize_line_endings(text);
+        let ptr = normalized.as_ptr();
+        let unescaped = unescape_normalized(text, normalized, try_unescape).unwrap();
+        assert_eq!(unescaped.as_ptr(), ptr);
+
+        let text = "\r\n&x;";
+        let error = unescape_normalized(text, normalize_line_endings(text), try_unescape);
+        assert_eq!(error.unwrap_err().offset(), 2);
     }
 }
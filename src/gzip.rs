@@ -0,0 +1,208 @@
+//! Transparent decompression of gzip-compressed documents, enabled by the `flate2` feature.
+
+use std::io::{self, Write};
+
+use flate2::{read, write};
+
+use crate::reader::Options;
+
+/// Returns whether `bytes` start with the gzip magic bytes.
+fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x1F, 0x8B])
+}
+
+/// Collects decompressed bytes, failing once more than the remaining limit was written.
+struct Output {
+    bytes: Vec<u8>,
+    remaining: Option<usize>,
+}
+
+impl Output {
+    fn new(limit: Option<usize>) -> Self {
+        Self {
+            bytes: Vec::new(),
+            remaining: limit,
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.checked_sub(buf.len()).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "decompressed document exceeds the size limit",
+                )
+            })?;
+        }
+
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decompresses `bytes` if they are gzip-compressed, otherwise returns them unchanged.
+pub(crate) fn decompress(bytes: Vec<u8>, options: &Options) -> io::Result<Vec<u8>> {
+    if options.disable_gzip_detection || !is_gzip(&bytes) {
+        return Ok(bytes);
+    }
+
+    let mut output = Output::new(options.max_decompressed_size);
+    io::copy(&mut read::MultiGzDecoder::new(&bytes[..]), &mut output)?;
+    Ok(output.bytes)
+}
+
+enum State {
+    /// Fewer than two bytes were seen so far, they are kept in the buffer.
+    Detecting,
+    Plain,
+    Compressed(Box<write::MultiGzDecoder<Output>>),
+}
+
+/// Decompresses a document that arrives in chunks if it starts with the gzip magic bytes.
+pub(crate) struct Decompressor {
+    state: State,
+    buffer: Vec<u8>,
+    max_size: Option<usize>,
+}
+
+impl Decompressor {
+    pub(crate) fn new(options: &Options) -> Self {
+        Self {
+            state: if options.disable_gzip_detection {
+                State::Plain
+            } else {
+                State::Detecting
+            },
+            buffer: Vec::new(),
+            max_size: options.max_decompressed_size,
+        }
+    }
+
+    /// Processes the next chunk of `input` and returns the document bytes it produced.
+    pub(crate) fn process<'s>(&'s mut self, input: &'s [u8]) -> io::Result<&'s [u8]> {
+        if let State::Detecting = self.state {
+            self.buffer.extend_from_slice(input);
+            if self.buffer.len() < 2 {
+                return Ok(&[]);
+            }
+
+            if !is_gzip(&self.buffer) {
+                self.state = State::Plain;
+                return Ok(&self.buffer);
+            }
+
+            let mut decoder = write::MultiGzDecoder::new(Output::new(self.max_size));
+            decoder.write_all(&std::mem::take(&mut self.buffer))?;
+            self.state = State::Compressed(Box::new(decoder));
+            let State::Compressed(decoder) = &self.state else {
+                unreachable!()
+            };
+            return Ok(&decoder.get_ref().bytes);
+        }
+
+        match &mut self.state {
+            State::Detecting => unreachable!(),
+            State::Plain => Ok(input),
+            State::Compressed(decoder) => {
+                decoder.get_mut().bytes.clear();
+                decoder.write_all(input)?;
+                Ok(&decoder.get_ref().bytes)
+            }
+        }
+    }
+
+    /// Signals the end of the input and returns the remaining document bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressed stream was truncated or corrupted.
+    pub(crate) fn finish(&mut self) -> io::Result<&[u8]> {
+        if let State::Detecting = self.state {
+            self.state = State::Plain;
+            return Ok(&self.buffer);
+        }
+
+        match &mut self.state {
+            State::Detecting => unreachable!(),
+            State::Plain => Ok(&[]),
+            State::Compressed(decoder) => {
+                decoder.get_mut().bytes.clear();
+                decoder.try_finish()?;
+                Ok(&decoder.get_ref().bytes)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::{decompress, Decompressor};
+    use crate::reader::Options;
+
+    fn compress(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn detect() {
+        let options = Options::default();
+        let compressed = compress(b"<a/>");
+        assert_eq!(decompress(compressed.clone(), &options).unwrap(), b"<a/>");
+        assert_eq!(decompress(b"<b/>".to_vec(), &options).unwrap(), b"<b/>");
+
+        let mut decompressor = Decompressor::new(&options);
+        let mut output = Vec::new();
+        for byte in compressed.chunks(1) {
+            output.extend_from_slice(decompressor.process(byte).unwrap());
+        }
+        output.extend_from_slice(decompressor.finish().unwrap());
+        assert_eq!(output, b"<a/>");
+
+        let mut decompressor = Decompressor::new(&options);
+        assert_eq!(decompressor.process(b"<").unwrap(), b"");
+        assert_eq!(decompressor.finish().unwrap(), b"<");
+
+        let mut decompressor = Decompressor::new(&options);
+        decompressor
+            .process(&compressed[..compressed.len() - 4])
+            .unwrap();
+        assert!(decompressor.finish().is_err());
+
+        let options = Options::default().disable_gzip_detection(true);
+        assert_eq!(
+            decompress(compressed.clone(), &options).unwrap(),
+            compressed
+        );
+        let mut decompressor = Decompressor::new(&options);
+        assert_eq!(decompressor.process(&compressed).unwrap(), compressed);
+        assert_eq!(decompressor.finish().unwrap(), b"");
+    }
+
+    #[test]
+    fn size_limit() {
+        let document = vec![b' '; 64 * 1024];
+        let compressed = compress(&document);
+
+        let options = Options::default().max_decompressed_size(document.len());
+        assert_eq!(decompress(compressed.clone(), &options).unwrap(), document);
+
+        let options = Options::default().max_decompressed_size(document.len() - 1);
+        assert!(decompress(compressed.clone(), &options).is_err());
+        let mut decompressor = Decompressor::new(&options);
+        assert!(decompressor.process(&compressed).is_err() || decompressor.finish().is_err());
+        // Plain documents are not limited.
+        assert!(decompress(document.clone(), &options).is_ok());
+    }
+}
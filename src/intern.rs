@@ -0,0 +1,136 @@
+//! Interning of element and attribute names.
+//!
+//! An [`Interner`] maps every distinct name it sees to a small [`Symbol`], so consumers
+//! that dispatch on names or store them in many nodes can compare and copy integers
+//! instead of strings. Symbols for events are obtained with methods like
+//! [`StartEvent::name_symbol`](crate::reader::StartEvent::name_symbol).
+//!
+//! # Examples
+//!
+//! ```
+//! # use speedy_xml::{intern::Interner, reader::Event, Reader};
+//! let mut interner = Interner::new();
+//! let item = interner.intern("item");
+//!
+//! let mut items = 0;
+//! for event in Reader::new("<list><item/><other/><item></item></list>") {
+//!     if let Event::Start(start) | Event::Empty(start) = event.unwrap() {
+//!         if start.name_symbol(&mut interner) == item {
+//!             items += 1;
+//!         }
+//!     }
+//! }
+//! assert_eq!(items, 2);
+//! assert_eq!(interner.len(), 3);
+//! ```
+
+use std::{collections::HashMap, sync::Arc};
+
+/// A name interned by an [`Interner`].
+///
+/// Symbols are only meaningful for the interner that created them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Returns the index of this symbol, symbols are numbered from zero in the order their
+    /// names were first interned.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A cache mapping names to [`Symbol`]s.
+#[derive(Debug, Default, Clone)]
+pub struct Interner {
+    names: Vec<Arc<str>>,
+    symbols: HashMap<Arc<str>, Symbol>,
+}
+
+impl Interner {
+    /// Creates a new empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the symbol for `name`, allocating a new one if `name` was not interned before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than [`u32::MAX`] distinct names were interned.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+
+        let symbol = Symbol(u32::try_from(self.names.len()).expect("too many interned names"));
+        let name: Arc<str> = name.into();
+        self.names.push(name.clone());
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// Returns the symbol for `name` if it was interned before.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Returns the name that `symbol` was interned from.
+    ///
+    /// # Panics
+    ///
+    /// May panic if `symbol` was not created by this interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.index()]
+    }
+
+    /// Returns the number of distinct names interned so far.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns whether no names were interned yet.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Interner;
+    use crate::{reader::Event, Reader};
+
+    #[test]
+    fn intern_names() {
+        let mut interner = Interner::new();
+        assert!(interner.is_empty());
+        let a = interner.intern("a:x");
+        assert_eq!(interner.intern("a:x"), a);
+        assert_eq!(interner.get("b"), None);
+        assert_eq!(interner.resolve(a), "a:x");
+
+        let mut reader = Reader::new("<a:x a:x='1' y='2'></a:x>");
+        let Some(Ok(Event::Start(start))) = reader.next() else {
+            panic!()
+        };
+        assert_eq!(start.name_symbol(&mut interner), a);
+        let symbols = start
+            .attributes()
+            .map(|attr| attr.name_symbol(&mut interner))
+            .collect::<Vec<_>>();
+        assert_eq!(symbols[0], a);
+        assert_eq!(interner.resolve(symbols[1]), "y");
+        let Some(Ok(Event::End(end))) = reader.next() else {
+            panic!()
+        };
+        assert_eq!(end.name_symbol(&mut interner), a);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(symbols[1].index(), 1);
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Interner>();
+    }
+}
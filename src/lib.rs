@@ -2,10 +2,29 @@
 #![warn(missing_docs)]
 #![warn(clippy::missing_errors_doc)]
 
+pub mod adapter;
+#[cfg(feature = "async-tokio")]
+pub mod async_reader;
+pub mod dtd;
+pub mod encoding;
 pub mod escape;
+#[cfg(feature = "flate2")]
+mod gzip;
+pub mod intern;
 mod lut;
+#[cfg(feature = "memmap2")]
+pub mod mmap;
+pub mod namespace;
+pub mod push;
 pub mod reader;
+pub mod sax;
+#[cfg(feature = "bytes")]
+pub mod shared;
+pub mod stream;
+pub mod token;
 pub mod writer;
 
+pub use namespace::{NsReader, NsWriter};
+pub use push::PushParser;
 pub use reader::Reader;
 pub use writer::Writer;
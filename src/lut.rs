@@ -3,6 +3,8 @@ pub const RAPIDXML_WHITESPACE: &[u8] = b" \n\r\t";
 //       This is to implement prefixed names, and is not how RapidXML works.
 pub const RAPIDXML_INVALID_NAME: &[u8] = b" \n\r\t/>?\0:";
 pub const RAPIDXML_INVALID_ATTRNAME: &[u8] = b" \n\r\t/<>=?!\0:";
+// C0 control characters that may not appear in XML 1.0 documents.
+pub const RESTRICTED_CONTROL: &[u8] = b"\0\x01\x02\x03\x04\x05\x06\x07\x08\x0B\x0C\x0E\x0F\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19\x1A\x1B\x1C\x1D\x1E\x1F";
 
 const fn make_big_lut(values: &[u8]) -> [bool; 256] {
     let mut result = [false; 256];
@@ -31,6 +33,39 @@ pub fn is_invalid_attribute_name(chr: u8) -> bool {
     LUT[chr as usize]
 }
 
+pub fn is_restricted_control(chr: u8) -> bool {
+    const LUT: [bool; 256] = make_big_lut(RESTRICTED_CONTROL);
+    LUT[chr as usize]
+}
+
+/// Returns whether `chr` matches the `NameStartChar` production of the XML specification,
+/// excluding `:`.
+pub fn is_name_start_char(chr: char) -> bool {
+    matches!(chr,
+        'A'..='Z' | '_' | 'a'..='z' | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}'
+        | '\u{F8}'..='\u{2FF}' | '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}'
+        | '\u{200C}'..='\u{200D}' | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}'
+        | '\u{3001}'..='\u{D7FF}' | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{EFFFF}'
+    )
+}
+
+/// Returns whether `chr` matches the `NameChar` production of the XML specification,
+/// excluding `:`.
+pub fn is_name_char(chr: char) -> bool {
+    is_name_start_char(chr)
+        || matches!(chr,
+            '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}'
+        )
+}
+
+/// Returns whether `name` is a valid XML name without a colon, like each component of a
+/// prefixed name.
+pub fn is_ncname(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_name_start_char) && chars.all(is_name_char)
+}
+
 #[cfg(test)]
 mod test {
     fn test_lut_fn(truthy: &[u8], fun: impl Fn(u8) -> bool) {
@@ -56,4 +91,28 @@ mod test {
             super::is_invalid_attribute_name,
         );
     }
+
+    #[test]
+    fn is_ncname() {
+        for name in ["a", "_x-1.2", "données", "ñ·\u{301}", "日本語", "\u{10000}"] {
+            assert!(super::is_ncname(name), "{name}");
+        }
+        for name in [
+            "",
+            "1a",
+            "-a",
+            "a:b",
+            "a b",
+            "\u{D7}",
+            "a\u{2000}",
+            "\u{300}a",
+        ] {
+            assert!(!super::is_ncname(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn is_restricted_control() {
+        test_lut_fn(super::RESTRICTED_CONTROL, super::is_restricted_control);
+    }
 }
@@ -0,0 +1,99 @@
+//! Parsing of memory-mapped files.
+//!
+//! Mapping a file lets the reader parse it without first reading it into memory, which
+//! is useful for very large documents. This module is only available with the `memmap2`
+//! feature.
+
+use std::{borrow::Cow, fs::File, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{encoding, push::ReadError};
+
+/// A document backed by a memory-mapped file.
+///
+/// Documents in UTF-8 are parsed directly from the mapping, documents in other encodings
+/// are decoded into memory when the file is opened.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use speedy_xml::{mmap::MappedDocument, reader::Options, Reader};
+/// // SAFETY: The file is not modified while it is mapped.
+/// let document = unsafe { MappedDocument::open("large.xml") }.unwrap();
+/// for event in Reader::from_mmap(&document, Options::default()) {
+///     println!("{:?}", event.unwrap());
+/// }
+/// ```
+pub struct MappedDocument {
+    map: Mmap,
+    /// The position in the mapping where the text starts, after any byte order mark.
+    start: usize,
+    /// The decoded document if it was not in UTF-8.
+    decoded: Option<String>,
+}
+
+impl MappedDocument {
+    /// Maps the file at `path` and checks that it is valid in its detected encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be opened or mapped, or if its contents are
+    /// not valid in their detected encoding.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, by this or any other process, for as long as the
+    /// returned document exists. See [`Mmap::map`] for details.
+    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Self, ReadError> {
+        let file = File::open(path)?;
+        // SAFETY: Upheld by the caller.
+        let map = unsafe { Mmap::map(&file)? };
+        let (start, decoded) = match encoding::decode(&map)? {
+            Cow::Borrowed(text) => (text.as_ptr().addr() - map.as_ptr().addr(), None),
+            Cow::Owned(text) => (0, Some(text)),
+        };
+
+        Ok(Self {
+            map,
+            start,
+            decoded,
+        })
+    }
+
+    /// Returns the text of this document.
+    pub fn text(&self) -> &str {
+        match &self.decoded {
+            Some(text) => text,
+            // SAFETY: The mapping was validated in open and the caller guarantees that
+            //         it is not modified afterwards.
+            None => unsafe { std::str::from_utf8_unchecked(&self.map[self.start..]) },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MappedDocument;
+    use crate::{reader::Options, Reader};
+
+    #[test]
+    fn from_mmap() {
+        let path = std::env::temp_dir().join(format!("speedy-xml-mmap-{}.xml", std::process::id()));
+        std::fs::write(&path, "\u{FEFF}<root>text</root>").unwrap();
+
+        // SAFETY: The file is only modified after the document is dropped.
+        let document = unsafe { MappedDocument::open(&path) }.unwrap();
+        assert_eq!(document.text(), "<root>text</root>");
+        assert_eq!(Reader::from_mmap(&document, Options::default()).count(), 3);
+        drop(document);
+
+        std::fs::write(&path, b"\xFF\xFE<\0a\0/\0>\0").unwrap();
+        // SAFETY: See above.
+        let document = unsafe { MappedDocument::open(&path) }.unwrap();
+        assert_eq!(document.text(), "<a/>");
+        drop(document);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,796 @@
+//! Namespace-aware wrappers around [`Reader`] and [`Writer`].
+//!
+//! [`NsReader`] tracks `xmlns` and `xmlns:*` declarations as it reads events and
+//! allows resolving element and attribute prefixes to their namespace URIs.
+//!
+//! [`NsWriter`] does the opposite, it writes elements and attributes by namespace URI
+//! and picks prefixes from the declarations in scope.
+
+use std::{
+    borrow::Cow,
+    io::Write,
+    iter::FusedIterator,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    lut::is_invalid_name,
+    reader::{AttributeEvent, Error, ErrorKind, Event, Options, Reader, StartEvent},
+    writer::{self, Writer},
+};
+
+/// The namespace URI permanently bound to the `xml` prefix.
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+/// The namespace URI permanently bound to the `xmlns` prefix.
+pub const XMLNS_NAMESPACE: &str = "http://www.w3.org/2000/xmlns/";
+
+struct Binding<'a> {
+    prefix: Option<&'a str>,
+    uri: Cow<'a, str>,
+}
+
+/// An XML reader that keeps track of namespace declarations.
+///
+/// Bindings declared on a start tag stay in scope until after its matching end event
+/// has been returned, so the names of both the start and end events can be resolved
+/// while handling them.
+pub struct NsReader<'a> {
+    reader: Reader<'a>,
+    bindings: Vec<Binding<'a>>,
+    scopes: Vec<usize>,
+    pop_pending: bool,
+}
+
+impl<'a> NsReader<'a> {
+    /// Creates a new namespace-resolving reader that will parse the contents of `text`.
+    pub fn new(text: &'a str) -> Self {
+        Self::from_reader(Reader::new(text))
+    }
+
+    /// Creates a new namespace-resolving reader that will parse the contents of `text` with the provided [`Options`].
+    pub fn with_options(text: &'a str, options: Options) -> Self {
+        Self::from_reader(Reader::with_options(text, options))
+    }
+
+    /// Wraps an existing [`Reader`].
+    ///
+    /// # Notes
+    ///
+    /// Declarations made by elements that were already read from `reader` are not known.
+    pub fn from_reader(reader: Reader<'a>) -> Self {
+        Self {
+            reader,
+            bindings: Vec::new(),
+            scopes: Vec::new(),
+            pop_pending: false,
+        }
+    }
+
+    /// Returns a reference to the underlying [`Reader`].
+    pub fn reader(&self) -> &Reader<'a> {
+        &self.reader
+    }
+
+    /// Returns the underlying [`Reader`].
+    pub fn into_inner(self) -> Reader<'a> {
+        self.reader
+    }
+
+    fn lookup(&self, prefix: Option<&str>) -> Option<&str> {
+        match prefix {
+            Some("xml") => return Some(XML_NAMESPACE),
+            Some("xmlns") => return Some(XMLNS_NAMESPACE),
+            _ => (),
+        }
+
+        self.bindings
+            .iter()
+            .rev()
+            .find(|binding| binding.prefix == prefix)
+            .map(|binding| binding.uri.as_ref())
+            .filter(|uri| !uri.is_empty())
+    }
+
+    /// Resolves an element `prefix` to the namespace URI currently bound to it.
+    ///
+    /// Unprefixed elements are resolved to the default namespace, if one is declared.
+    pub fn resolve_element(&self, prefix: Option<&str>) -> Option<&str> {
+        self.lookup(prefix)
+    }
+
+    /// Resolves an attribute `prefix` to the namespace URI currently bound to it.
+    ///
+    /// Unlike elements, unprefixed attributes are never in any namespace.
+    pub fn resolve_attribute(&self, prefix: Option<&str>) -> Option<&str> {
+        prefix.and_then(|prefix| self.lookup(Some(prefix)))
+    }
+
+    /// Returns the first attribute of `start` with the local name `name` in the namespace
+    /// `namespace`, resolving prefixes with the bindings currently in scope.
+    ///
+    /// Unprefixed attributes are only matched if `namespace` is `None`.
+    pub fn get_attribute(
+        &self,
+        start: &StartEvent<'a>,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> Option<AttributeEvent<'a>> {
+        start.attributes().find(|attr| {
+            attr.local_name() == name && self.resolve_attribute(attr.prefix()) == namespace
+        })
+    }
+
+    /// Skips events up to and including the next start or empty tag with the local name
+    /// `name` in the namespace `namespace` and returns its event.
+    ///
+    /// Returns `Ok(None)` if the end of the document was reached without finding a
+    /// matching element.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parse error occurred.
+    pub fn find_element(
+        &mut self,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> Result<Option<Event<'a>>, Error> {
+        while let Some(event) = self.next().transpose()? {
+            if let Event::Start(start) | Event::Empty(start) = event {
+                if start.name() == name && self.resolve_element(start.prefix()) == namespace {
+                    return Ok(Some(event));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn push_scope(&mut self, start: &StartEvent<'a>) {
+        self.scopes.push(self.bindings.len());
+
+        for attr in start.attributes() {
+            let prefix = match (attr.prefix(), attr.local_name()) {
+                (None, "xmlns") => None,
+                (Some("xmlns"), name) => Some(name),
+                _ => continue,
+            };
+
+            self.bindings.push(Binding {
+                prefix,
+                uri: attr.value(),
+            });
+        }
+    }
+
+    /// Returns an error for the first name in `start` whose prefix is not bound to a namespace.
+    fn check_prefixes(&self, start: &StartEvent<'a>) -> Result<(), Error> {
+        if let Some(prefix) = start.prefix() {
+            if self.lookup(Some(prefix)).is_none() {
+                let span = start.prefix_position_in(&self.reader).unwrap().start
+                    ..start.name_position_in(&self.reader).end;
+                return Err(Error::new(ErrorKind::UndeclaredPrefix, span));
+            }
+        }
+
+        for attr in start.attributes() {
+            if attr.prefix().is_some() && self.resolve_attribute(attr.prefix()).is_none() {
+                let span = attr.name_position_in(&self.reader);
+                return Err(Error::new(ErrorKind::UndeclaredPrefix, span));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(len) = self.scopes.pop() {
+            self.bindings.truncate(len);
+        }
+    }
+}
+
+impl<'a> Iterator for NsReader<'a> {
+    type Item = Result<Event<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if std::mem::take(&mut self.pop_pending) {
+            self.pop_scope();
+        }
+
+        let result = self.reader.next();
+        let start = match result {
+            Some(Ok(Event::Start(start))) => {
+                self.push_scope(&start);
+                start
+            }
+            Some(Ok(Event::Empty(start))) => {
+                self.push_scope(&start);
+                self.pop_pending = true;
+                start
+            }
+            Some(Ok(Event::End(_))) => {
+                self.pop_pending = true;
+                return result;
+            }
+            _ => return result,
+        };
+
+        if self.reader.forbids_undeclared_prefixes() {
+            if let Err(error) = self.check_prefixes(&start) {
+                self.reader.set_error_state();
+                return Some(Err(error));
+            }
+        }
+
+        result
+    }
+}
+
+impl FusedIterator for NsReader<'_> {}
+
+struct WriterBinding {
+    prefix: Option<String>,
+    uri: String,
+    /// The number of open elements while this binding is in scope, one more than the
+    /// number of open elements outside of it for empty elements.
+    depth: usize,
+}
+
+/// An XML writer that keeps track of namespace declarations.
+///
+/// Namespaces are declared with [`NsWriter::declare`] before the element they should be
+/// declared on, elements and attributes are then written by their namespace URI and local
+/// name. Namespaces without a prefix in scope are bound to a generated prefix like `ns1`
+/// on the element they are used on.
+///
+/// Other content is written through the underlying [`Writer`], which this type
+/// dereferences to. Elements written directly with it do not declare any namespaces and
+/// are in the default namespace that is in scope.
+///
+/// # Examples
+///
+/// ```
+/// # use speedy_xml::NsWriter;
+/// let mut writer = NsWriter::new(Vec::new());
+/// writer.declare(None, "urn:items").unwrap();
+/// writer.declare(Some("m"), "urn:meta").unwrap();
+/// writer.write_start(Some("urn:items"), "list").unwrap();
+/// writer.write_empty(Some("urn:meta"), "info").unwrap();
+/// writer.write_attribute(Some("urn:meta"), "id", "1").unwrap();
+/// writer.write_empty(None, "plain").unwrap();
+/// writer.write_end().unwrap();
+/// assert_eq!(
+///     writer.finish().unwrap(),
+///     concat!(
+///         r#"<list xmlns="urn:items" xmlns:m="urn:meta">"#,
+///         r#"<m:info m:id="1"/><plain xmlns=""/></list>"#
+///     )
+///     .as_bytes()
+/// );
+/// ```
+pub struct NsWriter<W: Write> {
+    writer: Writer<W>,
+    bindings: Vec<WriterBinding>,
+    /// Declarations that will be made on the next element.
+    pending: Vec<(Option<String>, String)>,
+    /// The number of the last generated prefix.
+    generated: u32,
+}
+
+impl<W: Write> NsWriter<W> {
+    /// Creates a new namespace-aware writer that will write into `writer`.
+    pub fn new(writer: W) -> Self {
+        Self::from_writer(Writer::new(writer))
+    }
+
+    /// Creates a new namespace-aware writer that will write into `writer` with the specified options.
+    pub fn with_options(writer: W, options: writer::Options) -> Self {
+        Self::from_writer(Writer::with_options(writer, options))
+    }
+
+    /// Wraps an existing [`Writer`].
+    ///
+    /// # Notes
+    ///
+    /// Elements that are already open in `writer` do not declare any namespaces.
+    pub fn from_writer(writer: Writer<W>) -> Self {
+        Self {
+            writer,
+            bindings: Vec::new(),
+            pending: Vec::new(),
+            generated: 0,
+        }
+    }
+
+    /// Returns the underlying [`Writer`].
+    pub fn into_inner(self) -> Writer<W> {
+        self.writer
+    }
+
+    /// Finishes the underlying [`Writer`] and returns what it wrote into, see [`Writer::finish`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an underlying I/O error occurred.
+    pub fn finish(self) -> std::io::Result<W> {
+        self.writer.finish()
+    }
+
+    /// Drops the bindings of elements that are not open anymore, `depth` is the number of
+    /// elements whose bindings are still in scope.
+    fn pop_scopes(&mut self, depth: usize) {
+        while self.bindings.last().is_some_and(|b| b.depth > depth) {
+            self.bindings.pop();
+        }
+    }
+
+    /// Returns the namespace URIs bound to prefixes, from the innermost binding outwards.
+    fn scope<'s>(
+        pending: &'s [(Option<String>, String)],
+        bindings: &'s [WriterBinding],
+    ) -> impl Iterator<Item = (Option<&'s str>, &'s str)> + Clone {
+        pending
+            .iter()
+            .rev()
+            .map(|(prefix, uri)| (prefix.as_deref(), uri.as_str()))
+            .chain(
+                bindings
+                    .iter()
+                    .rev()
+                    .map(|binding| (binding.prefix.as_deref(), binding.uri.as_str())),
+            )
+    }
+
+    /// Returns a prefix in `scope` bound to `uri` that is not shadowed by an inner binding.
+    fn find_prefix<'s>(
+        scope: impl Iterator<Item = (Option<&'s str>, &'s str)> + Clone,
+        uri: &str,
+        attribute: bool,
+    ) -> Option<Option<&'s str>> {
+        if uri == XML_NAMESPACE {
+            return Some(Some("xml"));
+        }
+
+        scope
+            .clone()
+            .enumerate()
+            .find(|&(index, (prefix, bound))| {
+                // Unprefixed attributes are never in the default namespace.
+                bound == uri
+                    && (prefix.is_some() || !attribute)
+                    && scope.clone().take(index).all(|(other, _)| other != prefix)
+            })
+            .map(|(_, (prefix, _))| prefix)
+    }
+
+    /// Returns a new prefix like `ns1` that is not bound in the current scope.
+    fn generate_prefix(&mut self) -> String {
+        loop {
+            self.generated += 1;
+            let prefix = format!("ns{}", self.generated);
+            if Self::scope(&self.pending, &self.bindings).all(|(other, _)| other != Some(&prefix)) {
+                return prefix;
+            }
+        }
+    }
+
+    /// Declares `prefix` as bound to the namespace `uri` on the next element written, or
+    /// the default namespace if `prefix` is `None`.
+    ///
+    /// Declarations that are already in scope are not repeated, the default namespace can
+    /// be undeclared by binding it to an empty URI.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the prefix is invalid, the binding is reserved, like the
+    /// `xmlns` prefix, or `prefix` would be bound to an empty URI.
+    pub fn declare(&mut self, prefix: Option<&str>, uri: &str) -> Result<(), writer::Error> {
+        let invalid = match prefix {
+            Some("xml") => uri != XML_NAMESPACE,
+            Some("xmlns") => true,
+            Some(prefix) => {
+                prefix.is_empty()
+                    || self.writer.is_invalid(prefix, is_invalid_name)
+                    || uri.is_empty()
+                    || uri == XML_NAMESPACE
+            }
+            None => uri == XML_NAMESPACE,
+        };
+        if invalid || uri == XMLNS_NAMESPACE {
+            return Err(writer::Error::InvalidNamespace);
+        }
+
+        self.pending.retain(|(other, _)| other.as_deref() != prefix);
+
+        // Bindings of an empty tag that is still being written are not in scope of the next element.
+        let depth = self.writer.open_elements();
+        let bound = self
+            .bindings
+            .iter()
+            .rev()
+            .filter(|binding| binding.depth <= depth)
+            .find(|binding| binding.prefix.as_deref() == prefix)
+            .map(|binding| binding.uri.as_str());
+        let in_scope = match bound {
+            Some(bound) => bound == uri,
+            None => (prefix.is_none() && uri.is_empty()) || prefix == Some("xml"),
+        };
+        if !in_scope {
+            self.pending
+                .push((prefix.map(str::to_owned), uri.to_owned()));
+        }
+
+        Ok(())
+    }
+
+    fn write_start_tag(
+        &mut self,
+        namespace: Option<&str>,
+        name: &str,
+        empty: bool,
+    ) -> Result<(), writer::Error> {
+        self.pop_scopes(self.writer.open_elements());
+
+        let namespace = namespace.filter(|uri| !uri.is_empty());
+        if let Some(uri) = namespace {
+            if Self::find_prefix(Self::scope(&self.pending, &self.bindings), uri, false).is_none() {
+                let prefix = self.generate_prefix();
+                self.pending.push((Some(prefix), uri.to_owned()));
+            }
+        }
+        let prefix = namespace
+            .and_then(|uri| {
+                Self::find_prefix(Self::scope(&self.pending, &self.bindings), uri, false)
+            })
+            .flatten();
+
+        // Elements in no namespace have to undeclare the default namespace.
+        let undeclare = namespace.is_none()
+            && Self::scope(&self.pending, &self.bindings)
+                .find(|(prefix, _)| prefix.is_none())
+                .is_some_and(|(_, uri)| !uri.is_empty());
+        if undeclare && self.pending.iter().any(|(prefix, _)| prefix.is_none()) {
+            return Err(writer::Error::InvalidNamespace);
+        }
+
+        if empty {
+            self.writer.write_empty(prefix, name)?;
+        } else {
+            self.writer.write_start(prefix, name)?;
+        }
+
+        if undeclare {
+            self.pending.push((None, String::new()));
+        }
+
+        let depth = self.writer.open_elements() + usize::from(empty);
+        for (prefix, uri) in self.pending.drain(..) {
+            match &prefix {
+                Some(prefix) => {
+                    self.writer
+                        .write_prefixed_attribute(Some("xmlns"), prefix, &uri)?;
+                }
+                None => self.writer.write_attribute("xmlns", &uri)?,
+            }
+            self.bindings.push(WriterBinding { prefix, uri, depth });
+        }
+
+        Ok(())
+    }
+
+    /// Writes a start tag for the element `name` in the namespace `namespace`, declaring
+    /// the namespaces passed to [`Self::declare`] on it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is invalid, an element in no namespace is written while
+    /// a default namespace is declared on it or an underlying I/O error occurs.
+    pub fn write_start(
+        &mut self,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> Result<(), writer::Error> {
+        self.write_start_tag(namespace, name, false)
+    }
+
+    /// Writes an empty tag for the element `name` in the namespace `namespace`, declaring
+    /// the namespaces passed to [`Self::declare`] on it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is invalid, an element in no namespace is written while
+    /// a default namespace is declared on it or an underlying I/O error occurs.
+    pub fn write_empty(
+        &mut self,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> Result<(), writer::Error> {
+        self.write_start_tag(namespace, name, true)
+    }
+
+    /// Writes the attribute `name` in the namespace `namespace` into the current start tag,
+    /// see [`Writer::write_attribute`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is invalid, the writer is not in a start tag or an
+    /// underlying I/O error occurs.
+    pub fn write_attribute(
+        &mut self,
+        namespace: Option<&str>,
+        name: &str,
+        value: &str,
+    ) -> Result<(), writer::Error> {
+        // Check the name first so that no declaration is left behind if it is invalid.
+        self.writer.check_attribute(None, name)?;
+
+        let depth = self.writer.open_elements() + usize::from(self.writer.in_empty_tag());
+        self.pop_scopes(depth);
+
+        let namespace = namespace.filter(|uri| !uri.is_empty());
+        if let Some(uri) = namespace {
+            if Self::find_prefix(Self::scope(&[], &self.bindings), uri, true).is_none() {
+                let prefix = self.generate_prefix();
+                self.writer
+                    .write_prefixed_attribute(Some("xmlns"), &prefix, uri)?;
+                self.bindings.push(WriterBinding {
+                    prefix: Some(prefix),
+                    uri: uri.to_owned(),
+                    depth,
+                });
+            }
+        }
+        let prefix = namespace
+            .and_then(|uri| Self::find_prefix(Self::scope(&[], &self.bindings), uri, true))
+            .flatten();
+
+        self.writer.write_prefixed_attribute(prefix, name, value)
+    }
+
+    /// Writes an end tag for the most recently opened element and drops the namespaces
+    /// declared on it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no element is open or an underlying I/O error occurs.
+    pub fn write_end(&mut self) -> Result<(), writer::Error> {
+        self.writer.write_end_auto()?;
+        self.pop_scopes(self.writer.open_elements());
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Deref for NsWriter<W> {
+    type Target = Writer<W>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.writer
+    }
+}
+
+impl<W: Write> DerefMut for NsWriter<W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.writer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NsReader, NsWriter, XML_NAMESPACE};
+    use crate::{
+        reader::{ErrorKind, Event, Options},
+        writer,
+    };
+
+    #[test]
+    fn resolve_scopes() {
+        let mut reader = NsReader::new(concat!(
+            r#"<root xmlns="urn:default" xmlns:a="urn:a">"#,
+            r#"<a:child a:attr="1" plain="2"><inner xmlns="" xmlns:a="urn:b"/></a:child>"#,
+            r#"</root>"#
+        ));
+
+        let Some(Ok(Event::Start(root))) = reader.next() else {
+            panic!()
+        };
+        assert_eq!(reader.resolve_element(root.prefix()), Some("urn:default"));
+
+        let Some(Ok(Event::Start(child))) = reader.next() else {
+            panic!()
+        };
+        assert_eq!(reader.resolve_element(child.prefix()), Some("urn:a"));
+        let mut attributes = child.attributes();
+        let attr = attributes.next().unwrap();
+        assert_eq!(reader.resolve_attribute(attr.prefix()), Some("urn:a"));
+        let attr = attributes.next().unwrap();
+        assert_eq!(reader.resolve_attribute(attr.prefix()), None);
+        assert_eq!(
+            reader
+                .get_attribute(&child, Some("urn:a"), "attr")
+                .unwrap()
+                .raw_value(),
+            "1"
+        );
+        assert!(reader.get_attribute(&child, None, "attr").is_none());
+        assert!(reader
+            .get_attribute(&child, Some("urn:default"), "plain")
+            .is_none());
+        assert!(reader.get_attribute(&child, None, "plain").is_some());
+
+        let Some(Ok(Event::Empty(inner))) = reader.next() else {
+            panic!()
+        };
+        assert_eq!(reader.resolve_element(inner.prefix()), None);
+        assert_eq!(reader.resolve_element(Some("a")), Some("urn:b"));
+
+        let Some(Ok(Event::End(end))) = reader.next() else {
+            panic!()
+        };
+        assert_eq!(reader.resolve_element(end.prefix()), Some("urn:a"));
+
+        let Some(Ok(Event::End(end))) = reader.next() else {
+            panic!()
+        };
+        assert_eq!(reader.resolve_element(end.prefix()), Some("urn:default"));
+        assert_eq!(
+            reader.resolve_element(Some("xml")),
+            Some(super::XML_NAMESPACE)
+        );
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn undeclared_prefixes() {
+        let options = Options::default().forbid_undeclared_prefixes(true);
+        let mut reader = NsReader::with_options(
+            r#"<root xmlns:a="urn:a" xml:lang="en"><a:x a:y="1"/><b:x/></root>"#,
+            options.clone(),
+        );
+
+        assert!(matches!(reader.next(), Some(Ok(Event::Start(_)))));
+        assert!(matches!(reader.next(), Some(Ok(Event::Empty(_)))));
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UndeclaredPrefix);
+        assert_eq!(error.span(), 51..54);
+        assert!(reader.next().is_none());
+
+        let mut reader = NsReader::with_options(r#"<root b:y="1"/>"#, options);
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.span(), 6..9);
+
+        // Undeclared prefixes are ignored by default.
+        assert!(NsReader::new("<b:x/>").all(|result| result.is_ok()));
+    }
+
+    #[test]
+    fn find_element() {
+        let mut reader = NsReader::new(concat!(
+            r#"<root xmlns:a="urn:a" xmlns:b="urn:b">"#,
+            r#"<b:item n="1"/><a:item n="2"/><item xmlns="urn:b" n="3"/>"#,
+            r#"</root>"#
+        ));
+
+        let find = |reader: &mut NsReader, namespace| match reader.find_element(namespace, "item") {
+            Ok(Some(Event::Empty(item))) => {
+                Some(item.attributes().last().unwrap().raw_value().to_owned())
+            }
+            Ok(None) => None,
+            event => panic!("{event:?}"),
+        };
+
+        assert_eq!(find(&mut reader, Some("urn:b")).as_deref(), Some("1"));
+        assert_eq!(find(&mut reader, Some("urn:b")).as_deref(), Some("3"));
+        assert_eq!(find(&mut reader, Some("urn:b")), None);
+    }
+
+    #[test]
+    fn write_namespaces() {
+        let mut writer = NsWriter::new(Vec::new());
+        writer.declare(None, "urn:default").unwrap();
+        writer.declare(Some("a"), "urn:a").unwrap();
+        writer.write_start(Some("urn:default"), "root").unwrap();
+        writer
+            .write_attribute(Some(XML_NAMESPACE), "lang", "en")
+            .unwrap();
+
+        // Redeclaring a binding that is in scope does nothing.
+        writer.declare(Some("a"), "urn:a").unwrap();
+        writer.declare(Some("b"), "urn:a").unwrap();
+        writer.write_empty(Some("urn:a"), "child").unwrap();
+        writer.write_attribute(Some("urn:a"), "attr", "1").unwrap();
+        writer.write_attribute(None, "plain", "2").unwrap();
+
+        // The prefix `b` went out of scope with the empty element.
+        writer.declare(Some("a"), "urn:b").unwrap();
+        writer.write_start(None, "inner").unwrap();
+        writer.write_text("text").unwrap();
+        writer.write_end().unwrap();
+
+        writer.write_empty(Some("urn:a"), "last").unwrap();
+        writer.write_end().unwrap();
+        assert!(writer.write_end().is_err());
+
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            concat!(
+                r#"<root xmlns="urn:default" xmlns:a="urn:a" xml:lang="en">"#,
+                r#"<b:child xmlns:b="urn:a" b:attr="1" plain="2"/>"#,
+                r#"<inner xmlns:a="urn:b" xmlns="">text</inner>"#,
+                r#"<a:last/></root>"#
+            )
+        );
+
+        let mut reader = NsReader::new(&output);
+        let mut names = Vec::new();
+        while let Some(event) = reader.next() {
+            if let Event::Start(start) | Event::Empty(start) = event.unwrap() {
+                names.push((
+                    reader.resolve_element(start.prefix()).map(str::to_owned),
+                    start.name(),
+                ));
+            }
+        }
+        assert_eq!(
+            names,
+            [
+                (Some("urn:default".to_owned()), "root"),
+                (Some("urn:a".to_owned()), "child"),
+                (None, "inner"),
+                (Some("urn:a".to_owned()), "last"),
+            ]
+        );
+
+        let mut writer = NsWriter::new(Vec::new());
+        for (prefix, uri) in [
+            (Some("xmlns"), "urn:a"),
+            (Some("xml"), "urn:a"),
+            (Some("a"), ""),
+            (Some("a b"), "urn:a"),
+            (None, XML_NAMESPACE),
+            (None, super::XMLNS_NAMESPACE),
+        ] {
+            assert!(matches!(
+                writer.declare(prefix, uri),
+                Err(writer::Error::InvalidNamespace)
+            ));
+        }
+        writer.declare(None, "urn:a").unwrap();
+        assert!(matches!(
+            writer.write_start(None, "a"),
+            Err(writer::Error::InvalidNamespace)
+        ));
+        assert!(writer.into_inner().finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn generate_prefixes() {
+        let mut writer = NsWriter::new(Vec::new());
+        writer.declare(None, "urn:default").unwrap();
+        writer.declare(Some("ns2"), "urn:taken").unwrap();
+        writer.write_start(Some("urn:a"), "root").unwrap();
+        writer
+            .write_attribute(Some("urn:default"), "x", "1")
+            .unwrap();
+        writer.write_attribute(Some("urn:a"), "y", "2").unwrap();
+        writer.write_empty(Some("urn:b"), "child").unwrap();
+        assert!(matches!(
+            writer.write_attribute(Some("urn:c"), "bad name", "v"),
+            Err(writer::Error::InvalidAttributeName)
+        ));
+        writer.write_attribute(Some("urn:b"), "z", "3").unwrap();
+        writer.write_empty(Some("urn:default"), "child").unwrap();
+        writer.write_attribute(Some("urn:b"), "z", "4").unwrap();
+        writer.write_end().unwrap();
+        assert_eq!(
+            String::from_utf8(writer.finish().unwrap()).unwrap(),
+            concat!(
+                r#"<ns1:root xmlns="urn:default" xmlns:ns2="urn:taken" xmlns:ns1="urn:a" "#,
+                r#"xmlns:ns3="urn:default" ns3:x="1" ns1:y="2">"#,
+                r#"<ns4:child xmlns:ns4="urn:b" ns4:z="3"/>"#,
+                r#"<ns3:child xmlns:ns5="urn:b" ns5:z="4"/></ns1:root>"#
+            )
+        );
+    }
+}
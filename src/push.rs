@@ -0,0 +1,709 @@
+//! An incremental reader that is fed its input in chunks.
+//!
+//! [`PushParser`] is useful when the document is not available as a single contiguous
+//! buffer, for example when it is received over the network. Input is accumulated
+//! internally and events are produced as soon as they are complete.
+
+use std::{
+    fmt::{Debug, Display},
+    ops::Range,
+};
+
+use crate::{
+    encoding::DecodeError,
+    reader::{Error, Event, Options, Reader, State},
+};
+
+/// An XML reader that is fed input incrementally.
+///
+/// Input is provided as UTF-8 bytes through [`Self::feed`], chunk boundaries may fall
+/// anywhere, including in the middle of a character. Once all input has been fed,
+/// [`Self::finish`] has to be called so that trailing text and unclosed elements can be
+/// reported.
+///
+/// Spans of returned errors are relative to the start of all fed input. After an error,
+/// including a decoding error returned by [`Self::feed`] or [`Self::finish`], no more
+/// events are produced.
+///
+/// # Notes
+///
+/// When text coalescing is enabled, text that is split by the end of the currently
+/// available input right after a cdata section may be emitted as multiple events.
+///
+/// # Examples
+///
+/// ```
+/// # use speedy_xml::{push::PushParser, reader::Event};
+/// let mut parser = PushParser::new();
+/// let mut names = Vec::new();
+///
+/// for chunk in [&b"<root><chi"[..], b"ld/>text", b"</root>"] {
+///     parser.feed(chunk).unwrap();
+///     while let Some(event) = parser.next_event() {
+///         if let Event::Empty(start) = event.unwrap() {
+///             names.push(start.name().to_owned());
+///         }
+///     }
+/// }
+///
+/// parser.finish().unwrap();
+/// assert!(parser.next_event().is_none());
+/// assert_eq!(names, ["child"]);
+/// ```
+pub struct PushParser {
+    text: String,
+    /// Bytes at the end of the fed input that do not form a complete character yet.
+    pending: Vec<u8>,
+    state: Option<State>,
+    finished: bool,
+    errors: Vec<Error>,
+}
+
+impl PushParser {
+    /// Creates a new push parser.
+    pub fn new() -> Self {
+        Self::with_options(Options::default())
+    }
+
+    /// Creates a new push parser with the provided [`Options`].
+    pub fn with_options(options: Options) -> Self {
+        Self {
+            text: String::new(),
+            pending: Vec::new(),
+            state: Some(State::new(options)),
+            finished: false,
+            errors: Vec::new(),
+        }
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), DecodeError> {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => {
+                self.text.push_str(text);
+                Ok(())
+            }
+            Err(error) => {
+                let (valid, rest) = bytes.split_at(error.valid_up_to());
+                // SAFETY: The bytes up to valid_up_to were just validated.
+                self.text
+                    .push_str(unsafe { std::str::from_utf8_unchecked(valid) });
+
+                if error.error_len().is_some() {
+                    self.state.as_mut().unwrap().fail();
+                    return Err(DecodeError::InvalidUtf8(error));
+                }
+
+                self.pending.extend_from_slice(rest);
+                Ok(())
+            }
+        }
+    }
+
+    /// Appends `bytes` to the input.
+    ///
+    /// Input fed after an error is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input is not valid UTF-8.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called after [`Self::finish`].
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<(), DecodeError> {
+        assert!(!self.finished, "PushParser::feed called after finish");
+
+        let state = self.state.as_mut().unwrap();
+        if state.failed() {
+            return Ok(());
+        }
+
+        let consumed = state.position();
+        if consumed > 0 && consumed >= self.text.len() / 2 {
+            self.text.drain(..consumed);
+            state.discard(consumed);
+        }
+
+        if self.pending.is_empty() {
+            self.push_bytes(bytes)
+        } else {
+            let mut pending = std::mem::take(&mut self.pending);
+            pending.extend_from_slice(bytes);
+            self.push_bytes(&pending)
+        }
+    }
+
+    /// Marks the end of the input.
+    ///
+    /// Afterwards [`Self::next_event`] will return all remaining events, including errors
+    /// caused by the input ending prematurely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input ends with an incomplete UTF-8 sequence.
+    pub fn finish(&mut self) -> Result<(), DecodeError> {
+        self.finished = true;
+
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            let pending = std::mem::take(&mut self.pending);
+            self.state.as_mut().unwrap().fail();
+            Err(DecodeError::InvalidUtf8(
+                std::str::from_utf8(&pending).unwrap_err(),
+            ))
+        }
+    }
+
+    /// Returns the next complete event, or `None` if more input is needed.
+    ///
+    /// After [`Self::finish`] has been called, `None` signifies the end of the document.
+    pub fn next_event(&mut self) -> Option<Result<Event<'_>, Error>> {
+        self.next_detached()
+            .map(|result| result.map(|(event, range)| self.attach(event, range)))
+    }
+
+    /// Like [`Self::next_event`] but returns the event detached from the buffer along
+    /// with the range of its text, see [`Self::attach`].
+    ///
+    /// This allows callers to feed more input in a loop without holding on to a borrow.
+    pub(crate) fn next_detached(
+        &mut self,
+    ) -> Option<Result<(Event<'static>, Range<usize>), Error>> {
+        let state = self.state.take().unwrap();
+        if state.failed() {
+            self.state = Some(state);
+            return None;
+        }
+
+        let offset = state.offset();
+        let mut reader = Reader::resume(&self.text, state, !self.finished);
+        let result = reader.next().map(|result| {
+            result
+                .map(|event| {
+                    let start = event.text().as_ptr().addr() - self.text.as_ptr().addr();
+                    (event.with_text(""), start..start + event.text().len())
+                })
+                .map_err(|error| error.shifted(offset))
+        });
+        self.errors.extend(
+            reader
+                .take_errors()
+                .into_iter()
+                .map(|error| error.shifted(offset)),
+        );
+        self.state = Some(reader.suspend());
+
+        result
+    }
+
+    /// Removes and returns the errors collected so far if enabled with
+    /// [`Options::collect_errors`], see [`Reader::errors`].
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Reattaches an event returned by [`Self::next_detached`] to the buffer.
+    pub(crate) fn attach(&self, event: Event<'static>, range: Range<usize>) -> Event<'_> {
+        event.with_text(&self.text[range])
+    }
+
+    /// Returns whether no more events will be produced, regardless of further input.
+    pub(crate) fn is_done(&self) -> bool {
+        self.finished || self.state.as_ref().unwrap().failed()
+    }
+
+    /// Stops producing events because of an error that occurred outside of the parser.
+    pub(crate) fn fail(&mut self) {
+        self.state.as_mut().unwrap().fail();
+    }
+
+    /// Saves the progress of this parser so that parsing can be resumed later with
+    /// [`Self::restore`], without feeding the input that was already parsed again.
+    ///
+    /// Input that was fed but not parsed yet is not part of the saved state, it has to be
+    /// fed again starting at [`SavedState::position`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::{push::{PushParser, SavedState}, reader::{Event, Options}};
+    /// let input = b"<root><a/><b/></root>";
+    /// let mut parser = PushParser::new();
+    /// parser.feed(&input[..12]).unwrap();
+    /// while let Some(event) = parser.next_event() {
+    ///     event.unwrap();
+    /// }
+    /// let bytes = parser.save().as_bytes().to_vec();
+    /// drop(parser);
+    ///
+    /// let state = SavedState::from_bytes(&bytes).unwrap();
+    /// let position = state.position();
+    /// let mut parser = PushParser::restore(&state, Options::default());
+    /// parser.feed(&input[position..]).unwrap();
+    /// parser.finish().unwrap();
+    /// let Some(Ok(Event::Empty(b))) = parser.next_event() else { panic!() };
+    /// assert_eq!(b.name(), "b");
+    /// ```
+    pub fn save(&self) -> SavedState {
+        let state = self.state.as_ref().unwrap();
+        SavedState {
+            bytes: state.save(),
+            position: state.offset() + state.position(),
+        }
+    }
+
+    /// Creates a parser that continues from a state saved with [`Self::save`].
+    ///
+    /// `options` should be the same options that the saved parser was created with. The
+    /// restored parser expects to be fed the input starting at [`SavedState::position`].
+    pub fn restore(state: &SavedState, options: Options) -> Self {
+        Self {
+            text: String::new(),
+            pending: Vec::new(),
+            state: Some(State::load(&state.bytes, options).unwrap()),
+            finished: false,
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// The progress of a [`PushParser`], created with [`PushParser::save`].
+///
+/// A saved state can be converted to bytes with [`Self::as_bytes`] to persist it, for
+/// example to resume parsing a long document after a restart. It does not include the
+/// parser's [`Options`] or any of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedState {
+    /// The encoded state, always valid for [`State::load`].
+    bytes: Vec<u8>,
+    position: usize,
+}
+
+impl SavedState {
+    /// Decodes a state previously encoded with [`Self::as_bytes`].
+    ///
+    /// Returns `None` if `bytes` are not a state encoded by this version of the library.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let state = State::load(bytes, Options::default())?;
+        Some(Self {
+            bytes: bytes.to_vec(),
+            position: state.offset(),
+        })
+    }
+
+    /// Returns the encoded form of this state.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the position in the document, in bytes, at which input has to be fed to a
+    /// parser restored from this state.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// The number of bytes requested from an I/O source at once by [`Driver`].
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// The result of [`Driver::next`].
+pub(crate) enum Next {
+    /// An event detached from the buffer, see [`Driver::attach`].
+    Event(Event<'static>, Range<usize>),
+    Error(ReadError),
+    /// The end of the document was reached or parsing stopped after an error.
+    Done,
+    /// More input has to be read into [`Driver::chunk`] and passed to [`Driver::consume`].
+    NeedInput,
+}
+
+/// Parses a document read from an I/O source in chunks, shared by the readers built on
+/// [`PushParser`].
+pub(crate) struct Driver {
+    parser: PushParser,
+    chunk: Box<[u8]>,
+    #[cfg(feature = "flate2")]
+    decompressor: crate::gzip::Decompressor,
+    progress: Option<Progress>,
+}
+
+impl Driver {
+    pub(crate) fn new(options: Options) -> Self {
+        Self {
+            #[cfg(feature = "flate2")]
+            decompressor: crate::gzip::Decompressor::new(&options),
+            parser: PushParser::with_options(options),
+            chunk: vec![0; CHUNK_SIZE].into_boxed_slice(),
+            progress: None,
+        }
+    }
+
+    pub(crate) fn set_progress_callback(
+        &mut self,
+        total: Option<u64>,
+        callback: impl FnMut(u64, Option<u64>) + Send + 'static,
+    ) {
+        self.progress = Some(Progress::new(total, callback));
+    }
+
+    /// Returns the next event if it can be produced without reading more input.
+    pub(crate) fn next(&mut self) -> Next {
+        match self.parser.next_detached() {
+            Some(Ok((event, range))) => Next::Event(event, range),
+            Some(Err(error)) => Next::Error(error.into()),
+            None if self.parser.is_done() => Next::Done,
+            None => Next::NeedInput,
+        }
+    }
+
+    /// Reattaches an event returned by [`Self::next`] to the buffer.
+    pub(crate) fn attach(&self, event: Event<'static>, range: Range<usize>) -> Event<'_> {
+        self.parser.attach(event, range)
+    }
+
+    /// Returns the buffer that the next chunk of input should be read into.
+    pub(crate) fn chunk(&mut self) -> &mut [u8] {
+        &mut self.chunk
+    }
+
+    /// Passes the result of reading into [`Self::chunk`] to the parser, reading zero bytes
+    /// marks the end of the input.
+    ///
+    /// Errors returned by the source are passed through and reading may be retried, any
+    /// other error stops the parser.
+    pub(crate) fn consume(&mut self, read: std::io::Result<usize>) -> Result<(), ReadError> {
+        let result = match read {
+            Ok(0) => self.finish(),
+            Ok(len) => self.feed(len),
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => Ok(()),
+            Err(error) => return Err(error.into()),
+        };
+        if result.is_err() {
+            self.parser.fail();
+        }
+        result
+    }
+
+    /// Feeds the first `len` bytes of the chunk to the parser.
+    fn feed(&mut self, len: usize) -> Result<(), ReadError> {
+        if let Some(progress) = &mut self.progress {
+            progress.advance(len);
+        }
+        #[cfg(feature = "flate2")]
+        let bytes = self.decompressor.process(&self.chunk[..len])?;
+        #[cfg(not(feature = "flate2"))]
+        let bytes = &self.chunk[..len];
+        Ok(self.parser.feed(bytes)?)
+    }
+
+    fn finish(&mut self) -> Result<(), ReadError> {
+        #[cfg(feature = "flate2")]
+        self.parser.feed(self.decompressor.finish()?)?;
+        Ok(self.parser.finish()?)
+    }
+}
+
+/// Reports how much of an I/O source was read, see [`Driver::set_progress_callback`].
+struct Progress {
+    read: u64,
+    total: Option<u64>,
+    callback: Box<dyn FnMut(u64, Option<u64>) + Send>,
+}
+
+impl Progress {
+    pub(crate) fn new(
+        total: Option<u64>,
+        callback: impl FnMut(u64, Option<u64>) + Send + 'static,
+    ) -> Self {
+        Self {
+            read: 0,
+            total,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Records that `len` more bytes were read from the source and invokes the callback.
+    fn advance(&mut self, len: usize) {
+        self.read += len as u64;
+        (self.callback)(self.read, self.total);
+    }
+}
+
+/// An error that can occur while reading a document from an I/O source.
+pub enum ReadError {
+    /// Reading from the source failed.
+    Io(std::io::Error),
+    /// The document is not valid UTF-8.
+    Decode(DecodeError),
+    /// The document could not be parsed.
+    Parse(Error),
+}
+
+impl From<std::io::Error> for ReadError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<DecodeError> for ReadError {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+impl From<Error> for ReadError {
+    fn from(error: Error) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(error) => Some(error),
+            ReadError::Decode(error) => Some(error),
+            ReadError::Parse(error) => Some(error),
+        }
+    }
+}
+
+impl Debug for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as Display>::fmt(self, f)
+    }
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Io(error) => <std::io::Error as Display>::fmt(error, f),
+            ReadError::Decode(error) => <DecodeError as Display>::fmt(error, f),
+            ReadError::Parse(error) => <Error as Display>::fmt(error, f),
+        }
+    }
+}
+
+impl Default for PushParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PushParser, SavedState};
+    use crate::reader::{ErrorKind, Event, Options};
+
+    #[test]
+    fn byte_at_a_time() {
+        let code = "<?pi ?><root a='1 > 2'><!-- > --><![CDATA[x]]>zażółć</root>";
+        let mut parser = PushParser::new();
+        let mut events = Vec::new();
+
+        for byte in code.as_bytes() {
+            parser.feed(std::slice::from_ref(byte)).unwrap();
+            while let Some(event) = parser.next_event() {
+                events.push(match event.unwrap() {
+                    Event::Start(start) => format!("start {}", start.name()),
+                    Event::End(end) => format!("end {}", end.name()),
+                    Event::Text(text) => format!("text {}", text.content()),
+                    Event::CData(cdata) => format!("cdata {}", cdata.content()),
+                    Event::Comment(comment) => format!("comment {}", comment.content()),
+                    event => panic!("unexpected event {event:?}"),
+                });
+            }
+        }
+        parser.finish().unwrap();
+        assert!(parser.next_event().is_none());
+
+        assert_eq!(
+            events,
+            [
+                "start root",
+                "comment  > ",
+                "cdata x",
+                "text zażółć",
+                "end root"
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut parser = PushParser::new();
+        parser.feed(b"<root><a>").unwrap();
+        assert!(matches!(parser.next_event(), Some(Ok(Event::Start(_)))));
+        assert!(matches!(parser.next_event(), Some(Ok(Event::Start(_)))));
+        assert!(parser.next_event().is_none());
+
+        parser.finish().unwrap();
+        let error = parser.next_event().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnclosedElement);
+        assert_eq!(error.span(), 9..9);
+        assert!(parser.next_event().is_none());
+
+        let mut parser = PushParser::new();
+        parser.feed(b"<root>text</root>\xC5").unwrap();
+        assert!(parser.finish().is_err());
+        assert!(parser.next_event().is_none());
+
+        let mut parser = PushParser::new();
+        assert!(parser.feed(b"<a>x\xFFy<b/>").is_err());
+        parser.feed(b"</a>").unwrap();
+        parser.finish().unwrap();
+        assert!(parser.next_event().is_none());
+        assert!(parser.is_done());
+
+        let mut parser = PushParser::new();
+        parser.feed(b"<root><a b></root>").unwrap();
+        assert!(matches!(parser.next_event(), Some(Ok(Event::Start(_)))));
+        let error = parser.next_event().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ExpectedAttributeEq);
+        parser.feed(b"<more/>").unwrap();
+        assert!(parser.next_event().is_none());
+
+        let mut parser = PushParser::with_options(Options::default().validate_closing_tags(true));
+        for chunk in [&b"<a>"[..], b"<b>"] {
+            parser.feed(chunk).unwrap();
+            assert!(matches!(parser.next_event(), Some(Ok(Event::Start(_)))));
+        }
+        parser.feed(b"</a>").unwrap();
+        let error = parser.next_event().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MismatchedEndTag);
+        assert_eq!(error.span(), 8..9);
+        assert_eq!(error.opened_at(), Some(4..5));
+    }
+
+    #[test]
+    fn collect_errors() {
+        let options = Options::default().recover(true).collect_errors(true);
+        let mut parser = PushParser::with_options(options);
+        for chunk in [&b"<a><b c/>"[..], b"<d e=/>", b"</a>"] {
+            parser.feed(chunk).unwrap();
+            while let Some(event) = parser.next_event() {
+                event.unwrap();
+            }
+        }
+        parser.finish().unwrap();
+        assert!(parser.next_event().is_none());
+
+        let spans = parser
+            .take_errors()
+            .into_iter()
+            .map(|error| (error.kind(), error.span()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                (ErrorKind::ExpectedAttributeEq, 6..7),
+                (ErrorKind::ExpectedAttributeValue, 12..14)
+            ]
+        );
+    }
+
+    #[test]
+    fn event_size_limit() {
+        let mut parser = PushParser::with_options(Options::default().max_event_size(16));
+        parser.feed(b"<root>").unwrap();
+        assert!(matches!(parser.next_event(), Some(Ok(Event::Start(_)))));
+
+        parser.feed(b"0123456789").unwrap();
+        assert!(parser.next_event().is_none());
+        parser.feed(b"0123456789").unwrap();
+        let error = parser.next_event().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::EventTooLarge);
+        assert_eq!(error.span(), 6..26);
+        assert!(parser.is_done());
+    }
+
+    #[test]
+    fn raw_text_elements() {
+        let code = "<script>a</scr < b</script>";
+        let mut parser = PushParser::with_options(Options::default().raw_text_elements(["script"]));
+        let mut events = Vec::new();
+
+        for byte in code.as_bytes() {
+            parser.feed(std::slice::from_ref(byte)).unwrap();
+            while let Some(event) = parser.next_event() {
+                events.push(match event.unwrap() {
+                    Event::Start(start) => format!("start {}", start.name()),
+                    Event::End(end) => format!("end {}", end.name()),
+                    Event::Text(text) => format!("text {}", text.content()),
+                    event => panic!("unexpected event {event:?}"),
+                });
+            }
+        }
+        parser.finish().unwrap();
+        assert!(parser.next_event().is_none());
+
+        assert_eq!(events, ["start script", "text a</scr < b", "end script"]);
+    }
+
+    #[test]
+    fn text_chunks() {
+        let mut parser = PushParser::with_options(Options::default().text_chunk_size(4));
+        parser.feed(b"<root>ab").unwrap();
+        assert!(matches!(parser.next_event(), Some(Ok(Event::Start(_)))));
+        assert!(parser.next_event().is_none());
+
+        parser.feed(b"cd&am").unwrap();
+        let Some(Ok(Event::Text(text))) = parser.next_event() else {
+            panic!()
+        };
+        assert_eq!(text.raw_content(), "abcd");
+        assert!(parser.next_event().is_none());
+
+        parser.feed(b"p;efgh</root>").unwrap();
+        parser.finish().unwrap();
+        let chunks = std::iter::from_fn(|| match parser.next_event()? {
+            Ok(Event::Text(text)) => Some(text.content().into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(chunks, ["&", "efgh"]);
+    }
+
+    #[test]
+    fn save_restore() {
+        let code = "<?xml version='1.1'?><root xml:lang='pl'><a><b/>";
+        let options = Options::default()
+            .parse_declaration_node(true)
+            .forbid_control_characters(true)
+            .validate_closing_tags(true);
+        let mut parser = PushParser::with_options(options.clone());
+        parser.feed(code.as_bytes()).unwrap();
+        // An incomplete character is not part of the saved state.
+        parser.feed(b"x\xC5").unwrap();
+        while let Some(event) = parser.next_event() {
+            event.unwrap();
+        }
+
+        let saved = parser.save();
+        assert_eq!(saved.position(), code.len());
+        let state = SavedState::from_bytes(saved.as_bytes()).unwrap();
+        assert_eq!(state, saved);
+        assert!(SavedState::from_bytes(&saved.as_bytes()[1..]).is_none());
+        assert!(SavedState::from_bytes(&[saved.as_bytes(), b"\0"].concat()).is_none());
+
+        let mut parser = PushParser::restore(&state, options.clone());
+        parser.feed("xż</a>".as_bytes()).unwrap();
+        let Some(Ok(Event::Text(text))) = parser.next_event() else {
+            panic!()
+        };
+        assert_eq!(text.content(), "xż");
+        assert!(matches!(parser.next_event(), Some(Ok(Event::End(_)))));
+        parser.feed(b"</b>").unwrap();
+        let error = parser.next_event().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MismatchedEndTag);
+        assert_eq!(error.span(), code.len() + 9..code.len() + 10);
+        assert_eq!(error.opened_at(), Some(22..26));
+
+        // The document is still treated as XML 1.1.
+        let mut parser = PushParser::restore(&state, options);
+        parser.feed("\u{86}<".as_bytes()).unwrap();
+        let error = parser.next_event().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidCharacter);
+        assert_eq!(error.span(), code.len()..code.len() + 2);
+    }
+}
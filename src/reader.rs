@@ -2,7 +2,8 @@
 //!
 //! Prefixed names like `mod:findName` are implemented as an extension.
 //!
-//! Parsing is done using default flags.
+//! Parsing is done using default flags, except for `parse_pi_nodes` and `parse_declaration_node`
+//! which can be enabled using [`Options::parse_pi_nodes`] and [`Options::parse_declaration_node`].
 //! Note that the behaviour of non-default flags can usually be reconstructed after parsing
 //! with default flags.
 //! That is unless the implementation of the flag is "buggy" in RapidXML itself, for example
@@ -10,13 +11,33 @@
 
 use std::{
     borrow::Cow,
+    collections::HashSet,
+    convert::Infallible,
     fmt::{Debug, Display},
+    iter::FusedIterator,
     ops::Range,
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc,
+    },
 };
 
 use crate::{
-    escape::unescape,
-    lut::{is_invalid_attribute_name, is_invalid_name, is_whitespace},
+    dtd::{self, Declarations, ExternalId},
+    encoding::{self, DecodeError},
+    escape::{
+        normalize_attribute_value, normalize_attribute_value_1_1, normalize_line_endings,
+        normalize_line_endings_1_1, try_unescape, unescape, unescape_normalized,
+        unescape_with_limits, EntityResolver, PredefinedEntities, UnescapeError,
+    },
+    intern::{Interner, Symbol},
+    lut::{
+        is_invalid_attribute_name, is_invalid_name, is_ncname, is_restricted_control, is_whitespace,
+    },
+    push::ReadError,
+    sax,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -39,6 +60,26 @@ impl<'a> StartEvent<'a> {
         &self.text[self.prefix_end + 1..self.name_end]
     }
 
+    /// Returns the symbol for this event's prefixed name in `interner`.
+    pub fn name_symbol(&self, interner: &mut Interner) -> Symbol {
+        interner.intern(&self.text[1..self.name_end])
+    }
+
+    /// Returns the original text of this tag, including the angle brackets and all
+    /// whitespace between the attributes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<a  b = 'c'\n>text</a>");
+    /// let Some(Ok(Event::Start(start))) = reader.next() else { panic!() };
+    /// assert_eq!(start.raw(), "<a  b = 'c'\n>");
+    /// ```
+    pub fn raw(&self) -> &'a str {
+        self.text
+    }
+
     /// Returns `true` if this event is an empty tag.
     ///
     /// # Notes
@@ -47,7 +88,16 @@ impl<'a> StartEvent<'a> {
     /// element is empty, it only checks whether the tag itself is of the
     /// self-closing variety.
     pub fn is_empty(&self) -> bool {
-        self.text.as_bytes()[self.text.len() - 2] == b'/'
+        self.text.ends_with("/>")
+    }
+
+    /// Returns whether this tag was cut off by the end of the input.
+    ///
+    /// Truncated tags only contain the attributes that were read completely and are
+    /// always reported as [`Event::Start`]. Only produced if enabled with
+    /// [`Options::allow_truncated_input`].
+    pub fn is_truncated(&self) -> bool {
+        !self.text.ends_with('>')
     }
 
     /// Returns the span of this tag in `reader`.
@@ -74,8 +124,8 @@ impl<'a> StartEvent<'a> {
     ///
     /// May panic if `reader` is not the [`Reader`] that this event originated from.
     pub fn prefix_position_in(&self, reader: &Reader) -> Option<Range<usize>> {
-        (self.prefix_end > 0)
-            .then(|| reader.range_for_ptrs(self.text.as_bytes()[..self.prefix_end].as_ptr_range()))
+        self.prefix()
+            .map(|prefix| reader.range_for_ptrs(prefix.as_bytes().as_ptr_range()))
     }
 
     /// Returns the span of this tag's prefixed name in `reader`.
@@ -87,62 +137,231 @@ impl<'a> StartEvent<'a> {
         reader.range_for_ptrs(self.text.as_bytes()[1..self.name_end].as_ptr_range())
     }
 
+    /// Returns the whitespace between the last attribute, or the name if there are none, and
+    /// the closing `>` or `/>` of this tag.
+    ///
+    /// Together with [`AttributeEvent::leading_whitespace`] and [`AttributeEvent::raw`] this
+    /// allows reproducing the tag exactly, which [`Writer::write_event`](crate::Writer::write_event)
+    /// does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<a\n  b = 'c'\n/>");
+    /// let Some(Ok(Event::Empty(start))) = reader.next() else { panic!() };
+    /// let attr = start.attributes().next().unwrap();
+    /// assert_eq!((attr.leading_whitespace(), attr.raw()), ("\n  ", "b = 'c'"));
+    /// assert_eq!(start.trailing_whitespace(), "\n");
+    /// ```
+    pub fn trailing_whitespace(&self) -> &'a str {
+        let tag = match self.text.strip_suffix("/>") {
+            Some(tag) => tag,
+            None => self.text.strip_suffix('>').unwrap_or(self.text),
+        };
+        let content = tag.trim_end_matches(|chr: char| chr.is_ascii() && is_whitespace(chr as u8));
+        &tag[content.len()..]
+    }
+
     /// Returns an iterator over the attribute events of this start tag.
     pub fn attributes(&self) -> Attributes<'a> {
         Attributes(ParsingBuffer::new(&self.text[self.name_end..]))
     }
+
+    /// Returns the first attribute of this start tag with the prefixed name `name`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<a href='/x' xlink:href='/y'/>");
+    /// let Some(Ok(Event::Empty(start))) = reader.next() else { panic!() };
+    /// assert_eq!(start.get_attribute("href").unwrap().value(), "/x");
+    /// assert_eq!(start.get_attribute("xlink:href").unwrap().value(), "/y");
+    /// assert!(start.get_attribute("title").is_none());
+    /// ```
+    pub fn get_attribute(&self, name: &str) -> Option<AttributeEvent<'a>> {
+        self.attributes().find(|attr| attr.name() == name)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 /// An event returned by the [`Attributes`] iterator that represents a single attribute on a start tag.
 pub struct AttributeEvent<'a> {
     pub(crate) text: &'a str,
+    leading_whitespace: &'a str,
+    pub(crate) prefix_end: usize,
     name_end: usize,
     value_start: usize,
 }
 
 #[repr(u8)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 /// The quote character an attribute's value can be wrapped in.
 pub enum AttributeQuote {
     /// A single quote (`'`) character.
     Single = b'\'',
     /// A double quote (`"`) character.
+    #[default]
     Double = b'\"',
+    /// No quote character at all.
+    ///
+    /// Only produced if enabled with [`Options::allow_unquoted_attribute_values`].
+    None = 0,
 }
 
 impl AttributeQuote {
     /// Returns the quote character converted to a [`char`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if called on [`Self::None`], use [`Self::as_char`] if attributes may be
+    /// unquoted.
     pub fn to_char(self) -> char {
-        self as u8 as char
+        self.as_char()
+            .expect("AttributeQuote::to_char called on an unquoted attribute")
+    }
+
+    /// Returns the quote character converted to a [`char`], or `None` for [`Self::None`].
+    pub fn as_char(self) -> Option<char> {
+        match self {
+            Self::Single => Some('\''),
+            Self::Double => Some('"'),
+            Self::None => None,
+        }
     }
 }
 
 impl<'a> AttributeEvent<'a> {
-    /// Returns this attribute's name.
+    /// Returns the original text of this attribute, from the start of its name to the end
+    /// of its value including the quotes and any whitespace around the `=`.
+    pub fn raw(&self) -> &'a str {
+        self.text
+    }
+
+    /// Returns the whitespace that separated this attribute from the preceding attribute
+    /// or the tag name.
+    pub fn leading_whitespace(&self) -> &'a str {
+        self.leading_whitespace
+    }
+
+    /// Returns this attribute's name, including the prefix if present.
     pub fn name(&self) -> &'a str {
         &self.text[..self.name_end]
     }
 
+    /// Returns the symbol for this attribute's name, including the prefix if present,
+    /// in `interner`.
+    pub fn name_symbol(&self, interner: &mut Interner) -> Symbol {
+        interner.intern(self.name())
+    }
+
+    /// Returns the prefix component of this attribute's prefixed name, if present.
+    pub fn prefix(&self) -> Option<&'a str> {
+        (self.prefix_end > 0).then(|| &self.text[..self.prefix_end])
+    }
+
+    /// Returns the name component of this attribute's prefixed name.
+    pub fn local_name(&self) -> &'a str {
+        match self.prefix_end {
+            0 => self.name(),
+            prefix_end => &self.text[prefix_end + 1..self.name_end],
+        }
+    }
+
     /// Returns this attribute's unescaped value.
     pub fn value(&self) -> Cow<'a, str> {
         unescape(self.raw_value())
     }
 
+    /// Returns this attribute's unescaped value, failing on unknown or malformed entity
+    /// references instead of keeping them unexpanded.
+    ///
+    /// The original value is borrowed if it does not contain any entity references.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value contains an entity reference that is not one of the
+    /// predefined entities or a valid character reference. The error's offset is relative
+    /// to [`Self::raw_value`].
+    pub fn unescaped_value(&self) -> Result<Cow<'a, str>, UnescapeError> {
+        try_unescape(self.raw_value())
+    }
+
+    /// Returns this attribute's value unescaped using the [`EntityResolver`] of `reader`.
+    ///
+    /// Line endings and whitespace are normalized if enabled with
+    /// [`Options::normalize_line_endings`] or [`Options::normalize_attribute_values`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entity expansion limits set in [`Options`] were exceeded.
+    pub fn value_in(&self, reader: &Reader) -> Result<Cow<'a, str>, Error> {
+        let raw = self.raw_value();
+        unescape_normalized(raw, reader.normalize_attribute_value(raw), |text| {
+            reader.unescape(text)
+        })
+    }
+
+    /// Unescapes this attribute's value and parses it into `T`, ignoring surrounding
+    /// whitespace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<a width=' 12 ' height='x'/>");
+    /// let Some(Ok(Event::Empty(a))) = reader.next() else { panic!() };
+    /// let mut attributes = a.attributes();
+    /// assert_eq!(attributes.next().unwrap().parse::<u32>().unwrap(), 12);
+    ///
+    /// let error = attributes.next().unwrap().parse::<u32>().unwrap_err();
+    /// assert_eq!(error.value(), "x");
+    /// assert_eq!(error.position_in(&reader), 24..25);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value could not be parsed.
+    pub fn parse<T: FromStr>(&self) -> Result<T, ParseValueError<'a, T::Err>> {
+        ParseValueError::parse(self.raw_value(), self.value())
+    }
+
     /// Returns this attribute's escaped value.
+    ///
+    /// Like RapidXML, quoted values are only terminated by their quote character, so they
+    /// may contain unescaped `<` and `>` characters.
     pub fn raw_value(&self) -> &'a str {
-        &self.text[self.value_start..self.text.len() - 1]
+        let quote_len = match self.quote() {
+            AttributeQuote::None => 0,
+            _ => 1,
+        };
+        &self.text[self.value_start..self.text.len() - quote_len]
+    }
+
+    /// Returns whether this attribute has a value, which is only `false` for attributes
+    /// like `<input disabled>` accepted with [`Options::allow_valueless_attributes`].
+    pub fn has_value(&self) -> bool {
+        self.value_start != self.name_end
     }
 
     /// Returns the quote character that this attribute's value was wrapped in.
+    ///
+    /// Attributes without a value are reported with [`AttributeQuote::None`].
     pub fn quote(&self) -> AttributeQuote {
-        match self.text.bytes().last().unwrap() {
+        if !self.has_value() {
+            return AttributeQuote::None;
+        }
+
+        match self.text.as_bytes()[self.value_start - 1] {
             b'\'' => AttributeQuote::Single,
             b'\"' => AttributeQuote::Double,
-            _ => unreachable!(),
+            _ => AttributeQuote::None,
         }
     }
 
-    /// Returns the span of this atribute in `reader`.
+    /// Returns the span of this attribute, from the start of its name to the end of its
+    /// value, in `reader`.
     ///
     /// # Panics
     ///
@@ -151,7 +370,7 @@ impl<'a> AttributeEvent<'a> {
         reader.range_for_ptrs(self.text.as_bytes().as_ptr_range())
     }
 
-    /// Returns the span of this atribute's name in `reader`.
+    /// Returns the span of this attribute's name, including the prefix, in `reader`.
     ///
     /// # Panics
     ///
@@ -160,7 +379,37 @@ impl<'a> AttributeEvent<'a> {
         reader.range_for_ptrs(self.name().as_bytes().as_ptr_range())
     }
 
-    /// Returns the span of this atribute's value in `reader`.
+    /// Returns the span of this attribute's prefix component in `reader`.
+    ///
+    /// # Panics
+    ///
+    /// May panic if `reader` is not the [`Reader`] that this event originated from.
+    pub fn prefix_position_in(&self, reader: &Reader) -> Option<Range<usize>> {
+        self.prefix()
+            .map(|prefix| reader.range_for_ptrs(prefix.as_bytes().as_ptr_range()))
+    }
+
+    /// Returns the span of this attribute's name component in `reader`.
+    ///
+    /// # Panics
+    ///
+    /// May panic if `reader` is not the [`Reader`] that this event originated from.
+    pub fn local_name_position_in(&self, reader: &Reader) -> Range<usize> {
+        reader.range_for_ptrs(self.local_name().as_bytes().as_ptr_range())
+    }
+
+    /// Returns the span of this attribute's value in `reader`, excluding the quotes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<item id='1' count='many'/>");
+    /// let Some(Ok(Event::Empty(item))) = reader.next() else { panic!() };
+    /// let count = item.attributes().find(|attr| attr.name() == "count").unwrap();
+    /// assert_eq!(count.name_position_in(&reader), 13..18);
+    /// assert_eq!(count.value_position_in(&reader), 20..24);
+    /// ```
     ///
     /// # Panics
     ///
@@ -190,6 +439,11 @@ impl<'a> EndEvent<'a> {
         &self.text[self.prefix_end + 1..self.name_end]
     }
 
+    /// Returns the symbol for this event's prefixed name in `interner`.
+    pub fn name_symbol(&self, interner: &mut Interner) -> Symbol {
+        interner.intern(&self.text[2..self.name_end])
+    }
+
     /// Returns the span of this event in `reader`.
     ///
     /// # Panics
@@ -201,14 +455,6 @@ impl<'a> EndEvent<'a> {
 }
 
 macro_rules! simple_text_event {
-    (@mkunescape raw_content) => {
-        /// Returns this event's unescaped content.
-        pub fn content(&self) -> Cow<'a, str> {
-            unescape(self.raw_content())
-        }
-    };
-    (@mkunescape content) => {};
-
     ($name: ident$(, $prefix: literal, $suffix: literal)?, $content_type: ident, $emitted_by_what: literal, $what_content: literal) => {
         #[derive(Debug, Clone, Copy)]
         #[doc = concat!("An event emitted by ", $emitted_by_what, ".")]
@@ -217,11 +463,14 @@ macro_rules! simple_text_event {
         }
 
         impl<'a> $name<'a> {
-            simple_text_event!(@mkunescape $content_type);
-
             #[doc = concat!("Returns this event's ", $what_content, " content.")]
             pub fn $content_type(&self) -> &'a str {
-                &self.text$([$prefix.len()..self.text.len() - $suffix.len()])?
+                let text = self.text;
+                $(
+                    let text = &text[$prefix.len()..];
+                    let text = text.strip_suffix($suffix).unwrap_or(text);
+                )?
+                text
             }
 
             /// Returns the span of this event in `reader`.
@@ -236,7 +485,192 @@ macro_rules! simple_text_event {
     };
 }
 
-simple_text_event!(TextEvent, raw_content, "text content", "escaped");
+#[derive(Debug, Clone, Copy)]
+/// An event emitted by text content.
+pub struct TextEvent<'a> {
+    pub(crate) text: &'a str,
+    cdata: bool,
+    raw: bool,
+}
+
+impl<'a> TextEvent<'a> {
+    pub(crate) fn unescape_with<E>(
+        &self,
+        unescape: impl Fn(&'a str) -> Result<Cow<'a, str>, E>,
+        cdata_content: impl Fn(&'a str) -> Cow<'a, str>,
+    ) -> Result<Cow<'a, str>, E> {
+        if self.raw {
+            return Ok(cdata_content(self.text));
+        }
+
+        if !self.cdata {
+            return unescape(self.text);
+        }
+
+        let mut result = Cow::Borrowed("");
+        let mut push = |content: Cow<'a, str>| {
+            if result.is_empty() {
+                result = content;
+            } else if !content.is_empty() {
+                result.to_mut().push_str(&content);
+            }
+        };
+
+        let mut rest = self.text;
+        while let Some(start) = memchr::memmem::find(rest.as_bytes(), b"<![CDATA[") {
+            push(unescape(&rest[..start])?);
+            let cdata = &rest[start + 9..];
+            let end = memchr::memmem::find(cdata.as_bytes(), b"]]>").unwrap();
+            push(cdata_content(&cdata[..end]));
+            rest = &cdata[end + 3..];
+        }
+        push(unescape(rest)?);
+
+        Ok(result)
+    }
+
+    /// Returns whether any part of this event originated from a cdata section.
+    ///
+    /// This can only be the case if enabled with [`Options::coalesce_text`] or
+    /// [`Options::cdata_as_text`]. The cdata sections are still present in
+    /// [`Self::raw_content`], so writing it out reproduces them faithfully.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let options = Options::default().coalesce_text(true);
+    /// let mut reader = Reader::with_options("<a>x<![CDATA[<y>]]></a>", options);
+    /// reader.next();
+    /// let Some(Ok(Event::Text(text))) = reader.next() else { panic!() };
+    /// assert!(text.contains_cdata());
+    /// assert_eq!(text.content(), "x<y>");
+    /// assert_eq!(text.raw_content(), "x<![CDATA[<y>]]>");
+    /// ```
+    pub fn contains_cdata(&self) -> bool {
+        self.cdata
+    }
+
+    /// Returns whether this event is the content of an element read as raw text, which
+    /// can only be the case if enabled with [`Options::raw_text_elements`].
+    ///
+    /// Entity references in raw text are not expanded, [`Self::content`] returns it as is.
+    pub fn is_raw(&self) -> bool {
+        self.raw
+    }
+
+    /// Returns whether this event's content consists entirely of whitespace.
+    ///
+    /// Only literal whitespace counts, text containing character references like `&#x20;`
+    /// is never considered whitespace. This is computed on every call by scanning the text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<a>\n  <b>text</b>\n</a>");
+    /// reader.next();
+    /// let Some(Ok(Event::Text(text))) = reader.next() else { panic!() };
+    /// assert!(text.is_whitespace());
+    /// ```
+    pub fn is_whitespace(&self) -> bool {
+        let all_whitespace = |text: &str| text.bytes().all(is_whitespace);
+        if !self.cdata {
+            return all_whitespace(self.text);
+        }
+
+        let mut rest = self.text;
+        while let Some(start) = memchr::memmem::find(rest.as_bytes(), b"<![CDATA[") {
+            let cdata = &rest[start + 9..];
+            let end = memchr::memmem::find(cdata.as_bytes(), b"]]>").unwrap();
+            if !all_whitespace(&rest[..start]) || !all_whitespace(&cdata[..end]) {
+                return false;
+            }
+            rest = &cdata[end + 3..];
+        }
+        all_whitespace(rest)
+    }
+
+    /// Returns this event's unescaped content.
+    pub fn content(&self) -> Cow<'a, str> {
+        self.unescape_with(|text| Ok::<_, Infallible>(unescape(text)), Cow::Borrowed)
+            .unwrap_or_else(|never| match never {})
+    }
+
+    /// Unescapes this event's content like [`Self::content`] and parses it into `T`,
+    /// ignoring surrounding whitespace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<price>\n  12.5\n</price>");
+    /// reader.next();
+    /// let Some(Ok(Event::Text(text))) = reader.next() else { panic!() };
+    /// assert_eq!(text.parse::<f64>().unwrap(), 12.5);
+    /// assert!(text.parse::<u32>().is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content could not be parsed.
+    pub fn parse<T: FromStr>(&self) -> Result<T, ParseValueError<'a, T::Err>> {
+        ParseValueError::parse(self.text, self.content())
+    }
+
+    /// Returns this event's unescaped content, failing on unknown or malformed entity
+    /// references instead of keeping them unexpanded.
+    ///
+    /// The original text is borrowed if it does not contain any entity references.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content contains an entity reference that is not one of the
+    /// predefined entities or a valid character reference. The error's offset is relative
+    /// to [`Self::raw_content`].
+    pub fn unescaped_text(&self) -> Result<Cow<'a, str>, UnescapeError> {
+        self.unescape_with(
+            |segment| {
+                try_unescape(segment).map_err(|error| UnescapeError {
+                    offset: segment.as_ptr().addr() - self.text.as_ptr().addr() + error.offset,
+                })
+            },
+            Cow::Borrowed,
+        )
+    }
+
+    /// Returns this event's content unescaped using the [`EntityResolver`] of `reader`.
+    ///
+    /// Line endings are normalized if enabled with [`Options::normalize_line_endings`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entity expansion limits set in [`Options`] were exceeded.
+    pub fn content_in(&self, reader: &Reader) -> Result<Cow<'a, str>, Error> {
+        self.unescape_with(
+            |text| unescape_normalized(text, reader.normalize(text), |text| reader.unescape(text)),
+            |text| reader.normalize(text),
+        )
+    }
+
+    /// Returns this event's escaped content.
+    ///
+    /// If this event was produced by coalescing text with cdata, the returned string
+    /// will contain the original cdata sections.
+    pub fn raw_content(&self) -> &'a str {
+        self.text
+    }
+
+    /// Returns the span of this event in `reader`.
+    ///
+    /// # Panics
+    ///
+    /// May panic if `reader` is not the [`Reader`] that this event originated from.
+    pub fn position_in(&self, reader: &Reader) -> Range<usize> {
+        reader.range_for_ptrs(self.text.as_bytes().as_ptr_range())
+    }
+}
+
 simple_text_event!(
     CDataEvent,
     "<![CDATA[",
@@ -262,6 +696,135 @@ simple_text_event!(
     "unescaped"
 );
 
+impl CDataEvent<'_> {
+    /// Returns whether this cdata section was cut off by the end of the input.
+    ///
+    /// Only produced if enabled with [`Options::allow_truncated_input`].
+    pub fn is_truncated(&self) -> bool {
+        !self.text[9..].ends_with("]]>")
+    }
+}
+
+impl CommentEvent<'_> {
+    /// Returns whether this comment was cut off by the end of the input.
+    ///
+    /// Only produced if enabled with [`Options::allow_truncated_input`].
+    pub fn is_truncated(&self) -> bool {
+        !self.text[4..].ends_with("-->")
+    }
+}
+
+impl<'a> DoctypeEvent<'a> {
+    /// Returns the name of the root element declared by this doctype.
+    pub fn name(&self) -> Option<&'a str> {
+        dtd::parse_doctype(self.content()).0
+    }
+
+    /// Returns the `PUBLIC` or `SYSTEM` identifier of this doctype's external subset.
+    pub fn external_id(&self) -> Option<ExternalId<'a>> {
+        dtd::parse_doctype(self.content()).1
+    }
+
+    /// Returns the content of this doctype's internal subset, excluding the surrounding brackets.
+    pub fn internal_subset(&self) -> Option<&'a str> {
+        let content = self.content();
+        dtd::find_internal_subset(content).map(|range| &content[range])
+    }
+
+    /// Returns an iterator over the markup declarations in this doctype's internal subset.
+    ///
+    /// The iterator is empty if there is no internal subset.
+    pub fn declarations(&self) -> Declarations<'a> {
+        Declarations::new(self.internal_subset().unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// An event emitted by processing instructions like `<?xml-stylesheet href="style.xsl"?>`.
+pub struct PIEvent<'a> {
+    pub(crate) text: &'a str,
+    target_end: usize,
+    content_start: usize,
+}
+
+impl<'a> PIEvent<'a> {
+    /// Returns this processing instruction's target.
+    pub fn target(&self) -> &'a str {
+        &self.text[2..self.target_end]
+    }
+
+    /// Returns this processing instruction's content, excluding any leading whitespace.
+    pub fn content(&self) -> &'a str {
+        &self.text[self.content_start..self.text.len() - 2]
+    }
+
+    /// Returns the span of this event in `reader`.
+    ///
+    /// # Panics
+    ///
+    /// May panic if `reader` is not the [`Reader`] that this event originated from.
+    pub fn position_in(&self, reader: &Reader) -> Range<usize> {
+        reader.range_for_ptrs(self.text.as_bytes().as_ptr_range())
+    }
+
+    /// Returns the span of this processing instruction's target in `reader`.
+    ///
+    /// # Panics
+    ///
+    /// May panic if `reader` is not the [`Reader`] that this event originated from.
+    pub fn target_position_in(&self, reader: &Reader) -> Range<usize> {
+        reader.range_for_ptrs(self.target().as_bytes().as_ptr_range())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// An event emitted by the XML declaration like `<?xml version="1.0" encoding="utf-8"?>`.
+pub struct DeclEvent<'a> {
+    pub(crate) text: &'a str,
+}
+
+impl<'a> DeclEvent<'a> {
+    /// Returns an iterator over the pseudo-attributes of this declaration.
+    pub fn attributes(&self) -> Attributes<'a> {
+        Attributes(ParsingBuffer::new(&self.text[5..self.text.len() - 2]))
+    }
+
+    fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.attributes()
+            .find(|attr| attr.name() == name)
+            .map(|attr| attr.raw_value())
+    }
+
+    /// Returns the value of the `version` pseudo-attribute, if present.
+    pub fn version(&self) -> Option<&'a str> {
+        self.attribute("version")
+    }
+
+    /// Returns the value of the `encoding` pseudo-attribute, if present.
+    pub fn encoding(&self) -> Option<&'a str> {
+        self.attribute("encoding")
+    }
+
+    /// Returns the value of the `standalone` pseudo-attribute, if present and equal to
+    /// either `yes` or `no`.
+    pub fn standalone(&self) -> Option<bool> {
+        match self.attribute("standalone")? {
+            "yes" => Some(true),
+            "no" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns the span of this event in `reader`.
+    ///
+    /// # Panics
+    ///
+    /// May panic if `reader` is not the [`Reader`] that this event originated from.
+    pub fn position_in(&self, reader: &Reader) -> Range<usize> {
+        reader.range_for_ptrs(self.text.as_bytes().as_ptr_range())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 /// An event emitted by the [`Reader`].
 pub enum Event<'a> {
@@ -279,20 +842,186 @@ pub enum Event<'a> {
     Comment(CommentEvent<'a>),
     /// An event emitted by doctype declarations like `<!DOCTYPE hello>`.
     Doctype(DoctypeEvent<'a>),
-}
+    /// An event emitted by processing instructions like `<?hello world?>`.
+    ///
+    /// Only emitted if enabled with [`Options::parse_pi_nodes`].
+    PI(PIEvent<'a>),
+    /// An event emitted by the XML declaration like `<?xml version="1.0"?>`.
+    ///
+    /// Only emitted if enabled with [`Options::parse_declaration_node`].
+    Decl(DeclEvent<'a>),
+}
+
+impl<'a> Event<'a> {
+    /// Returns the raw text of this event.
+    pub(crate) fn text(&self) -> &'a str {
+        match self {
+            Event::Start(StartEvent { text, .. })
+            | Event::End(EndEvent { text, .. })
+            | Event::Empty(StartEvent { text, .. })
+            | Event::Text(TextEvent { text, .. })
+            | Event::CData(CDataEvent { text })
+            | Event::Comment(CommentEvent { text })
+            | Event::Doctype(DoctypeEvent { text })
+            | Event::PI(PIEvent { text, .. })
+            | Event::Decl(DeclEvent { text }) => text,
+        }
+    }
+
+    /// Returns a copy of this event that refers to `text`, which must be equal to the
+    /// event's original text, instead.
+    pub(crate) fn with_text<'b>(self, text: &'b str) -> Event<'b> {
+        match self {
+            Event::Start(event) => Event::Start(StartEvent { text, ..event }),
+            Event::End(event) => Event::End(EndEvent { text, ..event }),
+            Event::Empty(event) => Event::Empty(StartEvent { text, ..event }),
+            Event::Text(event) => Event::Text(TextEvent { text, ..event }),
+            Event::CData(_) => Event::CData(CDataEvent { text }),
+            Event::Comment(_) => Event::Comment(CommentEvent { text }),
+            Event::Doctype(_) => Event::Doctype(DoctypeEvent { text }),
+            Event::PI(event) => Event::PI(PIEvent { text, ..event }),
+            Event::Decl(_) => Event::Decl(DeclEvent { text }),
+        }
+    }
+
+    /// Copies this event into an [`OwnedEvent`] that does not borrow from the input.
+    pub fn into_owned(self) -> OwnedEvent {
+        OwnedEvent {
+            text: self.text().into(),
+            event: self.with_text(""),
+        }
+    }
+
+    /// Copies this event into an [`OwnedEvent`] with the prefixed name of a start, empty or
+    /// end tag replaced by `prefix` and `name`, other events are copied unchanged.
+    ///
+    /// The attributes and whitespace of the tag are kept, so the result can be passed to
+    /// [`Writer::write_event`](crate::Writer::write_event) to rename elements or strip
+    /// their prefixes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::{reader::*, Writer};
+    /// let mut writer = Writer::new(Vec::new());
+    /// for event in Reader::new("<x:a b='c'><x:d/></x:a>") {
+    ///     let event = event.unwrap();
+    ///     let name = match event {
+    ///         Event::Start(start) | Event::Empty(start) => start.name(),
+    ///         Event::End(end) => end.name(),
+    ///         _ => unreachable!(),
+    ///     };
+    ///     writer.write_event(&event.with_name(None, name).as_event()).unwrap();
+    /// }
+    /// assert_eq!(writer.finish().unwrap(), b"<a b='c'><d/></a>");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prefix` or `name` is not a valid name component.
+    pub fn with_name(&self, prefix: Option<&str>, name: &str) -> OwnedEvent {
+        let is_valid = |name: &str| !name.is_empty() && !name.bytes().any(is_invalid_name);
+        assert!(
+            prefix.is_none_or(is_valid) && is_valid(name),
+            "Event::with_name called with an invalid name"
+        );
+
+        let (open, rest) = match self {
+            Event::Start(start) | Event::Empty(start) => ("<", &start.text[start.name_end..]),
+            Event::End(end) => ("</", &end.text[end.name_end..]),
+            _ => return self.into_owned(),
+        };
+
+        let mut text = String::from(open);
+        if let Some(prefix) = prefix {
+            text.push_str(prefix);
+            text.push(':');
+        }
+        // The offset of the colon, or of the character before the name without a prefix.
+        let prefix_end = text.len() - 1;
+        text.push_str(name);
+        let name_end = text.len();
+        text.push_str(rest);
+
+        let start = StartEvent {
+            text: "",
+            prefix_end,
+            name_end,
+        };
+        let event = match self {
+            Event::Start(_) => Event::Start(start),
+            Event::Empty(_) => Event::Empty(start),
+            _ => Event::End(EndEvent {
+                text: "",
+                prefix_end,
+                name_end,
+            }),
+        };
+
+        OwnedEvent {
+            text: text.into(),
+            event,
+        }
+    }
+}
+
+/// An [`Event`] that owns its text, created with [`Event::into_owned`].
+///
+/// # Examples
+///
+/// ```
+/// # use speedy_xml::reader::*;
+/// let events = Reader::new("<a>text</a>")
+///     .map(|event| event.map(Event::into_owned))
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+///
+/// let Event::Text(text) = events[1].as_event() else { panic!() };
+/// assert_eq!(text.content(), "text");
+/// ```
+#[derive(Debug, Clone)]
+pub struct OwnedEvent {
+    text: Box<str>,
+    /// The original event with its text replaced by an empty string.
+    event: Event<'static>,
+}
+
+impl OwnedEvent {
+    /// Returns the event borrowing from this owned event.
+    ///
+    /// The `position_in` methods of the returned event must not be used, since its text
+    /// is no longer part of any reader's buffer.
+    pub fn as_event(&self) -> Event<'_> {
+        self.event.with_text(&self.text)
+    }
+}
+
+impl From<Event<'_>> for OwnedEvent {
+    fn from(event: Event<'_>) -> Self {
+        event.into_owned()
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 /// An error that may be emitted by [`Reader`] while parsing XML.
 pub enum ErrorKind {
     /// Top-level text encountered while [`Options`] did not allow it.
     TopLevelText,
     /// An unclosed processing instruction tag encountered.
     UnclosedPITag,
+    /// A processing instruction without a target was encountered.
+    ExpectedPITarget,
+    /// An XML declaration that was not terminated by `?>` encountered.
+    UnclosedDeclaration,
 
     /// End of tag was encountered when an element name was expected.
     ExpectedElementName,
     /// An invalid character was encountered when an element name was expected.
     InvalidElementName,
+    /// An element or attribute name that is not a valid XML name encountered while
+    /// [`Options::strict_names`] was enabled.
+    InvalidName,
     /// An unclosed tag encountered.
     UnclosedElementTag,
     /// An unclosed empty tag encountered.
@@ -301,7 +1030,14 @@ pub enum ErrorKind {
     UnclosedEndTag,
     /// An unclosed element encountered while [`Options`] did not allow it.
     UnclosedElement,
-
+    /// An end tag that does not match the last open element encountered while
+    /// [`Options::validate_closing_tags`] was enabled.
+    MismatchedEndTag,
+    /// An element nested deeper than [`Options::max_depth`] allows encountered.
+    MaxDepthExceeded,
+
+    /// End of tag was encountered when an attribute name was expected after a prefix.
+    ExpectedAttributeName,
     /// Missing `=` character after attribute name.
     ExpectedAttributeEq,
     /// Missing attribute value after `=` character.
@@ -310,6 +1046,17 @@ pub enum ErrorKind {
     InvalidAttributeValue,
     /// An unclosed attribute value was encountered.
     UnclosedAttributeValue,
+    /// An attribute name repeated in the same tag encountered while
+    /// [`Options::forbid_duplicate_attributes`] was enabled.
+    DuplicateAttribute,
+    /// A tag with more attributes than [`Options::max_attributes`] allows encountered.
+    TooManyAttributes,
+    /// An attribute value longer than [`Options::max_attribute_value_length`] allows encountered.
+    AttributeValueTooLong,
+
+    /// A disallowed control character encountered while
+    /// [`Options::forbid_control_characters`] was enabled.
+    InvalidCharacter,
 
     /// An unclosed comment was encountered.
     UnclosedComment,
@@ -319,6 +1066,23 @@ pub enum ErrorKind {
     UnclosedUnknownSpecial,
     /// An unclosed doctype tag encountered.
     DoctypeEof,
+    /// A doctype declaration encountered while [`Options::forbid_doctype`] was enabled.
+    DoctypeForbidden,
+
+    /// An entity expansion exceeding the limits set in [`Options`] encountered.
+    EntityExpansionLimit,
+    /// More events than [`Options::max_total_events`] allows encountered.
+    TooManyEvents,
+    /// An event larger than [`Options::max_event_size`] allows encountered.
+    EventTooLarge,
+
+    /// An element or attribute name with a prefix that is not bound to a namespace
+    /// encountered by a [`NsReader`](crate::NsReader) while
+    /// [`Options::forbid_undeclared_prefixes`] was enabled.
+    UndeclaredPrefix,
+
+    /// The [`CancelToken`] set with [`Options::cancel_token`] was cancelled.
+    Cancelled,
 }
 
 impl ErrorKind {
@@ -327,29 +1091,148 @@ impl ErrorKind {
         match self {
             Self::TopLevelText => "top-level text is forbidden",
             Self::UnclosedPITag => "unclosed processing instruction",
+            Self::ExpectedPITarget => "expected processing instruction target",
+            Self::UnclosedDeclaration => "expected `?>`",
 
             Self::ExpectedElementName => "expected element name",
             Self::InvalidElementName => "invalid element name",
+            Self::InvalidName => "invalid XML name",
             Self::UnclosedElementTag => "expected a `>` or `/`",
             Self::UnclosedEmptyElementTag => "expected a `>`",
             Self::UnclosedEndTag => "expected a `>`",
             Self::UnclosedElement => "unclosed element",
+            Self::MismatchedEndTag => "mismatched end tag",
+            Self::MaxDepthExceeded => "maximum nesting depth exceeded",
 
+            Self::ExpectedAttributeName => "expected attribute name",
             Self::ExpectedAttributeEq => "expected `=` after attribute name",
             Self::ExpectedAttributeValue => {
                 "expected an attribute value enclosed in either `'` or `\"`"
             }
             Self::UnclosedAttributeValue => "unclosed attribute value",
             Self::InvalidAttributeValue => "attribute value contains null byte",
+            Self::DuplicateAttribute => "duplicate attribute",
+            Self::TooManyAttributes => "too many attributes",
+            Self::AttributeValueTooLong => "attribute value too long",
+
+            Self::InvalidCharacter => "disallowed control character",
 
             Self::UnclosedComment => "unclosed comment",
             Self::UnclosedCData => "unclosed cdata",
             Self::UnclosedUnknownSpecial => "unclosed unknown <! tag",
             Self::DoctypeEof => "unexpected end of file in <!DOCTYPE",
+            Self::DoctypeForbidden => "doctype declarations are forbidden",
+
+            Self::EntityExpansionLimit => "entity expansion limit exceeded",
+            Self::TooManyEvents => "too many events",
+            Self::EventTooLarge => "event too large",
+
+            Self::UndeclaredPrefix => "undeclared namespace prefix",
+
+            Self::Cancelled => "parsing was cancelled",
+        }
+    }
+
+    /// Returns a short identifier for this error kind, like `"unclosed-comment"`.
+    ///
+    /// Unlike [`Self::message`], codes are guaranteed to stay the same across releases.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::TopLevelText => "top-level-text",
+            Self::UnclosedPITag => "unclosed-pi",
+            Self::ExpectedPITarget => "expected-pi-target",
+            Self::UnclosedDeclaration => "unclosed-declaration",
+            Self::ExpectedElementName => "expected-element-name",
+            Self::InvalidElementName => "invalid-element-name",
+            Self::InvalidName => "invalid-name",
+            Self::UnclosedElementTag => "unclosed-element-tag",
+            Self::UnclosedEmptyElementTag => "unclosed-empty-element-tag",
+            Self::UnclosedEndTag => "unclosed-end-tag",
+            Self::UnclosedElement => "unclosed-element",
+            Self::MismatchedEndTag => "mismatched-end-tag",
+            Self::MaxDepthExceeded => "max-depth-exceeded",
+            Self::ExpectedAttributeName => "expected-attribute-name",
+            Self::ExpectedAttributeEq => "expected-attribute-eq",
+            Self::ExpectedAttributeValue => "expected-attribute-value",
+            Self::UnclosedAttributeValue => "unclosed-attribute-value",
+            Self::InvalidAttributeValue => "invalid-attribute-value",
+            Self::DuplicateAttribute => "duplicate-attribute",
+            Self::TooManyAttributes => "too-many-attributes",
+            Self::AttributeValueTooLong => "attribute-value-too-long",
+            Self::InvalidCharacter => "invalid-character",
+            Self::UnclosedComment => "unclosed-comment",
+            Self::UnclosedCData => "unclosed-cdata",
+            Self::UnclosedUnknownSpecial => "unclosed-unknown-special",
+            Self::DoctypeEof => "doctype-eof",
+            Self::DoctypeForbidden => "doctype-forbidden",
+            Self::EntityExpansionLimit => "entity-expansion-limit",
+            Self::TooManyEvents => "too-many-events",
+            Self::EventTooLarge => "event-too-large",
+            Self::UndeclaredPrefix => "undeclared-prefix",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    /// Returns the broad category this error kind belongs to.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::UnclosedPITag
+            | Self::ExpectedPITarget
+            | Self::UnclosedDeclaration
+            | Self::ExpectedElementName
+            | Self::InvalidElementName
+            | Self::InvalidName
+            | Self::UnclosedElementTag
+            | Self::UnclosedEmptyElementTag
+            | Self::UnclosedEndTag
+            | Self::ExpectedAttributeName
+            | Self::ExpectedAttributeEq
+            | Self::ExpectedAttributeValue
+            | Self::UnclosedAttributeValue
+            | Self::UnclosedComment
+            | Self::UnclosedCData
+            | Self::UnclosedUnknownSpecial
+            | Self::DoctypeEof => ErrorCategory::Syntax,
+            Self::TopLevelText
+            | Self::UnclosedElement
+            | Self::MismatchedEndTag
+            | Self::DuplicateAttribute
+            | Self::UndeclaredPrefix => ErrorCategory::Structure,
+            Self::InvalidAttributeValue | Self::InvalidCharacter => ErrorCategory::Character,
+            Self::MaxDepthExceeded
+            | Self::TooManyAttributes
+            | Self::AttributeValueTooLong
+            | Self::EntityExpansionLimit
+            | Self::TooManyEvents
+            | Self::EventTooLarge => ErrorCategory::Limit,
+            Self::DoctypeForbidden => ErrorCategory::Forbidden,
+            Self::Cancelled => ErrorCategory::Cancelled,
         }
     }
 }
 
+/// A broad category of errors, returned by [`ErrorKind::category`] and
+/// [`DecodeError::category`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// Malformed markup, like an unclosed tag or a missing attribute value.
+    Syntax,
+    /// Markup that is well-formed on its own but violates the structure of a document,
+    /// like mismatched end tags or duplicate attributes.
+    Structure,
+    /// A character that is not allowed in the document.
+    Character,
+    /// A limit set in [`Options`] was exceeded.
+    Limit,
+    /// A construct that was forbidden in [`Options`] was encountered.
+    Forbidden,
+    /// Parsing was cancelled with a [`CancelToken`].
+    Cancelled,
+    /// The document could not be decoded, see [`DecodeError::category`].
+    Encoding,
+}
+
 impl Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(self.message())
@@ -361,11 +1244,52 @@ impl Display for ErrorKind {
 pub struct Error {
     kind: ErrorKind,
     span: Range<usize>,
+    mismatch: Option<Box<NameMismatch>>,
+}
+
+#[derive(Clone)]
+struct NameMismatch {
+    expected: Box<str>,
+    found: Box<str>,
+    opened_at: Range<usize>,
 }
 
 impl Error {
-    fn new(kind: ErrorKind, span: Range<usize>) -> Self {
-        Self { kind, span }
+    pub(crate) fn new(kind: ErrorKind, span: Range<usize>) -> Self {
+        Self {
+            kind,
+            span,
+            mismatch: None,
+        }
+    }
+
+    /// Moves this error's span forward by `offset` bytes.
+    pub(crate) fn shifted(mut self, offset: usize) -> Self {
+        self.span = self.span.start + offset..self.span.end + offset;
+        self
+    }
+
+    /// Returns the name of the element that was expected to be closed if this is an
+    /// [`ErrorKind::MismatchedEndTag`] error.
+    pub fn expected_name(&self) -> Option<&str> {
+        self.mismatch.as_ref().map(|mismatch| &*mismatch.expected)
+    }
+
+    /// Returns the name of the end tag that was found instead if this is an
+    /// [`ErrorKind::MismatchedEndTag`] error.
+    pub fn found_name(&self) -> Option<&str> {
+        self.mismatch.as_ref().map(|mismatch| &*mismatch.found)
+    }
+
+    /// Returns the span of the expected element's name in its start tag if this is an
+    /// [`ErrorKind::MismatchedEndTag`] error.
+    ///
+    /// Unlike [`Self::span`], which covers the name in the end tag that was found, this
+    /// points at the start tag that was left unclosed.
+    pub fn opened_at(&self) -> Option<Range<usize>> {
+        self.mismatch
+            .as_ref()
+            .map(|mismatch| mismatch.opened_at.clone())
     }
 
     /// Returns this error's [`ErrorKind`].
@@ -377,6 +1301,47 @@ impl Error {
     pub fn span(&self) -> Range<usize> {
         self.span.clone()
     }
+
+    /// Returns the 1-based line and column of the start of this error's span in `source`,
+    /// which must be the document that was parsed.
+    ///
+    /// Columns are counted in characters.
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        let (line, column, _) = locate(source, self.span.start);
+        (line, column)
+    }
+
+    /// Returns a value that displays this error together with the offending line of
+    /// `source`, which must be the document that was parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let source = "<root>\n  <a></b>\n</root>";
+    /// let options = Options::default().validate_closing_tags(true);
+    /// let error = Reader::with_options(source, options)
+    ///     .find_map(Result::err)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     error.display_with_source(source).to_string(),
+    ///     concat!(
+    ///         "error: mismatched end tag, expected `</a>` but found `</b>`\n",
+    ///         " --> 2:8\n",
+    ///         "  |\n",
+    ///         "2 |   <a></b>\n",
+    ///         "  |        ^\n",
+    ///         "  = note: `<a>` was opened at 2:4\n",
+    ///     )
+    /// );
+    /// ```
+    pub fn display_with_source<'s>(&'s self, source: &'s str) -> SourceSnippet<'s> {
+        SourceSnippet {
+            error: self,
+            source,
+        }
+    }
 }
 
 impl std::error::Error for Error {}
@@ -389,10 +1354,162 @@ impl Debug for Error {
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "parse error at {:?}: {}", self.span, self.kind)
+        write!(f, "parse error at {:?}: {}", self.span, self.kind)?;
+        if let Some(NameMismatch {
+            expected,
+            found,
+            opened_at,
+        }) = self.mismatch.as_deref()
+        {
+            write!(
+                f,
+                ", expected `</{expected}>` but found `</{found}>` (`<{expected}>` opened at {opened_at:?})"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An error returned by [`TextEvent::parse`] and [`AttributeEvent::parse`] when the
+/// unescaped value could not be parsed.
+#[derive(Clone)]
+pub struct ParseValueError<'a, E> {
+    /// The escaped text the value originated from.
+    raw: &'a str,
+    value: Cow<'a, str>,
+    error: E,
+}
+
+impl<'a, E> ParseValueError<'a, E> {
+    /// Parses `value`, the unescaped form of `raw`, after trimming whitespace.
+    fn parse<T: FromStr<Err = E>>(raw: &'a str, value: Cow<'a, str>) -> Result<T, Self> {
+        value
+            .trim_matches(|c: char| c.is_ascii() && is_whitespace(c as u8))
+            .parse()
+            .map_err(|error| Self { raw, value, error })
+    }
+
+    /// Returns the unescaped value that could not be parsed.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns the error returned by [`FromStr::from_str`].
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the error returned by [`FromStr::from_str`].
+    pub fn into_error(self) -> E {
+        self.error
+    }
+
+    /// Returns the span of the escaped value in `reader`.
+    ///
+    /// # Panics
+    ///
+    /// May panic if `reader` is not the [`Reader`] that the value originated from.
+    pub fn position_in(&self, reader: &Reader) -> Range<usize> {
+        reader.range_for_ptrs(self.raw.as_bytes().as_ptr_range())
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ParseValueError<'_, E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<E: Display> Debug for ParseValueError<'_, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as Display>::fmt(self, f)
+    }
+}
+
+impl<E: Display> Display for ParseValueError<'_, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid value {:?}: {}", self.value, self.error)
+    }
+}
+
+/// Returns the 1-based line and column of `offset` in `source`, with columns counted in
+/// characters, along with the range of the line.
+fn locate(source: &str, offset: usize) -> (usize, usize, Range<usize>) {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    let line_start = memchr::memrchr(b'\n', &source.as_bytes()[..offset]).map_or(0, |idx| idx + 1);
+    let line_end = memchr::memchr(b'\n', &source.as_bytes()[offset..])
+        .map_or(source.len(), |idx| offset + idx);
+    let line = memchr::memchr_iter(b'\n', &source.as_bytes()[..line_start]).count() + 1;
+    let column = source[line_start..offset].chars().count() + 1;
+
+    (line, column, line_start..line_end)
+}
+
+/// An [`Error`] displayed together with the line of the document it occurred on, created
+/// with [`Error::display_with_source`].
+pub struct SourceSnippet<'a> {
+    error: &'a Error,
+    source: &'a str,
+}
+
+impl Display for SourceSnippet<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { error, source } = self;
+        let (line, column, line_range) = locate(source, error.span.start);
+
+        write!(f, "error: {}", error.kind)?;
+        if let Some(NameMismatch {
+            expected, found, ..
+        }) = error.mismatch.as_deref()
+        {
+            write!(f, ", expected `</{expected}>` but found `</{found}>`")?;
+        }
+        writeln!(f)?;
+
+        let text = source[line_range.clone()].trim_end_matches('\r');
+        let span_end = error
+            .span
+            .end
+            .min(line_range.start + text.len())
+            .max(error.span.start);
+        let width = source
+            .get(error.span.start..span_end)
+            .map_or(0, |span| span.chars().count())
+            .max(1);
+        let gutter = " ".repeat(line.to_string().len());
+
+        writeln!(f, "{gutter}--> {line}:{column}")?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{line} | {text}")?;
+        writeln!(
+            f,
+            "{gutter} | {}{}",
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        )?;
+
+        if let Some(NameMismatch {
+            expected,
+            opened_at,
+            ..
+        }) = error.mismatch.as_deref()
+        {
+            let (line, column, _) = locate(source, opened_at.start);
+            writeln!(
+                f,
+                "{gutter} = note: `<{expected}>` was opened at {line}:{column}"
+            )?;
+        }
+
+        Ok(())
     }
 }
 
+#[derive(Clone)]
 struct ParsingBuffer<'a> {
     text: &'a str,
     current: usize,
@@ -424,6 +1541,22 @@ impl<'a> ParsingBuffer<'a> {
     }
 
     #[inline]
+    /// Returns the end of the unquoted attribute value starting at `start`.
+    fn unquoted_value_end(&self, start: usize) -> usize {
+        let mut current = start;
+        while let Some(b) = self.byte(current) {
+            if is_whitespace(b)
+                || b == b'>'
+                || b == b'\0'
+                || (b == b'/' && self.byte(current + 1) == Some(b'>'))
+            {
+                break;
+            }
+            current += 1;
+        }
+        current
+    }
+
     fn position_or_end(&self, start: usize, fun: impl Fn(u8) -> bool) -> usize {
         let mut current = start;
         // Iterators seem to generate pretty bad code here, use a loop instead.
@@ -458,6 +1591,98 @@ impl<'a> ParsingBuffer<'a> {
     }
 }
 
+/// Compares two names, ignoring ASCII case if `ignore_case` is `true`.
+fn names_equal(a: &str, b: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+/// The names of all currently open elements, stored in a single buffer so that
+/// pushing an element does not require an allocation in the common case.
+///
+/// Alongside each name the position of the name in its start tag is kept, relative
+/// to the start of the whole document, together with whether the element is in an
+/// `xml:space="preserve"` scope. Values of `xml:lang` attributes are kept separately
+/// along with the index of the element that declared them.
+#[derive(Debug, Default, Clone)]
+struct ElementStack {
+    names: String,
+    name_starts: Vec<usize>,
+    positions: Vec<usize>,
+    preserve_space: Vec<bool>,
+    langs: Vec<(usize, Box<str>)>,
+}
+
+impl ElementStack {
+    fn push(&mut self, name: &str, position: usize, preserve_space: bool, lang: Option<&str>) {
+        if let Some(lang) = lang {
+            self.langs.push((self.name_starts.len(), lang.into()));
+        }
+        self.name_starts.push(self.names.len());
+        self.positions.push(position);
+        self.preserve_space.push(preserve_space);
+        self.names.push_str(name);
+    }
+
+    fn pop(&mut self) {
+        if let Some(start) = self.name_starts.pop() {
+            self.positions.pop();
+            self.preserve_space.pop();
+            self.names.truncate(start);
+            if self
+                .langs
+                .last()
+                .is_some_and(|&(idx, _)| idx == self.name_starts.len())
+            {
+                self.langs.pop();
+            }
+        }
+    }
+
+    fn names(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator + '_ {
+        (0..self.name_starts.len()).map(|idx| {
+            let end = self
+                .name_starts
+                .get(idx + 1)
+                .copied()
+                .unwrap_or(self.names.len());
+            &self.names[self.name_starts[idx]..end]
+        })
+    }
+
+    fn last_name(&self) -> Option<&str> {
+        self.name_starts.last().map(|&start| &self.names[start..])
+    }
+
+    /// Returns the span of the innermost open element's name in its start tag.
+    fn last_span(&self) -> Option<Range<usize>> {
+        let name = self.last_name()?;
+        let start = *self.positions.last()?;
+        Some(start..start + name.len())
+    }
+
+    /// Returns whether the innermost open element is in an `xml:space="preserve"` scope.
+    fn preserves_space(&self) -> bool {
+        self.preserve_space.last().copied().unwrap_or(false)
+    }
+
+    /// Returns the `xml:lang` value in scope of the innermost open element.
+    fn lang(&self) -> Option<&str> {
+        self.langs.last().map(|(_, lang)| &**lang)
+    }
+
+    fn clear(&mut self) {
+        self.names.clear();
+        self.name_starts.clear();
+        self.positions.clear();
+        self.preserve_space.clear();
+        self.langs.clear();
+    }
+}
+
 /// An iterator over the attributes of a [`StartEvent`], obtained via [`StartEvent::attributes`].
 pub struct Attributes<'a>(ParsingBuffer<'a>);
 
@@ -465,38 +1690,116 @@ impl<'a> Iterator for Attributes<'a> {
     type Item = AttributeEvent<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let whitespace_start = self.0.current;
         self.0.skip_whitespace();
 
         let name_start = self.0.current;
-        let name_end = self
+        let leading_whitespace = &self.0.text[whitespace_start..name_start];
+        let mut name_end = self
             .0
             .position_or_end(self.0.current, is_invalid_attribute_name);
         if name_end == self.0.current {
             return None;
         }
+
+        let mut prefix_end = 0;
+        if self.0.byte(name_end) == Some(b':') {
+            prefix_end = name_end - name_start;
+            name_end = self
+                .0
+                .position_or_end(name_end + 1, is_invalid_attribute_name);
+        }
         self.0.current = name_end;
 
         self.0.skip_whitespace();
+        if self.0.byte(self.0.current) != Some(b'=') {
+            // Leave the whitespace for the next attribute.
+            self.0.current = name_end;
+            return Some(AttributeEvent {
+                text: &self.0.text[name_start..name_end],
+                leading_whitespace,
+                prefix_end,
+                name_end: name_end - name_start,
+                value_start: name_end - name_start,
+            });
+        }
         self.0.current += 1;
         self.0.skip_whitespace();
 
-        let quote = self.0.byte(self.0.current).unwrap();
-
-        self.0.current += 1;
-
-        let value_start = self.0.current;
-        let value_end = self.0.memchr(self.0.current, quote).unwrap();
-
-        self.0.current = value_end + 1;
+        let value_start;
+        match self.0.byte(self.0.current).unwrap() {
+            quote @ (b'\'' | b'"') => {
+                value_start = self.0.current + 1;
+                self.0.current = self.0.memchr(value_start, quote).unwrap() + 1;
+            }
+            _ => {
+                value_start = self.0.current;
+                self.0.current = self.0.unquoted_value_end(value_start);
+            }
+        }
 
         Some(AttributeEvent {
             text: &self.0.text[name_start..self.0.current],
+            leading_whitespace,
+            prefix_end,
             name_end: name_end - name_start,
             value_start: value_start - name_start,
         })
     }
 }
 
+/// The elements that can never have any content in HTML.
+const HTML_VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+fn is_void_element(name: &str) -> bool {
+    HTML_VOID_ELEMENTS
+        .iter()
+        .any(|void| void.eq_ignore_ascii_case(name))
+}
+
+/// A handle used to abort parsing from another thread, set with [`Options::cancel_token`].
+///
+/// Clones of a token share its state, so cancelling any clone cancels all of them.
+///
+/// # Examples
+///
+/// ```
+/// # use speedy_xml::reader::*;
+/// let token = CancelToken::new();
+/// let mut reader = Reader::with_options(
+///     "<a><b/><c/></a>",
+///     Options::default().cancel_token(token.clone()),
+/// );
+/// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
+///
+/// token.cancel();
+/// let error = reader.next().unwrap().unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::Cancelled);
+/// assert!(reader.next().is_none());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Creates a new token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels this token and all of its clones.
+    pub fn cancel(&self) {
+        self.0.store(true, atomic::Ordering::Relaxed);
+    }
+
+    /// Returns whether this token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(atomic::Ordering::Relaxed)
+    }
+}
+
 #[non_exhaustive]
 #[derive(Default, Debug, Clone)]
 /// XML reader options.
@@ -504,6 +1807,44 @@ pub struct Options {
     allow_top_level_text: bool,
     allow_unmatched_closing_tags: bool,
     allow_unclosed_tags: bool,
+    allow_truncated_input: bool,
+    parse_pi_nodes: bool,
+    parse_declaration_node: bool,
+    recover: bool,
+    trim_text: bool,
+    coalesce_text: bool,
+    validate_closing_tags: bool,
+    forbid_duplicate_attributes: bool,
+    lazy_attributes: bool,
+    max_depth: Option<u32>,
+    max_entity_expansions: Option<usize>,
+    max_entity_expansion_size: Option<usize>,
+    max_attributes: Option<usize>,
+    max_attribute_value_length: Option<usize>,
+    max_total_events: Option<usize>,
+    max_event_size: Option<usize>,
+    cancel_token: Option<CancelToken>,
+    text_chunk_size: Option<usize>,
+    collect_errors: bool,
+    skip_comments: bool,
+    cdata_as_text: bool,
+    html_void_elements: bool,
+    raw_text_elements: Vec<Box<str>>,
+    only_elements: Vec<Box<str>>,
+    allow_unquoted_attribute_values: bool,
+    allow_valueless_attributes: bool,
+    ignore_name_case: bool,
+    forbid_control_characters: bool,
+    strict_names: bool,
+    xml_version_1_1: bool,
+    forbid_doctype: bool,
+    forbid_undeclared_prefixes: bool,
+    normalize_line_endings: bool,
+    normalize_attribute_values: bool,
+    #[cfg(feature = "flate2")]
+    pub(crate) disable_gzip_detection: bool,
+    #[cfg(feature = "flate2")]
+    pub(crate) max_decompressed_size: Option<usize>,
 }
 
 impl Options {
@@ -524,597 +1865,4141 @@ impl Options {
         self.allow_unclosed_tags = value;
         self
     }
-}
 
-/// An XML reader.
-pub struct Reader<'a> {
-    buffer: ParsingBuffer<'a>,
-    depth: u32,
-    options: Options,
-}
+    /// Changes whether a comment, cdata section or start tag cut off by the end of the input
+    /// should be emitted as a final event instead of causing an error.
+    ///
+    /// Such events can be recognized with their `is_truncated` methods, like
+    /// [`StartEvent::is_truncated`]. No events are emitted after a truncated event, not even
+    /// errors about unclosed elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let options = Options::default().allow_truncated_input(true);
+    /// let mut reader = Reader::with_options("<log><entry level='info' time='12:", options);
+    /// reader.next();
+    /// let Some(Ok(Event::Start(entry))) = reader.next() else { panic!() };
+    /// assert!(entry.is_truncated());
+    /// assert_eq!(entry.get_attribute("level").unwrap().value(), "info");
+    /// assert!(entry.get_attribute("time").is_none());
+    /// assert!(reader.next().is_none());
+    /// ```
+    pub fn allow_truncated_input(mut self, value: bool) -> Self {
+        self.allow_truncated_input = value;
+        self
+    }
 
-impl<'a> Reader<'a> {
-    /// Creates a new XML reader that will parse the contents of `text`.
-    pub fn new(text: &'a str) -> Self {
-        Self {
-            buffer: ParsingBuffer::new(text),
-            depth: 0,
-            options: Default::default(),
-        }
+    /// Changes whether processing instructions should be emitted as [`Event::PI`] events
+    /// instead of being skipped.
+    ///
+    /// This is equivalent to RapidXML's `parse_pi_nodes` flag, the XML declaration
+    /// is never treated as a processing instruction.
+    pub fn parse_pi_nodes(mut self, value: bool) -> Self {
+        self.parse_pi_nodes = value;
+        self
     }
 
-    /// Creates a new XML reader that will parse the contents of `text` with the provided [`Options`].
-    pub fn with_options(text: &'a str, options: Options) -> Self {
-        Self {
-            buffer: ParsingBuffer::new(text),
-            depth: 0,
-            options,
-        }
+    /// Changes whether the XML declaration should be parsed and emitted as an
+    /// [`Event::Decl`] event instead of being skipped.
+    ///
+    /// This is equivalent to RapidXML's `parse_declaration_node` flag.
+    pub fn parse_declaration_node(mut self, value: bool) -> Self {
+        self.parse_declaration_node = value;
+        self
     }
 
-    /// Returns the string that this reader was originally created with.
-    pub fn buffer(&self) -> &'a str {
-        self.buffer.text
+    /// Changes whether the reader should try to continue parsing after encountering
+    /// malformed markup.
+    ///
+    /// When enabled, the reader will still return an error for every malformed node but
+    /// afterwards it will skip over the offending markup and continue producing events.
+    ///
+    /// # Notes
+    ///
+    /// Skipped start or end tags are not accounted for in [`Reader::depth`], which means
+    /// that the resulting event stream may be unbalanced.
+    pub fn recover(mut self, value: bool) -> Self {
+        self.recover = value;
+        self
     }
 
-    /// Returns the element depth the parser is currently at.
-    pub fn depth(&self) -> u32 {
-        self.depth
+    /// Changes whether errors the reader can continue after should be collected instead of
+    /// being returned.
+    ///
+    /// Collected errors are available through [`Reader::errors`]. Errors that stop the
+    /// reader are still returned, combine this with [`Self::recover`] to continue after
+    /// most errors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let options = Options::default().recover(true).collect_errors(true);
+    /// let mut reader = Reader::with_options("<a><b c/><d e=/></a>", options);
+    /// assert!(reader.by_ref().all(|event| event.is_ok()));
+    /// let kinds = reader.errors().iter().map(Error::kind).collect::<Vec<_>>();
+    /// assert_eq!(kinds, [ErrorKind::ExpectedAttributeEq, ErrorKind::ExpectedAttributeValue]);
+    /// ```
+    pub fn collect_errors(mut self, value: bool) -> Self {
+        self.collect_errors = value;
+        self
     }
 
-    fn range_for_ptrs(&self, range: Range<*const u8>) -> Range<usize> {
-        let self_range = self.buffer.as_bytes().as_ptr_range();
-        assert!(
-            self_range.start <= range.start
-                && self_range.end >= range.end
-                && range.start <= range.end,
-            "Parser::range_for_ptrs called with invalid pointer range"
-        );
+    /// Changes whether leading and trailing whitespace should be trimmed from text events.
+    ///
+    /// Text events that consist entirely of whitespace will not be emitted at all.
+    ///
+    /// Text inside of elements with an `xml:space="preserve"` attribute, and their
+    /// descendants, is left untouched unless a descendant resets the scope with
+    /// `xml:space="default"`.
+    pub fn trim_text(mut self, value: bool) -> Self {
+        self.trim_text = value;
+        self
+    }
 
-        range.start.addr() - self_range.start.addr()..range.end.addr() - self_range.start.addr()
+    /// Changes whether adjacent text and cdata should be merged into a single [`Event::Text`].
+    ///
+    /// When enabled, cdata will never be emitted as an [`Event::CData`], instead it will
+    /// always be emitted as part of a text event. The content of such events is available
+    /// through [`TextEvent::content`] while [`TextEvent::raw_content`] will contain the
+    /// original cdata markup.
+    pub fn coalesce_text(mut self, value: bool) -> Self {
+        self.coalesce_text = value;
+        self
     }
 
-    fn set_error_state(&mut self) {
-        self.buffer.current = self.buffer.text.len();
-        self.depth = 0;
+    /// Changes whether the names of end tags should be checked against the names of the
+    /// elements they close.
+    ///
+    /// This is equivalent to RapidXML's `parse_validate_closing_tags` flag.
+    pub fn validate_closing_tags(mut self, value: bool) -> Self {
+        self.validate_closing_tags = value;
+        self
     }
 
-    #[inline]
-    fn bytes(&self) -> &'a [u8] {
-        self.buffer.as_bytes()
+    /// Changes whether attributes with the same name appearing multiple times in the same
+    /// tag should be treated as an error.
+    pub fn forbid_duplicate_attributes(mut self, value: bool) -> Self {
+        self.forbid_duplicate_attributes = value;
+        self
     }
 
-    #[inline]
-    fn byte(&self, idx: usize) -> Option<u8> {
-        self.buffer.byte(idx)
+    /// Changes whether start tags should only be scanned for their end instead of parsing
+    /// their attributes while reading.
+    ///
+    /// Attributes are then only parsed when they are iterated over with
+    /// [`StartEvent::attributes`], which makes skipping over elements whose attributes are
+    /// never looked at cheaper. Malformed attributes are not reported as errors and the
+    /// checks and limits that apply to attributes, like [`Self::forbid_duplicate_attributes`]
+    /// and [`Self::max_attributes`], are not enforced. This has no effect if
+    /// [`Self::allow_unquoted_attribute_values`] is enabled.
+    pub fn lazy_attributes(mut self, value: bool) -> Self {
+        self.lazy_attributes = value;
+        self
     }
 
-    fn skip_element_attributes(&mut self) -> Result<(), Error> {
-        loop {
-            self.buffer.skip_whitespace();
+    /// Changes the maximum number of elements that can be nested inside each other.
+    ///
+    /// Encountering an element that would exceed this limit, including an empty one,
+    /// is treated as an error. There is no limit by default.
+    pub fn max_depth(mut self, value: u32) -> Self {
+        self.max_depth = Some(value);
+        self
+    }
 
-            let name_start = self.buffer.current;
-            let name_end = self
-                .buffer
-                .position_or_end(self.buffer.current, is_invalid_attribute_name);
-            if name_end == self.buffer.current {
-                return Ok(());
-            }
-            self.buffer.current = name_end;
+    /// Changes the maximum number of entities that can be expanded in a single piece of
+    /// text or attribute value when unescaping with a custom [`EntityResolver`].
+    ///
+    /// The predefined entities and character references do not count towards this limit.
+    /// There is no limit by default.
+    pub fn max_entity_expansions(mut self, value: usize) -> Self {
+        self.max_entity_expansions = Some(value);
+        self
+    }
 
-            self.buffer.skip_whitespace();
+    /// Changes the maximum total size in bytes of the replacement text of entities expanded
+    /// in a single piece of text or attribute value when unescaping with a custom
+    /// [`EntityResolver`].
+    ///
+    /// There is no limit by default.
+    pub fn max_entity_expansion_size(mut self, value: usize) -> Self {
+        self.max_entity_expansion_size = Some(value);
+        self
+    }
 
-            if self.byte(self.buffer.current) != Some(b'=') {
-                self.set_error_state();
-                return Err(Error::new(
-                    ErrorKind::ExpectedAttributeEq,
-                    name_start..name_end,
-                ));
-            };
+    /// Changes the maximum number of events a reader will emit.
+    ///
+    /// Exceeding the limit is a fatal [`ErrorKind::TooManyEvents`] error, even with
+    /// [`Self::recover`] enabled. There is no limit by default.
+    pub fn max_total_events(mut self, value: usize) -> Self {
+        self.max_total_events = Some(value);
+        self
+    }
 
-            self.buffer.current += 1;
+    /// Changes the maximum size in bytes of the markup of a single event, like the raw
+    /// content of a text event or a whole start tag.
+    ///
+    /// When parsing incrementally with a [`PushParser`](crate::push::PushParser), this also
+    /// limits how much input an incomplete event may buffer, so a huge text node is rejected
+    /// before it is read completely. Exceeding the limit is a fatal
+    /// [`ErrorKind::EventTooLarge`] error. There is no limit by default.
+    pub fn max_event_size(mut self, value: usize) -> Self {
+        self.max_event_size = Some(value);
+        self
+    }
 
-            let eq_end = self.buffer.current;
+    /// Changes whether gzip-compressed input is left as is instead of being detected and
+    /// decompressed by [`Reader::from_file`] and the readers pulling from I/O sources.
+    ///
+    /// This is only available with the `flate2` feature.
+    #[cfg(feature = "flate2")]
+    pub fn disable_gzip_detection(mut self, value: bool) -> Self {
+        self.disable_gzip_detection = value;
+        self
+    }
 
-            self.buffer.skip_whitespace();
+    /// Changes the maximum size in bytes of a decompressed gzip-compressed document, which
+    /// protects against small inputs that decompress into huge documents.
+    ///
+    /// Exceeding the limit is an [`InvalidData`](std::io::ErrorKind::InvalidData) I/O error.
+    /// There is no limit by default. This is only available with the `flate2` feature.
+    #[cfg(feature = "flate2")]
+    pub fn max_decompressed_size(mut self, value: usize) -> Self {
+        self.max_decompressed_size = Some(value);
+        self
+    }
 
-            let Some(quote) = self
-                .byte(self.buffer.current)
-                .filter(|b| [b'\'', b'\"'].contains(b))
-            else {
-                self.set_error_state();
-                return Err(Error::new(
-                    ErrorKind::ExpectedAttributeValue,
-                    name_start..eq_end,
-                ));
-            };
+    /// Sets a [`CancelToken`] that is checked before every node is parsed, including nodes
+    /// that are skipped without producing events.
+    ///
+    /// Once the token is cancelled, reading fails with a fatal [`ErrorKind::Cancelled`]
+    /// error, even with [`Self::recover`] enabled.
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel_token = Some(token);
+        self
+    }
 
-            self.buffer.current += 1;
+    /// Changes the size in bytes above which text is split into multiple consecutive
+    /// [`Event::Text`] events.
+    ///
+    /// When parsing incrementally with a [`PushParser`](crate::push::PushParser), a chunk is
+    /// emitted as soon as enough text is available, so a huge text node never has to be
+    /// buffered completely. Chunks never split a character or an entity reference. With
+    /// [`Self::trim_text`] enabled, leading whitespace is only trimmed from the first chunk
+    /// with any other content and trailing whitespace only from the last one. Text that
+    /// contains cdata sections coalesced with [`Self::coalesce_text`] and raw text read with
+    /// [`Self::raw_text_elements`] is not split. Text is not split by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let options = Options::default().text_chunk_size(4);
+    /// let chunks = Reader::with_options("<a>abc&amp;def</a>", options)
+    ///     .filter_map(|event| match event.unwrap() {
+    ///         Event::Text(text) => Some(text.content().into_owned()),
+    ///         _ => None,
+    ///     })
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(chunks, ["abc", "&", "def"]);
+    /// ```
+    pub fn text_chunk_size(mut self, value: usize) -> Self {
+        self.text_chunk_size = Some(value);
+        self
+    }
 
-            let value_start = self.buffer.current;
-            let Some(value_end) = self.buffer.memchr2(self.buffer.current, quote, b'\0') else {
-                self.set_error_state();
-                return Err(Error::new(
-                    ErrorKind::UnclosedAttributeValue,
-                    self.buffer.current..(self.buffer.current + 1).min(self.buffer.text.len()),
-                ));
-            };
+    /// Changes the maximum number of attributes a single tag can have.
+    ///
+    /// There is no limit by default.
+    pub fn max_attributes(mut self, value: usize) -> Self {
+        self.max_attributes = Some(value);
+        self
+    }
 
-            if self.bytes()[value_end] == b'\0' {
-                self.set_error_state();
-                return Err(Error::new(
-                    ErrorKind::InvalidAttributeValue,
-                    value_start..value_end + 1,
-                ));
-            }
+    /// Changes the maximum length in bytes of a raw attribute value.
+    ///
+    /// There is no limit by default.
+    pub fn max_attribute_value_length(mut self, value: usize) -> Self {
+        self.max_attribute_value_length = Some(value);
+        self
+    }
 
-            self.buffer.current = value_end + 1;
-        }
+    /// Changes whether comments should be skipped instead of being emitted as [`Event::Comment`].
+    pub fn skip_comments(mut self, value: bool) -> Self {
+        self.skip_comments = value;
+        self
     }
 
-    fn skip_doctype(&mut self) -> Result<(), Error> {
-        loop {
-            match self.buffer.memchr2(self.buffer.current, b'>', b'[') {
-                Some(idx) if self.bytes()[idx] == b'[' => {
-                    self.buffer.current = idx + 1;
-                    let mut depth = 1;
-                    while depth > 0 {
-                        match self.buffer.memchr2(self.buffer.current, b'[', b']') {
-                            Some(idx) => {
-                                if self.bytes()[idx] == b'[' {
-                                    depth += 1;
-                                } else {
-                                    depth -= 1;
-                                }
-                                self.buffer.current = idx + 1;
-                            }
-                            None => {
-                                self.set_error_state();
-                                return Err(Error::new(
-                                    ErrorKind::DoctypeEof,
-                                    self.buffer.empty_range_here(),
-                                ));
-                            }
-                        }
-                    }
-                }
-                Some(idx) => {
-                    self.buffer.current = idx + 1;
-                    return Ok(());
-                }
-                None => {
-                    self.set_error_state();
-                    return Err(Error::new(
-                        ErrorKind::DoctypeEof,
-                        self.buffer.empty_range_here(),
-                    ));
-                }
-            }
-        }
+    /// Changes whether cdata should be emitted as an [`Event::Text`] instead of an [`Event::CData`].
+    ///
+    /// The content of such events is available through [`TextEvent::content`] while
+    /// [`TextEvent::raw_content`] will contain the original cdata markup. Unlike
+    /// [`Self::coalesce_text`] this does not merge the cdata with adjacent text.
+    pub fn cdata_as_text(mut self, value: bool) -> Self {
+        self.cdata_as_text = value;
+        self
     }
 
-    fn take_prefixed_name(
-        &mut self,
-        start: usize,
-        prefix_end_default: usize,
-    ) -> Result<(usize, usize), Error> {
-        let first_end = self
-            .buffer
-            .position_or_end(self.buffer.current, is_invalid_name);
-        if first_end == self.buffer.current {
-            self.set_error_state();
-            return Err(Error::new(
-                ErrorKind::ExpectedElementName,
-                start..self.buffer.current,
-            ));
-        }
+    /// Changes whether unprefixed HTML void elements like `<br>` should be emitted as
+    /// [`Event::Empty`] even if they are not self-closing.
+    ///
+    /// End tags of void elements like `</br>` will be ignored. Names are matched
+    /// case-insensitively against the list of void elements in the HTML standard.
+    pub fn html_void_elements(mut self, value: bool) -> Self {
+        self.html_void_elements = value;
+        self
+    }
 
-        self.buffer.current = first_end;
+    /// Changes the prefixed names of elements whose content should be read as raw text,
+    /// like `script` and `style` in HTML.
+    ///
+    /// The content of these elements is emitted as a single [`Event::Text`] that extends
+    /// up to the first matching end tag, without interpreting any markup or entity
+    /// references inside it. Names are compared taking [`Self::ignore_name_case`] into account.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let options = Options::default().raw_text_elements(["script"]);
+    /// let mut reader = Reader::with_options("<script>if (a < b && c) {}</script>", options);
+    /// reader.next();
+    /// let Some(Ok(Event::Text(text))) = reader.next() else { panic!() };
+    /// assert!(text.is_raw());
+    /// assert_eq!(text.content(), "if (a < b && c) {}");
+    /// assert!(matches!(reader.next(), Some(Ok(Event::End(..)))));
+    /// ```
+    pub fn raw_text_elements<I>(mut self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Box<str>>,
+    {
+        self.raw_text_elements = names.into_iter().map(Into::into).collect();
+        self
+    }
 
-        let prefix_end;
-        let name_end;
-        if self.buffer.byte(self.buffer.current) == Some(b':') {
-            let second_end = self
-                .buffer
-                .position_or_end(self.buffer.current + 1, is_invalid_name);
-            if second_end == self.buffer.current {
-                self.set_error_state();
-                return Err(Error::new(
-                    ErrorKind::ExpectedElementName,
-                    start..self.buffer.current,
-                ));
-            }
-            self.buffer.current = second_end;
-            prefix_end = first_end;
-            name_end = second_end;
-        } else {
-            prefix_end = start + prefix_end_default;
-            name_end = first_end
-        }
+    /// Changes the prefixed names of the elements that events should be produced for.
+    ///
+    /// If any names are set, everything outside of matching elements is skipped without
+    /// producing events, only looking at as much markup as is needed to keep track of the
+    /// nesting. The start tag, content and end tag of every matching element are read
+    /// normally. Skipped markup is not checked for errors, like with [`Reader::find_element`],
+    /// but the names of the enclosing elements are still available from [`Reader::path`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let options = Options::default().only_elements(["title", "price"]);
+    /// let mut reader = Reader::with_options(
+    ///     "<feed><item><title>A</title><body><p>...</p></body><price>1</price></item></feed>",
+    ///     options,
+    /// );
+    /// let mut fields = Vec::new();
+    /// while let Some(event) = reader.next() {
+    ///     if let Event::Text(text) = event.unwrap() {
+    ///         fields.push(format!("{}: {}", reader.path().last().unwrap(), text.content()));
+    ///     }
+    /// }
+    /// assert_eq!(fields, ["title: A", "price: 1"]);
+    /// ```
+    pub fn only_elements<I>(mut self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Box<str>>,
+    {
+        self.only_elements = names.into_iter().map(Into::into).collect();
+        self
+    }
 
-        Ok((prefix_end, name_end))
+    /// Changes whether attribute values not enclosed in quotes like `<a b=c>` should be accepted.
+    ///
+    /// An unquoted value extends up to the next whitespace character, `>` or `/>`. Such
+    /// values are reported with [`AttributeQuote::None`].
+    pub fn allow_unquoted_attribute_values(mut self, value: bool) -> Self {
+        self.allow_unquoted_attribute_values = value;
+        self
     }
 
-    fn parse_node(&mut self) -> Result<Option<Event<'a>>, Error> {
-        let start = self.buffer.current;
-        self.buffer.current += 1;
+    /// Changes whether attributes without a value like `<input disabled>` should be accepted.
+    ///
+    /// Such attributes are reported with an empty value, use [`AttributeEvent::has_value`]
+    /// to tell them apart from attributes with an empty value like `disabled=""`.
+    pub fn allow_valueless_attributes(mut self, value: bool) -> Self {
+        self.allow_valueless_attributes = value;
+        self
+    }
 
-        match self.byte(self.buffer.current).ok_or_else(|| {
-            Error::new(
-                ErrorKind::InvalidElementName,
-                self.buffer.empty_range_here(),
-            )
-        })? {
-            b'?' => {
-                // xml declaration or processing instruction
-                // since both flags are disabled by default, they are treated the same.
-                //
-                // parse_declaration_node is disabled
-                // NOTE: This contains a glaring bug, it will fail on PIs like this:
-                //       <?hello something="?>"?>
-                //       But RapidXML doesn't care about this.
-                let Some(end) = self.buffer.memmem(b"?>") else {
-                    let name_range = self.buffer.current + 1
-                        ..self
-                            .buffer
-                            .position_or_end(self.buffer.current + 1, is_invalid_name);
-                    self.set_error_state();
-                    return Err(Error::new(ErrorKind::UnclosedPITag, name_range));
-                };
-                self.buffer.current = end + 2;
+    /// Changes whether element and attribute names should be compared ignoring ASCII case.
+    ///
+    /// This affects matching end tags against open elements with
+    /// [`Self::validate_closing_tags`], detecting duplicates with
+    /// [`Self::forbid_duplicate_attributes`] and [`Reader::find_element`]. Names in
+    /// events are still reported as they appear in the document.
+    pub fn ignore_name_case(mut self, value: bool) -> Self {
+        self.ignore_name_case = value;
+        self
+    }
 
-                Ok(None)
-            }
+    /// Changes whether line endings should be normalized as required by the XML specification.
+    ///
+    /// This replaces `\r\n` and any other `\r` with `\n` in text, cdata and attribute values
+    /// returned by [`TextEvent::content_in`], [`AttributeEvent::value_in`] and
+    /// [`Reader::read_text`]. In XML 1.1 documents `U+0085` and `U+2028` are also replaced,
+    /// see [`Self::xml_version_1_1`]. Carriage returns written as character references like
+    /// `&#13;` are kept.
+    ///
+    /// Accessors that do not take a reader, like [`TextEvent::content`], never normalize.
+    pub fn normalize_line_endings(mut self, value: bool) -> Self {
+        self.normalize_line_endings = value;
+        self
+    }
 
-            b'!' => match self.byte(self.buffer.current + 1) {
-                Some(b'-') if self.byte(self.buffer.current + 2) == Some(b'-') => {
-                    self.buffer.current += 2;
-                    let Some(end) = self.buffer.memmem(b"-->") else {
-                        let span = start..self.buffer.current;
-                        self.set_error_state();
-                        return Err(Error::new(ErrorKind::UnclosedComment, span));
-                    };
+    /// Changes whether attribute values returned by [`AttributeEvent::value_in`] should be
+    /// normalized as required by the XML specification for attributes without a declared type.
+    ///
+    /// Line endings are normalized as with [`Self::normalize_line_endings`] and every tab or
+    /// line feed is then replaced with a space, so `a='1\r\n\t2'` has the value `"1  2"`.
+    /// Characters written as character references like `&#10;` are kept.
+    ///
+    /// See [`escape::normalize_attribute_value`](crate::escape::normalize_attribute_value).
+    pub fn normalize_attribute_values(mut self, value: bool) -> Self {
+        self.normalize_attribute_values = value;
+        self
+    }
 
-                    self.buffer.current = end + 3;
-                    Ok(Some(Event::Comment(CommentEvent {
-                        text: &self.buffer.text[start..self.buffer.current],
-                    })))
-                }
-                Some(b'[') if self.bytes()[self.buffer.current + 2..].starts_with(b"CDATA[") => {
-                    self.buffer.current += 8;
-                    let Some(end) = self.buffer.memmem(b"]]>") else {
-                        let span = start..self.buffer.current;
-                        self.set_error_state();
-                        return Err(Error::new(ErrorKind::UnclosedCData, span));
-                    };
+    /// Changes whether control characters that are not allowed in XML documents should be
+    /// treated as an error when they appear in text, cdata or attribute values.
+    ///
+    /// These are all characters below `U+0020` except for tab, line feed and carriage return,
+    /// as well as the characters from `U+007F` to `U+009F` except for `U+0085` in XML 1.1
+    /// documents.
+    pub fn forbid_control_characters(mut self, value: bool) -> Self {
+        self.forbid_control_characters = value;
+        self
+    }
 
-                    self.buffer.current = end + 3;
-                    Ok(Some(Event::CData(CDataEvent {
-                        text: &self.buffer.text[start..self.buffer.current],
-                    })))
-                }
-                Some(b'D')
-                    if self.bytes()[self.buffer.current + 2..].starts_with(b"OCTYPE")
-                        && self
-                            .byte(self.buffer.current + 8)
-                            .is_some_and(is_whitespace) =>
-                {
-                    self.buffer.current += 9;
-                    self.skip_doctype()?;
-                    Ok(Some(Event::Doctype(DoctypeEvent {
-                        text: &self.buffer.text[start..self.buffer.current],
-                    })))
-                }
-                _ => {
-                    let Some(end) = self.buffer.memchr(self.buffer.current + 1, b'>') else {
-                        let span = start..self.buffer.position_or_end(start + 2, is_invalid_name);
-                        self.set_error_state();
-                        return Err(Error::new(ErrorKind::UnclosedUnknownSpecial, span));
-                    };
-                    self.buffer.current = end + 1;
-                    Ok(None)
-                }
-            },
+    /// Changes whether element and attribute names should be checked against the `Name`
+    /// production of the XML specification, with an [`ErrorKind::InvalidName`] error for
+    /// names that don't match.
+    ///
+    /// By default any characters that don't end the name are accepted, like RapidXML does,
+    /// so names like `<1st>` are allowed. With this enabled, the prefix and the local part
+    /// of prefixed names are checked separately. Attribute names are not checked while
+    /// [`Self::lazy_attributes`] is enabled.
+    pub fn strict_names(mut self, value: bool) -> Self {
+        self.strict_names = value;
+        self
+    }
 
-            // TODO: make depth=0 a separate error condition instead
-            b'/' if self.depth > 0 || self.options.allow_unmatched_closing_tags => {
-                self.buffer.current += 1;
-                let (prefix_end, name_end) = self.take_prefixed_name(start, 1)?;
+    /// Changes whether the document should be treated as an XML 1.1 document.
+    ///
+    /// This is enabled automatically once an XML declaration with `version="1.1"` is
+    /// encountered, regardless of [`Self::parse_declaration_node`].
+    pub fn xml_version_1_1(mut self, value: bool) -> Self {
+        self.xml_version_1_1 = value;
+        self
+    }
 
-                self.buffer.skip_whitespace();
+    /// Changes whether encountering a doctype declaration should be treated as an error.
+    ///
+    /// The error is reported before the declaration's internal subset is looked at, which
+    /// makes this a simple way to rule out entity expansion attacks on untrusted input.
+    pub fn forbid_doctype(mut self, value: bool) -> Self {
+        self.forbid_doctype = value;
+        self
+    }
 
-                if self.byte(self.buffer.current) != Some(b'>') {
-                    let span = self.buffer.char_range_here();
-                    self.set_error_state();
-                    return Err(Error::new(ErrorKind::UnclosedEndTag, span));
-                }
+    /// Changes whether element and attribute names with a prefix that is not bound to a
+    /// namespace should be treated as an error.
+    ///
+    /// Namespaces are only tracked by [`NsReader`](crate::NsReader), a plain [`Reader`]
+    /// ignores this option.
+    pub fn forbid_undeclared_prefixes(mut self, value: bool) -> Self {
+        self.forbid_undeclared_prefixes = value;
+        self
+    }
+}
 
-                self.depth = self.depth.saturating_sub(1);
-                self.buffer.current += 1;
-                Ok(Some(Event::End(EndEvent {
-                    text: &self.buffer.text[start..self.buffer.current],
-                    prefix_end: prefix_end - start,
-                    name_end: name_end - start,
-                })))
-            }
+/// An XML reader.
+///
+/// Events are read by using the reader as an [`Iterator`], once the reader returns `None`
+/// it will keep doing so until it is returned to an earlier position with
+/// [`Reader::rewind`].
+///
+/// Like RapidXML, the reader accepts any number of top-level elements, so concatenated
+/// fragments like a stream of log records can be parsed directly. Only text between them
+/// is rejected, unless [`Options::allow_top_level_text`] is enabled.
+///
+/// # Examples
+///
+/// ```
+/// # use speedy_xml::reader::*;
+/// let names = Reader::new("<a><b/><c></c></a>")
+///     .filter_map(|event| match event {
+///         Ok(Event::Start(start) | Event::Empty(start)) => Some(Ok(start.name())),
+///         Ok(_) => None,
+///         Err(error) => Some(Err(error)),
+///     })
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+/// assert_eq!(names, ["a", "b", "c"]);
+/// ```
+#[derive(Clone)]
+pub struct Reader<'a> {
+    buffer: ParsingBuffer<'a>,
+    /// The position of the start of the buffer in the whole document.
+    offset: usize,
+    depth: u32,
+    stack: ElementStack,
+    attribute_names: HashSet<Cow<'a, str>>,
+    /// The end of the last attribute read completely by `skip_element_attributes`.
+    attributes_end: usize,
+    options: Options,
+    entity_resolver: &'a dyn EntityResolver,
+    peeked: Option<Option<Result<Event<'a>, Error>>>,
+    /// Whether more input may be appended to the buffer later.
+    partial: bool,
+    failed: bool,
+    /// The number of events emitted so far.
+    events: usize,
+    /// The errors collected if enabled with [`Options::collect_errors`].
+    errors: Vec<Error>,
+    /// Whether the last event was a chunk of text that continues after it.
+    text_continues: bool,
+    /// The depth outside of the element matching [`Options::only_elements`] that is being read.
+    match_depth: Option<u32>,
+}
 
-            _ => {
-                let (prefix_end, name_end) = self.take_prefixed_name(start, 0)?;
+/// A saved position of a [`Reader`], created with [`Reader::checkpoint`].
+#[derive(Clone)]
+pub struct Checkpoint<'a> {
+    text: &'a str,
+    current: usize,
+    depth: u32,
+    stack: ElementStack,
+    peeked: Option<Option<Result<Event<'a>, Error>>>,
+    failed: bool,
+    events: usize,
+    errors: usize,
+    text_continues: bool,
+    match_depth: Option<u32>,
+}
 
-                self.skip_element_attributes()?;
-                self.buffer.skip_whitespace();
+/// The parts of a [`Reader`]'s state that do not borrow its buffer.
+///
+/// Used to suspend a reader and later resume it over an extended copy of the same buffer.
+pub(crate) struct State {
+    current: usize,
+    offset: usize,
+    depth: u32,
+    stack: ElementStack,
+    options: Options,
+    failed: bool,
+    events: usize,
+    text_continues: bool,
+    match_depth: Option<u32>,
+}
 
-                match self.byte(self.buffer.current) {
-                    Some(b'>') => {
-                        self.buffer.current += 1;
-                        self.depth += 1;
-                        Ok(Some(Event::Start(StartEvent {
-                            text: &self.buffer.text[start..self.buffer.current],
-                            prefix_end: prefix_end - start,
-                            name_end: name_end - start,
-                        })))
-                    }
-                    Some(b'/') => {
-                        if self.byte(self.buffer.current + 1) != Some(b'>') {
-                            let span = self.buffer.char_range_here();
-                            self.set_error_state();
-                            return Err(Error::new(ErrorKind::UnclosedEmptyElementTag, span));
-                        }
+impl State {
+    pub(crate) fn new(options: Options) -> Self {
+        Self {
+            current: 0,
+            offset: 0,
+            depth: 0,
+            stack: ElementStack::default(),
+            options,
+            failed: false,
+            events: 0,
+            text_continues: false,
+            match_depth: None,
+        }
+    }
 
-                        self.buffer.current += 2;
-                        Ok(Some(Event::Empty(StartEvent {
-                            text: &self.buffer.text[start..self.buffer.current],
-                            prefix_end: prefix_end - start,
-                            name_end: name_end - start,
-                        })))
-                    }
-                    _ => {
-                        let span = self.buffer.char_range_here();
-                        self.set_error_state();
-                        Err(Error::new(ErrorKind::UnclosedElementTag, span))
-                    }
-                }
+    /// Returns the position up to which the buffer has been parsed.
+    pub(crate) fn position(&self) -> usize {
+        self.current
+    }
+
+    /// Adjusts the state after the first `amount` bytes of the buffer have been removed.
+    pub(crate) fn discard(&mut self, amount: usize) {
+        self.current -= amount;
+        self.offset += amount;
+    }
+
+    /// Returns the number of bytes that were removed from the start of the buffer.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns whether the reader has stopped parsing because of an error.
+    pub(crate) fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Stops parsing because of an error that occurred outside of the reader.
+    pub(crate) fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    /// Encodes everything except the options into bytes that can be passed to [`Self::load`].
+    ///
+    /// The position is stored relative to the start of the document, a loaded state expects
+    /// its buffer to start at that position.
+    pub(crate) fn save(&self) -> Vec<u8> {
+        fn put(out: &mut Vec<u8>, value: usize) {
+            out.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+
+        let mut out = vec![STATE_VERSION];
+        let flags = [
+            self.failed,
+            self.text_continues,
+            self.options.xml_version_1_1,
+            self.match_depth.is_some(),
+        ];
+        out.push(
+            flags
+                .iter()
+                .rev()
+                .fold(0, |acc, &flag| acc << 1 | u8::from(flag)),
+        );
+        put(&mut out, self.offset + self.current);
+        put(&mut out, self.depth as usize);
+        put(&mut out, self.events);
+        put(&mut out, self.match_depth.unwrap_or(0) as usize);
+
+        let stack = &self.stack;
+        put(&mut out, stack.name_starts.len());
+        for (idx, name) in stack.names().enumerate() {
+            put(&mut out, stack.positions[idx]);
+            out.push(stack.preserve_space[idx].into());
+            put(&mut out, name.len());
+            out.extend_from_slice(name.as_bytes());
+        }
+        put(&mut out, stack.langs.len());
+        for (idx, lang) in &stack.langs {
+            put(&mut out, *idx);
+            put(&mut out, lang.len());
+            out.extend_from_slice(lang.as_bytes());
+        }
+
+        out
+    }
+
+    /// Decodes a state encoded by [`Self::save`], returns `None` if `bytes` are not a valid
+    /// encoded state.
+    pub(crate) fn load(bytes: &[u8], mut options: Options) -> Option<Self> {
+        let mut rest = bytes;
+        let mut take = |len: usize| -> Option<&[u8]> {
+            let taken = rest.get(..len)?;
+            rest = &rest[len..];
+            Some(taken)
+        };
+        macro_rules! get {
+            () => {
+                usize::try_from(u64::from_le_bytes(take(8)?.try_into().unwrap())).ok()?
+            };
+        }
+
+        if take(1)? != [STATE_VERSION] {
+            return None;
+        }
+        let flags = take(1)?[0];
+        let position = get!();
+        let depth = u32::try_from(get!()).ok()?;
+        let events = get!();
+        let match_depth = u32::try_from(get!()).ok()?;
+        options.xml_version_1_1 |= flags & 0b100 != 0;
+
+        let mut stack = ElementStack::default();
+        for _ in 0..get!() {
+            let position = get!();
+            let preserve_space = take(1)?[0] != 0;
+            let len = get!();
+            let name = std::str::from_utf8(take(len)?).ok()?;
+            stack.push(name, position, preserve_space, None);
+        }
+        for _ in 0..get!() {
+            let idx = get!();
+            let len = get!();
+            let lang = std::str::from_utf8(take(len)?).ok()?;
+            if idx >= stack.name_starts.len() {
+                return None;
             }
+            stack.langs.push((idx, lang.into()));
+        }
+
+        if !rest.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            current: 0,
+            offset: position,
+            depth,
+            stack,
+            options,
+            failed: flags & 0b1 != 0,
+            events,
+            text_continues: flags & 0b10 != 0,
+            match_depth: (flags & 0b1000 != 0).then_some(match_depth),
+        })
+    }
+}
+
+/// The version of the encoding produced by [`State::save`].
+const STATE_VERSION: u8 = 1;
+
+impl<'a> Reader<'a> {
+    /// Creates a new XML reader that will parse the contents of `text`.
+    pub fn new(text: &'a str) -> Self {
+        Self::with_options(text, Options::default())
+    }
+
+    /// Creates a new XML reader that will parse the contents of `text` with the provided [`Options`].
+    pub fn with_options(text: &'a str, options: Options) -> Self {
+        Self {
+            buffer: ParsingBuffer::new(text),
+            offset: 0,
+            depth: 0,
+            stack: ElementStack::default(),
+            attribute_names: HashSet::new(),
+            attributes_end: 0,
+            options,
+            entity_resolver: &PredefinedEntities,
+            peeked: None,
+            partial: false,
+            failed: false,
+            events: 0,
+            errors: Vec::new(),
+            text_continues: false,
+            match_depth: None,
+        }
+    }
+
+    /// Resumes a reader suspended with [`Self::suspend`] over `text`, which must start with
+    /// the text it was previously reading.
+    ///
+    /// If `partial` is `true` the reader will stop, without reporting any errors, at the
+    /// first piece of markup or text that may be incomplete.
+    pub(crate) fn resume(text: &'a str, state: State, partial: bool) -> Self {
+        let mut reader = Self::with_options(text, state.options);
+        reader.buffer.current = state.current;
+        reader.offset = state.offset;
+        reader.depth = state.depth;
+        reader.stack = state.stack;
+        reader.partial = partial;
+        reader.failed = state.failed;
+        reader.events = state.events;
+        reader.text_continues = state.text_continues;
+        reader.match_depth = state.match_depth;
+        reader
+    }
+
+    /// Suspends this reader, see [`Self::resume`].
+    pub(crate) fn suspend(self) -> State {
+        debug_assert!(self.peeked.is_none());
+        State {
+            current: self.buffer.current,
+            offset: self.offset,
+            depth: self.depth,
+            stack: self.stack,
+            options: self.options,
+            failed: self.failed,
+            events: self.events,
+            text_continues: self.text_continues,
+            match_depth: self.match_depth,
+        }
+    }
+
+    /// Returns the errors collected so far if enabled with [`Options::collect_errors`], in
+    /// the order they were encountered.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// Removes and returns the errors collected so far, see [`Self::errors`].
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Returns the next event without consuming it.
+    ///
+    /// # Notes
+    ///
+    /// The event is parsed immediately, which means that state accessors like
+    /// [`Self::depth`] will already reflect the peeked event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<a/>");
+    /// assert!(matches!(reader.peek(), Some(Ok(Event::Empty(..)))));
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Empty(..)))));
+    /// assert!(reader.peek().is_none());
+    /// ```
+    pub fn peek(&mut self) -> Option<&Result<Event<'a>, Error>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.read_event());
+        }
+
+        self.peeked.as_ref().unwrap().as_ref()
+    }
+
+    /// Returns the prefixed names of all currently open elements, starting with the outermost one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<root><items><x:item/></items></root>");
+    /// reader.next();
+    /// reader.next();
+    /// assert!(reader.path().eq(["root", "items"]));
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Empty(..)))));
+    /// assert!(reader.path().eq(["root", "items"]));
+    /// reader.next();
+    /// assert!(reader.path().eq(["root"]));
+    /// ```
+    pub fn path(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator + '_ {
+        self.stack.names()
+    }
+
+    /// Returns the value of the `xml:lang` attribute of the innermost open element that has one.
+    ///
+    /// This is the language of text read while that element is open, an empty string means
+    /// that the language was explicitly left unspecified. Like in [`Self::path`], empty tags
+    /// are never considered open, so their own `xml:lang` attributes are not reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<doc xml:lang='en'><p xml:lang='de'>Hallo</p><p>Hello</p></doc>");
+    /// let mut langs = Vec::new();
+    /// while let Some(event) = reader.next() {
+    ///     if let Event::Text(text) = event.unwrap() {
+    ///         langs.push((text.content(), reader.current_lang().unwrap().to_owned()));
+    ///     }
+    /// }
+    /// assert_eq!(langs, [("Hallo".into(), "de".into()), ("Hello".into(), "en".into())]);
+    /// ```
+    pub fn current_lang(&self) -> Option<&str> {
+        self.stack.lang()
+    }
+
+    /// Reads all remaining events and invokes the corresponding callbacks of `handler`.
+    ///
+    /// See [`sax::drive`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first parse error encountered.
+    pub fn drive(&mut self, handler: &mut impl sax::Handler<'a>) -> Result<(), Error> {
+        sax::drive(self, handler)
+    }
+
+    /// Returns a [`Checkpoint`] that can be used to return this reader to its current
+    /// position with [`Self::rewind`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<a><b/></a>");
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
+    /// let checkpoint = reader.checkpoint();
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Empty(..)))));
+    /// assert!(matches!(reader.next(), Some(Ok(Event::End(..)))));
+    ///
+    /// reader.rewind(checkpoint);
+    /// assert_eq!(reader.depth(), 1);
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Empty(..)))));
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint {
+            text: self.buffer.text,
+            current: self.buffer.current,
+            depth: self.depth,
+            stack: self.stack.clone(),
+            peeked: self.peeked.clone(),
+            failed: self.failed,
+            events: self.events,
+            errors: self.errors.len(),
+            text_continues: self.text_continues,
+            match_depth: self.match_depth,
         }
     }
 
-    /// Skips all events until the next end event on the same depth as the last seen start tag.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// # use speedy_xml::reader::*;
-    /// let mut reader = Reader::new("<event>content</event><another/>");
-    /// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
-    /// assert!(matches!(reader.skip_to_end(), Ok(Some(..))));
-    /// assert!(matches!(reader.next(), Some(Ok(Event::Empty(..)))));
-    /// assert!(matches!(reader.next(), None));
-    /// ```
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if a parse error occurred.
-    pub fn skip_to_end(&mut self) -> Result<Option<EndEvent<'a>>, Error> {
-        let end_depth = self.depth;
+    /// Returns this reader to the position it was at when `checkpoint` was created.
+    ///
+    /// This is also possible after the reader returned `None` or failed, which is why
+    /// [`Reader`] is not a [`FusedIterator`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `checkpoint` was created by a reader over a different buffer.
+    pub fn rewind(&mut self, checkpoint: Checkpoint<'a>) {
+        assert!(
+            std::ptr::eq(checkpoint.text, self.buffer.text),
+            "Reader::rewind called with a checkpoint from a different reader"
+        );
+
+        self.buffer.current = checkpoint.current;
+        self.depth = checkpoint.depth;
+        self.stack = checkpoint.stack;
+        self.peeked = checkpoint.peeked;
+        self.failed = checkpoint.failed;
+        self.events = checkpoint.events;
+        self.errors.truncate(checkpoint.errors);
+        self.text_continues = checkpoint.text_continues;
+        self.match_depth = checkpoint.match_depth;
+    }
+
+    /// Creates a new XML reader that will parse `bytes` with the provided [`Options`].
+    ///
+    /// The encoding of `bytes` is detected using [`encoding::detect`], if it is not UTF-8 the
+    /// document is decoded into `buffer` and the reader will parse its contents instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not valid in its detected encoding.
+    pub fn from_bytes(
+        bytes: &'a [u8],
+        buffer: &'a mut String,
+        options: Options,
+    ) -> Result<Self, DecodeError> {
+        Ok(match encoding::decode(bytes)? {
+            Cow::Borrowed(text) => Self::with_options(text, options),
+            Cow::Owned(text) => {
+                *buffer = text;
+                Self::with_options(buffer, options)
+            }
+        })
+    }
+
+    /// Creates a new XML reader that will parse the file at `path` with the provided [`Options`].
+    ///
+    /// The file is read into `buffer` and decoded as described in [`Self::from_bytes`], taking
+    /// both byte order marks and the encoding declared in the XML declaration into account.
+    ///
+    /// With the `flate2` feature enabled, gzip-compressed files are decompressed first.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use speedy_xml::reader::*;
+    /// let mut buffer = String::new();
+    /// let reader = Reader::from_file("document.xml", &mut buffer, Options::default()).unwrap();
+    /// for event in reader {
+    ///     println!("{:?}", event.unwrap());
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read or if its contents are not valid in
+    /// their detected encoding.
+    pub fn from_file(
+        path: impl AsRef<Path>,
+        buffer: &'a mut String,
+        options: Options,
+    ) -> Result<Self, ReadError> {
+        let bytes = std::fs::read(path)?;
+        #[cfg(feature = "flate2")]
+        let bytes = crate::gzip::decompress(bytes, &options)?;
+        let start = match encoding::decode(&bytes)? {
+            Cow::Borrowed(text) => text.as_ptr().addr() - bytes.as_ptr().addr(),
+            Cow::Owned(text) => {
+                *buffer = text;
+                return Ok(Self::with_options(buffer, options));
+            }
+        };
+
+        // SAFETY: The bytes after start were just validated by decode, anything before them
+        //         is a UTF-8 byte order mark.
+        *buffer = unsafe { String::from_utf8_unchecked(bytes) };
+        Ok(Self::with_options(&buffer[start..], options))
+    }
+
+    /// Creates a new XML reader that will parse the contents of a memory-mapped `document`
+    /// with the provided [`Options`].
+    ///
+    /// This is only available with the `memmap2` feature.
+    #[cfg(feature = "memmap2")]
+    pub fn from_mmap(document: &'a crate::mmap::MappedDocument, options: Options) -> Self {
+        Self::with_options(document.text(), options)
+    }
+
+    /// Creates a new XML reader that will parse the contents of a shared `document` with
+    /// the provided [`Options`].
+    ///
+    /// This is only available with the `bytes` feature.
+    #[cfg(feature = "bytes")]
+    pub fn from_shared(document: &'a crate::shared::SharedDocument, options: Options) -> Self {
+        Self::with_options(document.text(), options)
+    }
+
+    /// Sets the [`EntityResolver`] used by [`Self::unescape`] to resolve named entities.
+    ///
+    /// By default only the predefined entities are known.
+    pub fn set_entity_resolver(&mut self, resolver: &'a dyn EntityResolver) {
+        self.entity_resolver = resolver;
+    }
+
+    /// Unescapes `text` using this reader's [`EntityResolver`].
+    ///
+    /// Keeps all unresolved entities unexpanded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ErrorKind::EntityExpansionLimit`] error if the entity expansion limits set
+    /// in [`Options`] were exceeded. If `text` is part of this reader's buffer the error's span
+    /// is relative to the buffer, otherwise it is relative to `text`.
+    pub fn unescape<'s>(&self, text: &'s str) -> Result<Cow<'s, str>, Error> {
+        unescape_with_limits(
+            text,
+            self.entity_resolver,
+            self.options.max_entity_expansions,
+            self.options.max_entity_expansion_size,
+        )
+        .map_err(|offset| {
+            let buffer = self.buffer.as_bytes().as_ptr_range();
+            let text = text.as_bytes().as_ptr_range();
+            let base = if buffer.start <= text.start && text.end <= buffer.end {
+                text.start.addr() - buffer.start.addr()
+            } else {
+                0
+            };
+            Error::new(
+                ErrorKind::EntityExpansionLimit,
+                base + offset..base + offset + 1,
+            )
+        })
+    }
+
+    /// Normalizes the line endings in `text` if enabled with [`Options::normalize_line_endings`].
+    fn normalize<'s>(&self, text: &'s str) -> Cow<'s, str> {
+        match (
+            self.options.normalize_line_endings,
+            self.options.xml_version_1_1,
+        ) {
+            (false, _) => Cow::Borrowed(text),
+            (true, false) => normalize_line_endings(text),
+            (true, true) => normalize_line_endings_1_1(text),
+        }
+    }
+
+    /// Normalizes the attribute value `text` if enabled with
+    /// [`Options::normalize_attribute_values`], otherwise only normalizes line endings.
+    fn normalize_attribute_value<'s>(&self, text: &'s str) -> Cow<'s, str> {
+        match (
+            self.options.normalize_attribute_values,
+            self.options.xml_version_1_1,
+        ) {
+            (false, _) => self.normalize(text),
+            (true, false) => normalize_attribute_value(text),
+            (true, true) => normalize_attribute_value_1_1(text),
+        }
+    }
+
+    /// Returns the string that this reader was originally created with.
+    pub fn buffer(&self) -> &'a str {
+        self.buffer.text
+    }
+
+    /// Returns the element depth the parser is currently at.
+    ///
+    /// The depth is incremented after every [`Event::Start`] and decremented after every
+    /// [`Event::End`], [`Event::Empty`] events leave it unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<a><b/></a>");
+    /// assert_eq!(reader.depth(), 0);
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
+    /// assert_eq!(reader.depth(), 1);
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Empty(..)))));
+    /// assert_eq!(reader.depth(), 1);
+    /// assert!(matches!(reader.next(), Some(Ok(Event::End(..)))));
+    /// assert_eq!(reader.depth(), 0);
+    /// ```
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    fn range_for_ptrs(&self, range: Range<*const u8>) -> Range<usize> {
+        let self_range = self.buffer.as_bytes().as_ptr_range();
+        assert!(
+            self_range.start <= range.start
+                && self_range.end >= range.end
+                && range.start <= range.end,
+            "Parser::range_for_ptrs called with invalid pointer range"
+        );
+
+        range.start.addr() - self_range.start.addr()..range.end.addr() - self_range.start.addr()
+    }
+
+    /// Returns whether [`Options::forbid_undeclared_prefixes`] is enabled.
+    pub(crate) fn forbids_undeclared_prefixes(&self) -> bool {
+        self.options.forbid_undeclared_prefixes
+    }
+
+    pub(crate) fn set_error_state(&mut self) {
+        // The position will be fixed up by recover_from or read_event instead.
+        if self.options.recover || self.partial {
+            return;
+        }
+
+        self.fail();
+    }
+
+    /// Fails with an [`ErrorKind::Cancelled`] error if the [`CancelToken`] set in
+    /// [`Options`] was cancelled.
+    fn check_cancelled(&mut self) -> Result<(), Error> {
+        if self.failed
+            || !self
+                .options
+                .cancel_token
+                .as_ref()
+                .is_some_and(CancelToken::is_cancelled)
+        {
+            return Ok(());
+        }
+
+        let span = self.buffer.empty_range_here();
+        self.fail();
+        Err(Error::new(ErrorKind::Cancelled, span))
+    }
+
+    fn fail(&mut self) {
+        self.buffer.current = self.buffer.text.len();
+        self.depth = 0;
+        self.stack.clear();
+        self.failed = true;
+    }
+
+    /// Returns whether the markup starting at `start`, which failed to parse, might parse
+    /// successfully once more input is available.
+    fn is_truncated(&self, start: usize) -> bool {
+        let rest = &self.bytes()[start..];
+        if rest.starts_with(b"<!--")
+            || rest.starts_with(b"<![CDATA[")
+            || rest.starts_with(b"<!DOCTYPE")
+        {
+            // These can only fail to parse if they are not terminated.
+            true
+        } else if rest.starts_with(b"<?") {
+            memchr::memmem::find(rest, b"?>").is_none()
+        } else {
+            self.find_tag_end(start + 1).is_none()
+        }
+    }
+
+    /// Returns whether markup cut off by the end of the input should be emitted as a
+    /// truncated event.
+    fn allows_truncation(&self) -> bool {
+        self.options.allow_truncated_input && !self.partial
+    }
+
+    /// Turns `error`, raised while parsing the start tag at `start`, into a truncated start
+    /// event ending after the last complete attribute if the tag is cut off by the end of
+    /// the input.
+    fn truncated_start(
+        &mut self,
+        start: usize,
+        prefix_end: usize,
+        name_end: usize,
+        error: Error,
+    ) -> Result<Option<Event<'a>>, Error> {
+        if !self.allows_truncation() || self.find_tag_end(start + 1).is_some() {
+            return Err(error);
+        }
+
+        self.fail();
+        Ok(Some(Event::Start(StartEvent {
+            text: &self.buffer.text[start..self.attributes_end],
+            prefix_end: prefix_end - start,
+            name_end: name_end - start,
+        })))
+    }
+
+    /// Resumes parsing after a failure to parse the node starting at `start` at the first
+    /// `<` or after the first `>` following `start`, whichever comes first.
+    fn recover_from(&mut self, start: usize) {
+        self.buffer.current = match self.buffer.memchr2(start + 1, b'<', b'>') {
+            Some(idx) if self.bytes()[idx] == b'>' => idx + 1,
+            Some(idx) => idx,
+            None => self.buffer.text.len(),
+        };
+    }
+
+    /// Switches to XML 1.1 mode if the declaration `text` declares version 1.1.
+    ///
+    /// Skipped declarations are only checked for their `?>` terminator, so this looks for
+    /// the leading `version` pseudo-attribute by hand instead of going through [`Attributes`].
+    fn detect_version(&mut self, text: &str) {
+        fn skip_whitespace(text: &str) -> &str {
+            text.trim_start_matches([' ', '\t', '\r', '\n'])
+        }
+
+        let detect = || {
+            let rest = skip_whitespace(&text[5..]).strip_prefix("version")?;
+            let rest = skip_whitespace(skip_whitespace(rest).strip_prefix('=')?);
+            let quote = rest
+                .chars()
+                .next()
+                .filter(|&chr| chr == '\'' || chr == '"')?;
+            rest[1..].strip_prefix("1.1")?.strip_prefix(quote)
+        };
+        if detect().is_some() {
+            self.options.xml_version_1_1 = true;
+        }
+    }
+
+    /// Records the element started by `start`, whose name occupies `name` in the buffer, as open.
+    fn push_element(&mut self, start: &StartEvent<'a>, name: Range<usize>) {
+        // The space scope only matters for trimming, and neither attribute can be present
+        // without an `xml:` prefix, so avoid looking through the attributes otherwise.
+        let mut preserve_space = self.options.trim_text && self.stack.preserves_space();
+        let mut lang = None;
+        if start.text.contains("xml:") {
+            let mut space_seen = !self.options.trim_text;
+            for attribute in start.attributes() {
+                match attribute.name() {
+                    "xml:space" if !space_seen => {
+                        space_seen = true;
+                        match &*attribute.value() {
+                            "preserve" => preserve_space = true,
+                            "default" => preserve_space = false,
+                            _ => (),
+                        }
+                    }
+                    "xml:lang" if lang.is_none() => lang = Some(attribute.value()),
+                    _ => (),
+                }
+            }
+        }
+
+        self.depth += 1;
+        self.stack.push(
+            &self.buffer.text[name.clone()],
+            self.offset + name.start,
+            preserve_space,
+            lang.as_deref(),
+        );
+    }
+
+    fn pop_element(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        self.stack.pop();
+    }
+
+    #[inline]
+    fn bytes(&self) -> &'a [u8] {
+        self.buffer.as_bytes()
+    }
+
+    #[inline]
+    fn byte(&self, idx: usize) -> Option<u8> {
+        self.buffer.byte(idx)
+    }
+
+    fn check_attribute_value(&mut self, value: Range<usize>) -> Result<(), Error> {
+        if self
+            .options
+            .max_attribute_value_length
+            .is_some_and(|max| value.len() > max)
+        {
+            self.set_error_state();
+            return Err(Error::new(ErrorKind::AttributeValueTooLong, value));
+        }
+
+        self.check_characters(value)
+    }
+
+    /// Checks that `range` contains no control characters if they are forbidden.
+    fn check_characters(&mut self, range: Range<usize>) -> Result<(), Error> {
+        if !self.options.forbid_control_characters {
+            return Ok(());
+        }
+
+        let bytes = &self.bytes()[range.clone()];
+        let invalid = if self.options.xml_version_1_1 {
+            bytes.iter().enumerate().find_map(|(idx, &b)| match b {
+                b if is_restricted_control(b) || b == 0x7F => Some(idx..idx + 1),
+                // U+0080 to U+009F are encoded as 0xC2 followed by 0x80 to 0x9F.
+                0xC2 if matches!(bytes.get(idx + 1), Some(0x80..=0x84 | 0x86..=0x9F)) => {
+                    Some(idx..idx + 2)
+                }
+                _ => None,
+            })
+        } else {
+            bytes
+                .iter()
+                .position(|&b| is_restricted_control(b))
+                .map(|idx| idx..idx + 1)
+        };
+
+        match invalid {
+            Some(span) => {
+                self.set_error_state();
+                Err(Error::new(
+                    ErrorKind::InvalidCharacter,
+                    range.start + span.start..range.start + span.end,
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the name at `range` is a valid XML name if names are checked strictly.
+    fn check_name(&mut self, range: Range<usize>) -> Result<(), Error> {
+        if !self.options.strict_names || self.buffer.text[range.clone()].split(':').all(is_ncname) {
+            return Ok(());
+        }
+
+        self.set_error_state();
+        Err(Error::new(ErrorKind::InvalidName, range))
+    }
+
+    /// Records the attribute name at `range` as seen in the current tag, returns `false` if it
+    /// was seen before.
+    fn insert_attribute_name(&mut self, range: Range<usize>) -> bool {
+        let name = &self.buffer.text[range];
+        if self.options.ignore_name_case && name.bytes().any(|b| b.is_ascii_uppercase()) {
+            return self
+                .attribute_names
+                .insert(Cow::Owned(name.to_ascii_lowercase()));
+        }
+
+        self.attribute_names.insert(Cow::Borrowed(name))
+    }
+
+    fn skip_element_attributes(&mut self) -> Result<(), Error> {
+        self.attribute_names.clear();
+
+        let mut count = 0;
+        loop {
+            self.attributes_end = self.buffer.current;
+            self.buffer.skip_whitespace();
+
+            let name_start = self.buffer.current;
+            let mut name_end = self
+                .buffer
+                .position_or_end(self.buffer.current, is_invalid_attribute_name);
+            if name_end == self.buffer.current {
+                return Ok(());
+            }
+
+            if self.byte(name_end) == Some(b':') {
+                let local_end = self
+                    .buffer
+                    .position_or_end(name_end + 1, is_invalid_attribute_name);
+                if local_end == name_end + 1 {
+                    self.set_error_state();
+                    return Err(Error::new(
+                        ErrorKind::ExpectedAttributeName,
+                        name_start..local_end,
+                    ));
+                }
+                name_end = local_end;
+            }
+            self.buffer.current = name_end;
+            self.check_name(name_start..name_end)?;
+
+            if self.options.max_attributes.is_some_and(|max| count >= max) {
+                self.set_error_state();
+                return Err(Error::new(
+                    ErrorKind::TooManyAttributes,
+                    name_start..name_end,
+                ));
+            }
+
+            if self.options.forbid_duplicate_attributes
+                && !self.insert_attribute_name(name_start..name_end)
+            {
+                self.set_error_state();
+                return Err(Error::new(
+                    ErrorKind::DuplicateAttribute,
+                    name_start..name_end,
+                ));
+            }
+
+            self.buffer.skip_whitespace();
+
+            if self.byte(self.buffer.current) != Some(b'=') {
+                if self.options.allow_valueless_attributes {
+                    count += 1;
+                    continue;
+                }
+
+                self.set_error_state();
+                return Err(Error::new(
+                    ErrorKind::ExpectedAttributeEq,
+                    name_start..name_end,
+                ));
+            };
+
+            self.buffer.current += 1;
+
+            let eq_end = self.buffer.current;
+
+            self.buffer.skip_whitespace();
+
+            let Some(quote) = self
+                .byte(self.buffer.current)
+                .filter(|b| [b'\'', b'\"'].contains(b))
+            else {
+                let value_start = self.buffer.current;
+                let value_end = self.buffer.unquoted_value_end(value_start);
+                if !self.options.allow_unquoted_attribute_values || value_end == value_start {
+                    self.set_error_state();
+                    return Err(Error::new(
+                        ErrorKind::ExpectedAttributeValue,
+                        name_start..eq_end,
+                    ));
+                }
+
+                self.check_attribute_value(value_start..value_end)?;
+                self.buffer.current = value_end;
+                count += 1;
+                continue;
+            };
+
+            self.buffer.current += 1;
+
+            let value_start = self.buffer.current;
+            let Some(value_end) = self.buffer.memchr2(self.buffer.current, quote, b'\0') else {
+                self.set_error_state();
+                return Err(Error::new(
+                    ErrorKind::UnclosedAttributeValue,
+                    self.buffer.current..(self.buffer.current + 1).min(self.buffer.text.len()),
+                ));
+            };
+
+            if self.bytes()[value_end] == b'\0' {
+                self.set_error_state();
+                return Err(Error::new(
+                    ErrorKind::InvalidAttributeValue,
+                    value_start..value_end + 1,
+                ));
+            }
+
+            self.check_attribute_value(value_start..value_end)?;
+            self.buffer.current = value_end + 1;
+            count += 1;
+        }
+    }
+
+    fn skip_doctype(&mut self) -> Result<(), Error> {
+        loop {
+            match self.buffer.memchr2(self.buffer.current, b'>', b'[') {
+                Some(idx) if self.bytes()[idx] == b'[' => {
+                    self.buffer.current = idx + 1;
+                    let mut depth = 1;
+                    while depth > 0 {
+                        match self.buffer.memchr2(self.buffer.current, b'[', b']') {
+                            Some(idx) => {
+                                if self.bytes()[idx] == b'[' {
+                                    depth += 1;
+                                } else {
+                                    depth -= 1;
+                                }
+                                self.buffer.current = idx + 1;
+                            }
+                            None => {
+                                self.set_error_state();
+                                return Err(Error::new(
+                                    ErrorKind::DoctypeEof,
+                                    self.buffer.empty_range_here(),
+                                ));
+                            }
+                        }
+                    }
+                }
+                Some(idx) => {
+                    self.buffer.current = idx + 1;
+                    return Ok(());
+                }
+                None => {
+                    self.set_error_state();
+                    return Err(Error::new(
+                        ErrorKind::DoctypeEof,
+                        self.buffer.empty_range_here(),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn take_prefixed_name(
+        &mut self,
+        start: usize,
+        prefix_end_default: usize,
+    ) -> Result<(usize, usize), Error> {
+        let name_start = self.buffer.current;
+        let first_end = self
+            .buffer
+            .position_or_end(self.buffer.current, is_invalid_name);
+        if first_end == self.buffer.current {
+            self.set_error_state();
+            return Err(Error::new(
+                ErrorKind::ExpectedElementName,
+                start..self.buffer.current,
+            ));
+        }
+
+        self.buffer.current = first_end;
+
+        let prefix_end;
+        let name_end;
+        if self.buffer.byte(self.buffer.current) == Some(b':') {
+            let second_end = self
+                .buffer
+                .position_or_end(self.buffer.current + 1, is_invalid_name);
+            if second_end == self.buffer.current {
+                self.set_error_state();
+                return Err(Error::new(
+                    ErrorKind::ExpectedElementName,
+                    start..self.buffer.current,
+                ));
+            }
+            self.buffer.current = second_end;
+            prefix_end = first_end;
+            name_end = second_end;
+        } else {
+            prefix_end = start + prefix_end_default;
+            name_end = first_end
+        }
+
+        self.check_name(name_start..name_end)?;
+        Ok((prefix_end, name_end))
+    }
+
+    fn parse_node(&mut self) -> Result<Option<Event<'a>>, Error> {
+        self.parse_markup(self.options.lazy_attributes)
+    }
+
+    /// Parses the markup at the current position, only looking for the end of start tags
+    /// instead of scanning their attributes if `lazy_attributes` is `true`.
+    fn parse_markup(&mut self, lazy_attributes: bool) -> Result<Option<Event<'a>>, Error> {
+        let start = self.buffer.current;
+        self.buffer.current += 1;
+
+        match self.byte(self.buffer.current).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidElementName,
+                self.buffer.empty_range_here(),
+            )
+        })? {
+            b'?' => {
+                // NOTE: This contains a glaring bug, it will fail on PIs like this:
+                //       <?hello something="?>"?>
+                //       But RapidXML doesn't care about this.
+                if self.bytes()[self.buffer.current + 1..].starts_with(b"xml")
+                    && self
+                        .byte(self.buffer.current + 4)
+                        .is_some_and(is_whitespace)
+                {
+                    if self.options.parse_declaration_node {
+                        self.buffer.current += 4;
+                        self.skip_element_attributes()?;
+                        self.buffer.skip_whitespace();
+
+                        if !self.bytes()[self.buffer.current..].starts_with(b"?>") {
+                            let span = self.buffer.char_range_here();
+                            self.set_error_state();
+                            return Err(Error::new(ErrorKind::UnclosedDeclaration, span));
+                        }
+
+                        self.buffer.current += 2;
+                        let decl = DeclEvent {
+                            text: &self.buffer.text[start..self.buffer.current],
+                        };
+                        self.detect_version(decl.text);
+                        return Ok(Some(Event::Decl(decl)));
+                    }
+
+                    let Some(end) = self.buffer.memmem(b"?>") else {
+                        let name_range = self.buffer.current + 1..self.buffer.current + 4;
+                        self.set_error_state();
+                        return Err(Error::new(ErrorKind::UnclosedPITag, name_range));
+                    };
+                    self.buffer.current = end + 2;
+                    self.detect_version(&self.buffer.text[start..self.buffer.current]);
+
+                    return Ok(None);
+                }
+
+                let target_start = self.buffer.current + 1;
+                let target_end = self
+                    .buffer
+                    .position_or_end(target_start, |b| b != b':' && is_invalid_name(b));
+
+                let Some(end) = self.buffer.memmem(b"?>") else {
+                    self.set_error_state();
+                    return Err(Error::new(
+                        ErrorKind::UnclosedPITag,
+                        target_start..target_end,
+                    ));
+                };
+
+                if !self.options.parse_pi_nodes {
+                    self.buffer.current = end + 2;
+                    return Ok(None);
+                }
+
+                if target_start == target_end {
+                    let span = self.buffer.char_range_here();
+                    self.set_error_state();
+                    return Err(Error::new(ErrorKind::ExpectedPITarget, span));
+                }
+
+                self.buffer.current = target_end;
+                self.buffer.skip_whitespace();
+                let content_start = self.buffer.current.min(end);
+                self.buffer.current = end + 2;
+
+                Ok(Some(Event::PI(PIEvent {
+                    text: &self.buffer.text[start..self.buffer.current],
+                    target_end: target_end - start,
+                    content_start: content_start - start,
+                })))
+            }
+
+            b'!' => match self.byte(self.buffer.current + 1) {
+                Some(b'-') if self.byte(self.buffer.current + 2) == Some(b'-') => {
+                    self.buffer.current += 3;
+                    let Some(end) = self.buffer.memmem(b"-->") else {
+                        if self.allows_truncation() {
+                            self.fail();
+                            return Ok((!self.options.skip_comments).then(|| {
+                                Event::Comment(CommentEvent {
+                                    text: &self.buffer.text[start..],
+                                })
+                            }));
+                        }
+
+                        let span = start..self.buffer.current;
+                        self.set_error_state();
+                        return Err(Error::new(ErrorKind::UnclosedComment, span));
+                    };
+
+                    self.buffer.current = end + 3;
+                    if self.options.skip_comments {
+                        return Ok(None);
+                    }
+
+                    Ok(Some(Event::Comment(CommentEvent {
+                        text: &self.buffer.text[start..self.buffer.current],
+                    })))
+                }
+                Some(b'[') if self.bytes()[self.buffer.current + 2..].starts_with(b"CDATA[") => {
+                    self.buffer.current += 8;
+                    let Some(end) = self.buffer.memmem(b"]]>") else {
+                        if self.allows_truncation() {
+                            self.check_characters(start + 9..self.buffer.text.len())?;
+                            self.fail();
+                            return Ok(Some(Event::CData(CDataEvent {
+                                text: &self.buffer.text[start..],
+                            })));
+                        }
+
+                        let span = start..self.buffer.current;
+                        self.set_error_state();
+                        return Err(Error::new(ErrorKind::UnclosedCData, span));
+                    };
+
+                    self.check_characters(start + 9..end)?;
+                    self.buffer.current = end + 3;
+                    Ok(Some(Event::CData(CDataEvent {
+                        text: &self.buffer.text[start..self.buffer.current],
+                    })))
+                }
+                Some(b'D')
+                    if self.bytes()[self.buffer.current + 2..].starts_with(b"OCTYPE")
+                        && self
+                            .byte(self.buffer.current + 8)
+                            .is_some_and(is_whitespace) =>
+                {
+                    if self.options.forbid_doctype {
+                        let span = start..self.buffer.current + 8;
+                        self.set_error_state();
+                        return Err(Error::new(ErrorKind::DoctypeForbidden, span));
+                    }
+
+                    self.buffer.current += 9;
+                    self.skip_doctype()?;
+                    Ok(Some(Event::Doctype(DoctypeEvent {
+                        text: &self.buffer.text[start..self.buffer.current],
+                    })))
+                }
+                _ => {
+                    let Some(end) = self.buffer.memchr(self.buffer.current + 1, b'>') else {
+                        let span = start..self.buffer.position_or_end(start + 2, is_invalid_name);
+                        self.set_error_state();
+                        return Err(Error::new(ErrorKind::UnclosedUnknownSpecial, span));
+                    };
+                    self.buffer.current = end + 1;
+                    Ok(None)
+                }
+            },
+
+            // TODO: make depth=0 a separate error condition instead
+            b'/' if self.depth > 0 || self.options.allow_unmatched_closing_tags => {
+                self.buffer.current += 1;
+                let (prefix_end, name_end) = self.take_prefixed_name(start, 1)?;
+
+                self.buffer.skip_whitespace();
+
+                if self.byte(self.buffer.current) != Some(b'>') {
+                    let span = self.buffer.char_range_here();
+                    self.set_error_state();
+                    return Err(Error::new(ErrorKind::UnclosedEndTag, span));
+                }
+
+                if self.options.html_void_elements
+                    && prefix_end == start + 1
+                    && is_void_element(&self.buffer.text[start + 2..name_end])
+                {
+                    // Void elements were already reported as empty.
+                    self.buffer.current += 1;
+                    return Ok(None);
+                }
+
+                if self.options.validate_closing_tags {
+                    let ignore_case = self.options.ignore_name_case;
+                    if let Some(expected) = self.stack.last_name().filter(|&name| {
+                        !names_equal(name, &self.buffer.text[start + 2..name_end], ignore_case)
+                    }) {
+                        let mut error =
+                            Error::new(ErrorKind::MismatchedEndTag, start + 2..name_end);
+                        error.mismatch = Some(Box::new(NameMismatch {
+                            expected: expected.into(),
+                            found: self.buffer.text[start + 2..name_end].into(),
+                            opened_at: self.stack.last_span().unwrap(),
+                        }));
+                        self.set_error_state();
+                        return Err(error);
+                    }
+                }
+
+                self.pop_element();
+                self.buffer.current += 1;
+                Ok(Some(Event::End(EndEvent {
+                    text: &self.buffer.text[start..self.buffer.current],
+                    prefix_end: prefix_end - start,
+                    name_end: name_end - start,
+                })))
+            }
+
+            _ => {
+                let (prefix_end, name_end) = self.take_prefixed_name(start, 0)?;
+
+                if self.options.max_depth.is_some_and(|max| self.depth >= max) {
+                    self.set_error_state();
+                    return Err(Error::new(ErrorKind::MaxDepthExceeded, start + 1..name_end));
+                }
+
+                // Unquoted values may contain quotes, which would throw off the search.
+                let lazy = lazy_attributes && !self.options.allow_unquoted_attribute_values;
+                match self.find_tag_end(name_end).filter(|_| lazy) {
+                    Some(end) if end > name_end && self.bytes()[end - 1] == b'/' => {
+                        self.buffer.current = end - 1;
+                    }
+                    Some(end) => self.buffer.current = end,
+                    // Let the attribute scanner figure out what exactly is wrong with this tag.
+                    None => {
+                        if let Err(error) = self.skip_element_attributes() {
+                            return self.truncated_start(start, prefix_end, name_end, error);
+                        }
+                        self.buffer.skip_whitespace();
+                    }
+                }
+
+                match self.byte(self.buffer.current) {
+                    Some(b'>') => {
+                        self.buffer.current += 1;
+                        let event = StartEvent {
+                            text: &self.buffer.text[start..self.buffer.current],
+                            prefix_end: prefix_end - start,
+                            name_end: name_end - start,
+                        };
+
+                        if self.options.html_void_elements
+                            && prefix_end == start
+                            && is_void_element(event.name())
+                        {
+                            return Ok(Some(Event::Empty(event)));
+                        }
+
+                        self.push_element(&event, start + 1..name_end);
+                        Ok(Some(Event::Start(event)))
+                    }
+                    Some(b'/') => {
+                        if self.byte(self.buffer.current + 1) != Some(b'>') {
+                            let span = self.buffer.char_range_here();
+                            self.set_error_state();
+                            let error = Error::new(ErrorKind::UnclosedEmptyElementTag, span);
+                            return self.truncated_start(start, prefix_end, name_end, error);
+                        }
+
+                        self.buffer.current += 2;
+                        Ok(Some(Event::Empty(StartEvent {
+                            text: &self.buffer.text[start..self.buffer.current],
+                            prefix_end: prefix_end - start,
+                            name_end: name_end - start,
+                        })))
+                    }
+                    _ => {
+                        let span = self.buffer.char_range_here();
+                        self.set_error_state();
+                        let error = Error::new(ErrorKind::UnclosedElementTag, span);
+                        self.truncated_start(start, prefix_end, name_end, error)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the depth before the peeked event, if any, was read.
+    fn depth_before_peeked(&self) -> u32 {
+        match &self.peeked {
+            Some(Some(Ok(Event::Start(_)))) => self.depth - 1,
+            Some(Some(Ok(Event::End(_)))) => self.depth + 1,
+            _ => self.depth,
+        }
+    }
+
+    /// Skips all events until the next end event on the same depth as the last seen start tag.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<event>content</event><another/>");
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
+    /// assert!(matches!(reader.skip_to_end(), Ok(Some(..))));
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Empty(..)))));
+    /// assert!(matches!(reader.next(), None));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parse error occurred.
+    pub fn skip_to_end(&mut self) -> Result<Option<EndEvent<'a>>, Error> {
+        let end_depth = self.depth_before_peeked();
+
+        loop {
+            match self.next().transpose()? {
+                Some(Event::End(end)) if self.depth + 1 == end_depth => return Ok(Some(end)),
+                Some(_) => (),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Reads everything up to and including the end tag matching the last seen start tag
+    /// and returns the concatenated content of all text and cdata events encountered.
+    ///
+    /// Text content is unescaped using this reader's [`EntityResolver`], the content of
+    /// nested elements is included while comments and processing instructions are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<leaf>one &amp; <![CDATA[<two>]]></leaf>");
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
+    /// assert_eq!(reader.read_text().unwrap(), "one & <two>");
+    /// assert!(matches!(reader.next(), None));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parse error occurred.
+    pub fn read_text(&mut self) -> Result<Cow<'a, str>, Error> {
+        let end_depth = self.depth_before_peeked();
+        let mut result = Cow::Borrowed("");
+
+        loop {
+            let content = match self.next().transpose()? {
+                Some(Event::End(_)) if self.depth + 1 == end_depth => return Ok(result),
+                Some(Event::Text(text)) => text.content_in(self)?,
+                Some(Event::CData(cdata)) => self.normalize(cdata.content()),
+                Some(_) => continue,
+                None => return Ok(result),
+            };
+
+            if result.is_empty() {
+                result = content;
+            } else if !content.is_empty() {
+                result.to_mut().push_str(&content);
+            }
+        }
+    }
+
+    /// Skips everything up to and including the end tag matching the last seen start tag.
+    ///
+    /// Unlike [`Self::skip_to_end`] this does not produce any events for the skipped
+    /// content, it only scans for tag boundaries. This means that most malformed
+    /// markup inside the skipped element will not be reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<skipped><a x='>'><b/></a>text</skipped><another/>");
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
+    /// let end = reader.skip_subtree().unwrap().unwrap();
+    /// assert_eq!(end.name(), "skipped");
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Empty(..)))));
+    /// assert!(matches!(reader.next(), None));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parse error occurred.
+    pub fn skip_subtree(&mut self) -> Result<Option<EndEvent<'a>>, Error> {
+        let mut depth = 0u32;
+
+        match self.peeked.take() {
+            Some(Some(Ok(Event::Start(start)))) => {
+                // The scanner below does not account for nested elements in self.depth.
+                self.pop_element();
+                depth += 1;
+                self.skip_raw_text(&start.text[1..start.name_end]);
+            }
+            Some(Some(Ok(Event::End(end)))) => return Ok(Some(end)),
+            Some(Some(Err(error))) => return Err(error),
+            Some(None) => return Ok(None),
+            _ => {
+                if let Some(end) = self.raw_text_end() {
+                    self.buffer.current = end.unwrap_or(self.buffer.text.len());
+                }
+            }
+        }
+
+        loop {
+            let Some(idx) = self.buffer.memchr(self.buffer.current, b'<') else {
+                self.buffer.current = self.buffer.text.len();
+                return match self.next().transpose()? {
+                    Some(_) => Err(self.unclosed_skipped_element()),
+                    None => Ok(None),
+                };
+            };
+            self.buffer.current = idx;
+
+            match self.byte(idx + 1) {
+                // Void elements were already reported as empty, see parse_markup.
+                Some(b'/') if depth == 0 && !self.is_void_tag(idx + 2) => {
+                    return match self.next().transpose()? {
+                        Some(Event::End(end)) => Ok(Some(end)),
+                        Some(_) => Err(self.unclosed_skipped_element()),
+                        None => Ok(None),
+                    };
+                }
+                Some(b'/') => {
+                    if !self.is_void_tag(idx + 2) {
+                        depth -= 1;
+                    }
+                    self.buffer.current = self
+                        .buffer
+                        .memchr(idx + 2, b'>')
+                        .map_or(self.buffer.text.len(), |end| end + 1);
+                }
+                Some(b'!' | b'?') => {
+                    self.parse_node()?;
+                }
+                _ => match self.find_tag_end(idx + 1) {
+                    Some(end) => {
+                        self.buffer.current = end + 1;
+                        if self.bytes()[end - 1] != b'/' && !self.is_void_tag(idx + 1) {
+                            depth += 1;
+                            self.skip_raw_text(self.tag_name(idx + 1));
+                        }
+                    }
+                    // Let the parser figure out what exactly is wrong with this tag.
+                    None => {
+                        self.parse_node()?;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Skips the content of the element opened by the last seen start tag, like
+    /// [`Self::skip_subtree`], and returns a new [`Reader`] over that content.
+    ///
+    /// The returned reader only yields the events between the start tag and its matching
+    /// end tag and reports the end of the document at the end tag, while this reader
+    /// continues after it. Positions reported by the returned reader are relative to
+    /// the same buffer as this one's, and it starts in the `xml:space` scope of the element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<root><item><a/>text</item><after/></root>");
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
+    /// let mut item = reader.subtree().unwrap();
+    /// assert!(matches!(item.next(), Some(Ok(Event::Empty(..)))));
+    /// assert!(matches!(item.next(), Some(Ok(Event::Text(..)))));
+    /// assert!(matches!(item.next(), None));
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Empty(..)))));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parse error occurred while looking for the end tag.
+    pub fn subtree(&mut self) -> Result<Reader<'a>, Error> {
+        let mut stack = self.stack.clone();
+        let start = match &self.peeked {
+            Some(Some(Ok(event))) => {
+                if let Event::Start(_) = event {
+                    stack.pop();
+                }
+                self.range_for_ptrs(event.text().as_bytes().as_ptr_range())
+                    .start
+            }
+            _ => self.buffer.current,
+        };
+        // Skipping the subtree leaves the element matching `Options::only_elements`.
+        let matched = self.match_depth.is_some();
+
+        let end = match self.skip_subtree()? {
+            Some(end) => {
+                self.range_for_ptrs(end.text.as_bytes().as_ptr_range())
+                    .start
+            }
+            None => self.buffer.text.len(),
+        };
+
+        let mut reader = self.child_reader(start..end, stack);
+        if matched {
+            reader.options.only_elements.clear();
+        }
+        Ok(reader)
+    }
+
+    /// Creates a reader over the element content at `range` with the open elements in `stack`.
+    fn child_reader(&self, range: Range<usize>, stack: ElementStack) -> Reader<'a> {
+        let mut options = self.options.clone();
+        // The element's content is balanced, so anything at depth zero is still inside it.
+        options.allow_top_level_text = true;
+
+        let mut reader = Self::with_options(&self.buffer.text[..range.end], options);
+        reader.buffer.current = range.start;
+        reader.offset = self.offset;
+        reader.stack = stack;
+        reader.entity_resolver = self.entity_resolver;
+        reader
+    }
+
+    /// Returns an iterator over every element with the prefixed name `name` that comes
+    /// after the current position, together with a [`Reader`] over its content.
+    ///
+    /// Everything between the matching elements is skipped like with [`Self::find_element`].
+    /// Elements nested inside a matching element are only visible through its reader.
+    /// Empty tags are returned with a reader that yields no events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<export><skip/><record id='1'>a</record><record id='2'/></export>");
+    /// let mut contents = Vec::new();
+    /// for result in reader.elements("record") {
+    ///     let (start, mut record) = result.unwrap();
+    ///     let text = match record.next() {
+    ///         Some(Ok(Event::Text(text))) => text.content().into_owned(),
+    ///         _ => String::new(),
+    ///     };
+    ///     contents.push((start.attributes().next().unwrap().value().into_owned(), text));
+    /// }
+    /// assert_eq!(contents, [("1".into(), "a".into()), ("2".into(), String::new())]);
+    /// ```
+    pub fn elements<'r>(&'r mut self, name: &'r str) -> Elements<'r, 'a> {
+        Elements { reader: self, name }
+    }
+
+    /// Returns an iterator over the byte ranges of the elements directly inside the element
+    /// opened by the last seen start tag, stopping after its end tag.
+    ///
+    /// The content of every child element is skipped like with [`Self::skip_subtree`],
+    /// which makes this a cheap way to split a large record-oriented document into pieces
+    /// that can then be parsed independently, for example on multiple threads. Namespace
+    /// declarations and `xml:space` scopes of the enclosing elements are not carried over
+    /// into the ranges.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let text = "<export><record>1</record> <record/><!-- done --></export>";
+    /// let mut reader = Reader::new(text);
+    /// assert!(matches!(reader.next(), Some(Ok(Event::Start(..)))));
+    /// let ranges = reader.child_ranges().collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(ranges, [8..26, 27..36]);
+    ///
+    /// let records = ranges.into_iter().map(|range| Reader::new(&text[range]).count());
+    /// assert_eq!(records.collect::<Vec<_>>(), [3, 1]);
+    /// ```
+    pub fn child_ranges(&mut self) -> ChildRanges<'_, 'a> {
+        ChildRanges {
+            reader: self,
+            done: false,
+        }
+    }
+
+    /// Skips everything up to and including the next start or empty tag with the prefixed
+    /// name `name` and returns its event.
+    ///
+    /// Like [`Self::skip_subtree`] this does not produce events for the skipped content,
+    /// skipped text is not checked for errors. Attributes are only scanned for the end of
+    /// each tag, like with [`Options::lazy_attributes`]. Returns `Ok(None)` if the end of
+    /// the document was reached without finding a matching element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::reader::*;
+    /// let mut reader = Reader::new("<root><a>text</a><x:item id='1'/><item id='2'></item></root>");
+    /// let Ok(Some(Event::Empty(item))) = reader.find_element("x:item") else { panic!() };
+    /// assert_eq!(item.attributes().next().unwrap().value(), "1");
+    /// let Ok(Some(Event::Start(item))) = reader.find_element("item") else { panic!() };
+    /// assert_eq!(reader.depth(), 2);
+    /// assert!(matches!(reader.find_element("item"), Ok(None)));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parse error occurred.
+    pub fn find_element(&mut self, name: &str) -> Result<Option<Event<'a>>, Error> {
+        let (prefix, name) = match name.split_once(':') {
+            Some((prefix, name)) => (Some(prefix), name),
+            None => (None, name),
+        };
+        let ignore_case = self.options.ignore_name_case;
+        let matches = |event: &Option<Event<'a>>| {
+            let Some(Event::Start(start) | Event::Empty(start)) = event else {
+                return false;
+            };
+            let prefix_matches = match (start.prefix(), prefix) {
+                (Some(a), Some(b)) => names_equal(a, b, ignore_case),
+                (a, b) => a == b,
+            };
+            prefix_matches && names_equal(start.name(), name, ignore_case)
+        };
+
+        match self.peeked.take() {
+            Some(Some(Ok(event))) if matches(&Some(event)) => return Ok(Some(event)),
+            Some(Some(Err(error))) => return Err(error),
+            Some(None) => return Ok(None),
+            _ => (),
+        }
+
+        loop {
+            self.check_cancelled()?;
+            // The end tag of an element read as raw text is the only markup inside of it.
+            if let Some(end) = self.raw_text_end() {
+                self.buffer.current = end.unwrap_or(self.buffer.text.len());
+            }
+
+            let Some(start) = self.buffer.memchr(self.buffer.current, b'<') else {
+                self.buffer.current = self.buffer.text.len();
+                return match self.next().transpose()? {
+                    Some(_) => Err(self.unclosed_skipped_element()),
+                    None => Ok(None),
+                };
+            };
+            self.buffer.current = start;
+
+            match self.parse_markup(true) {
+                Ok(event) if matches(&event) => return Ok(event),
+                Ok(_) => (),
+                Err(error) => {
+                    if self.options.recover {
+                        self.recover_from(start);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Returns whether the tag name starting at `start` names an element that is always
+    /// empty because of [`Options::html_void_elements`].
+    fn is_void_tag(&self, start: usize) -> bool {
+        // Prefixed names are never void, so they do not need to be split here.
+        self.options.html_void_elements && is_void_element(self.tag_name(start))
+    }
+
+    /// Returns the prefixed tag name starting at `start`, as seen by the skip scanners.
+    fn tag_name(&self, start: usize) -> &'a str {
+        let end = self
+            .buffer
+            .position_or_end(start, |b| is_whitespace(b) || b == b'/' || b == b'>');
+        &self.buffer.text[start..end]
+    }
+
+    /// Moves to the end tag of the element with the prefixed name `name` that was just
+    /// started if its content is read as raw text, see [`Options::raw_text_elements`].
+    fn skip_raw_text(&mut self, name: &str) {
+        if self.options.raw_text_elements.is_empty() {
+            return;
+        }
+
+        if let Some(end) = self.raw_text_end_of(name, self.buffer.current) {
+            self.buffer.current = end.unwrap_or(self.buffer.text.len());
+        }
+    }
+
+    /// Returns the error for an element that was still open when the skip scanners reached
+    /// the end of the input or an unexpected event.
+    fn unclosed_skipped_element(&mut self) -> Error {
+        let span = self.buffer.empty_range_here();
+        self.set_error_state();
+        Error::new(ErrorKind::UnclosedElement, span)
+    }
+
+    /// Returns the position of the first `>` after `start` that is not inside a quoted attribute value.
+    fn find_tag_end(&self, start: usize) -> Option<usize> {
+        let mut current = start;
+        loop {
+            let idx = current + memchr::memchr3(b'>', b'\'', b'"', &self.bytes()[current..])?;
+            match self.bytes()[idx] {
+                b'>' => return Some(idx),
+                quote => current = self.buffer.memchr(idx + 1, quote)? + 1,
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Event<'a>, Error>;
+
+    fn next(&mut self) -> Option<Result<Event<'a>, Error>> {
+        match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.read_event(),
+        }
+    }
+}
+
+/// An iterator over matching elements and readers over their content, obtained via
+/// [`Reader::elements`].
+pub struct Elements<'r, 'a> {
+    reader: &'r mut Reader<'a>,
+    name: &'r str,
+}
+
+impl<'a> Iterator for Elements<'_, 'a> {
+    type Item = Result<(StartEvent<'a>, Reader<'a>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = &mut *self.reader;
+        match reader.find_element(self.name) {
+            Ok(Some(Event::Start(start))) => Some(reader.subtree().map(|content| (start, content))),
+            Ok(Some(Event::Empty(start))) => {
+                let end = reader.buffer.current;
+                let content = reader.child_reader(end..end, reader.stack.clone());
+                Some(Ok((start, content)))
+            }
+            Ok(_) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+impl FusedIterator for Elements<'_, '_> {}
+
+/// An iterator over the byte ranges of child elements, obtained via [`Reader::child_ranges`].
+pub struct ChildRanges<'r, 'a> {
+    reader: &'r mut Reader<'a>,
+    done: bool,
+}
+
+impl Iterator for ChildRanges<'_, '_> {
+    type Item = Result<Range<usize>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let reader = &mut *self.reader;
+        loop {
+            let (start, empty) = match reader.next()? {
+                Ok(Event::Start(start)) => (start, false),
+                Ok(Event::Empty(start)) => (start, true),
+                Ok(Event::End(_)) => {
+                    self.done = true;
+                    return None;
+                }
+                Ok(_) => continue,
+                Err(error) => return Some(Err(error)),
+            };
+
+            let range = reader.range_for_ptrs(start.text.as_bytes().as_ptr_range());
+            if empty {
+                return Some(Ok(range));
+            }
+
+            return Some(match reader.skip_subtree() {
+                Ok(Some(end)) => Ok(range.start
+                    ..reader
+                        .range_for_ptrs(end.text.as_bytes().as_ptr_range())
+                        .end),
+                Ok(None) => Ok(range.start..reader.buffer.text.len()),
+                Err(error) => Err(error),
+            });
+        }
+    }
+}
+
+impl FusedIterator for ChildRanges<'_, '_> {}
+
+impl<'a> Reader<'a> {
+    /// Creates a text event out of the text or cdata at `range`, applying coalescing and trimming.
+    ///
+    /// Returns `None` if the resulting event would be empty.
+    /// If `split` is `true` the text continues after `range`, see [`Options::text_chunk_size`].
+    fn text_event(
+        &mut self,
+        mut range: Range<usize>,
+        mut cdata: bool,
+        split: bool,
+    ) -> Option<Result<Event<'a>, Error>> {
+        let continued = std::mem::replace(&mut self.text_continues, split);
+
+        if self.options.coalesce_text
+            && !split
+            && (self.depth > 0 || self.options.allow_top_level_text)
+        {
+            loop {
+                match self.byte(range.end) {
+                    Some(b'<') if self.bytes()[range.end + 1..].starts_with(b"![CDATA[") => {
+                        let Some(end) =
+                            memchr::memmem::find(&self.bytes()[range.end + 9..], b"]]>")
+                        else {
+                            break;
+                        };
+                        range.end += 9 + end + 3;
+                        cdata = true;
+                    }
+                    Some(b'<') | None => break,
+                    Some(_) => {
+                        range.end = self
+                            .buffer
+                            .memchr(range.end, b'<')
+                            .unwrap_or(self.buffer.text.len())
+                    }
+                }
+            }
+            self.buffer.current = range.end;
+        }
+
+        if let Err(error) = self.check_characters(range.clone()) {
+            return Some(Err(error));
+        }
+
+        if self.options.trim_text && !self.stack.preserves_space() {
+            if !continued {
+                range.start = self
+                    .buffer
+                    .position_or_end(range.start, |b| !is_whitespace(b));
+            }
+            while !split && range.end > range.start && is_whitespace(self.bytes()[range.end - 1]) {
+                range.end -= 1;
+            }
+
+            if range.is_empty() {
+                // Leading whitespace may continue into the next chunk.
+                self.text_continues &= continued;
+                return None;
+            }
+        }
+
+        Some(Ok(Event::Text(TextEvent {
+            text: &self.buffer.text[range],
+            cdata,
+            raw: false,
+        })))
+    }
+
+    fn read_event(&mut self) -> Option<Result<Event<'a>, Error>> {
+        loop {
+            match self.parse_event() {
+                Some(Err(error)) if self.options.collect_errors && !self.failed => {
+                    self.errors.push(error);
+                }
+                result => return self.check_event_limits(result),
+            }
+        }
+    }
+
+    /// Checks the event `result` against the limits set in [`Options`].
+    fn check_event_limits(
+        &mut self,
+        result: Option<Result<Event<'a>, Error>>,
+    ) -> Option<Result<Event<'a>, Error>> {
+        let (kind, span) = match &result {
+            Some(Ok(event)) => {
+                let span = self.range_for_ptrs(event.text().as_bytes().as_ptr_range());
+                self.events += 1;
+                if self
+                    .options
+                    .max_event_size
+                    .is_some_and(|max| span.len() > max)
+                {
+                    (ErrorKind::EventTooLarge, span)
+                } else if self
+                    .options
+                    .max_total_events
+                    .is_some_and(|max| self.events > max)
+                {
+                    (ErrorKind::TooManyEvents, span)
+                } else {
+                    return result;
+                }
+            }
+            // The rest of the buffer belongs to an event that is still incomplete.
+            None if self.partial
+                && self
+                    .options
+                    .max_event_size
+                    .is_some_and(|max| self.buffer.text.len() - self.buffer.current > max) =>
+            {
+                (
+                    ErrorKind::EventTooLarge,
+                    self.buffer.current..self.buffer.text.len(),
+                )
+            }
+            _ => return result,
+        };
+
+        self.fail();
+        Some(Err(Error::new(kind, span)))
+    }
+
+    /// Returns where to split the text between the current position and `end` if it is
+    /// larger than [`Options::text_chunk_size`].
+    ///
+    /// If `complete` is `false` the text may continue after `end` and `None` is also returned
+    /// if an entity reference at the start of the text could be cut off.
+    fn text_chunk_end(&self, end: usize, complete: bool) -> Option<usize> {
+        let size = self.options.text_chunk_size?;
+        let start = self.buffer.current;
+        if end - start <= size {
+            return None;
+        }
+
+        let mut split = start + size;
+        while !self.buffer.text.is_char_boundary(split) {
+            split -= 1;
+        }
+
+        // Splitting an entity reference or a CRLF pair would change the content.
+        if let Some(amp) = memchr::memrchr(b'&', &self.bytes()[start..split]) {
+            let amp = start + amp;
+            if !self.bytes()[amp..split].contains(&b';') {
+                if amp > start {
+                    split = amp;
+                } else {
+                    match memchr::memchr(b';', &self.bytes()[amp..end]) {
+                        Some(idx) => split = amp + idx + 1,
+                        None if !complete => return None,
+                        None => (),
+                    }
+                }
+            }
+        }
+        if split > start + 1 && self.bytes()[split - 1] == b'\r' {
+            split -= 1;
+        }
+
+        if split == start {
+            split += 1;
+            while !self.buffer.text.is_char_boundary(split) {
+                split += 1;
+            }
+        }
+
+        Some(split)
+    }
+
+    /// Returns the position of the end tag of the innermost open element if its content is
+    /// read as raw text, or `Some(None)` if the end tag was not found.
+    fn raw_text_end(&self) -> Option<Option<usize>> {
+        if self.options.raw_text_elements.is_empty() {
+            return None;
+        }
+
+        self.raw_text_end_of(self.stack.last_name()?, self.buffer.current)
+    }
+
+    /// Returns the position of the end tag of an element with the prefixed name `name` whose
+    /// content starts at `start` if it is read as raw text, or `Some(None)` if the end tag
+    /// was not found.
+    fn raw_text_end_of(&self, name: &str, start: usize) -> Option<Option<usize>> {
+        let ignore_case = self.options.ignore_name_case;
+        if !self
+            .options
+            .raw_text_elements
+            .iter()
+            .any(|raw| names_equal(raw, name, ignore_case))
+        {
+            return None;
+        }
+
+        let mut current = start;
+        Some(loop {
+            let Some(idx) = memchr::memmem::find(&self.bytes()[current..], b"</") else {
+                break None;
+            };
+            let start = current + idx;
+            let name_end = start + 2 + name.len();
+            if self
+                .buffer
+                .text
+                .get(start + 2..name_end)
+                .is_some_and(|found| names_equal(found, name, ignore_case))
+                && self
+                    .byte(name_end)
+                    .is_some_and(|b| b == b'>' || is_whitespace(b))
+            {
+                break Some(start);
+            }
+
+            // The end tag may continue in input that is not available yet.
+            if self.partial && name_end >= self.buffer.text.len() {
+                break None;
+            }
+            current = start + 2;
+        })
+    }
+
+    /// Returns whether markup is being skipped because of [`Options::only_elements`].
+    fn skipping(&self) -> bool {
+        !self.options.only_elements.is_empty() && self.match_depth.is_none()
+    }
+
+    /// Returns whether `start` matches [`Options::only_elements`].
+    fn is_selected(&self, start: &StartEvent) -> bool {
+        let name = &start.text[1..start.name_end];
+        self.options
+            .only_elements
+            .iter()
+            .any(|selected| names_equal(selected, name, self.options.ignore_name_case))
+    }
+
+    fn parse_event(&mut self) -> Option<Result<Event<'a>, Error>> {
+        loop {
+            if let Err(error) = self.check_cancelled() {
+                return Some(Err(error));
+            }
+
+            if let Some(end) = self.raw_text_end() {
+                let end = match end {
+                    Some(end) => end,
+                    None if self.partial => return None,
+                    None => self.buffer.text.len(),
+                };
+
+                if end > self.buffer.current {
+                    let range = self.buffer.current..end;
+                    self.buffer.current = end;
+                    if self.skipping() {
+                        continue;
+                    }
+                    return match self.text_event(range, false, false) {
+                        Some(Ok(Event::Text(text))) => {
+                            Some(Ok(Event::Text(TextEvent { raw: true, ..text })))
+                        }
+                        Some(result) => Some(result),
+                        None => continue,
+                    };
+                }
+            }
+
+            return match self.byte(self.buffer.current) {
+                Some(b'<') => {
+                    let start = self.buffer.current;
+                    let skipping = self.skipping();
+                    match self.parse_markup(self.options.lazy_attributes || skipping) {
+                        Ok(Some(event)) if skipping => match event {
+                            Event::Start(start)
+                                if self.is_selected(&start) && !start.is_truncated() =>
+                            {
+                                self.match_depth = Some(self.depth - 1);
+                                Some(Ok(event))
+                            }
+                            // Truncated start tags don't open an element, so there is no
+                            // content left to match.
+                            Event::Start(start) | Event::Empty(start)
+                                if self.is_selected(&start) =>
+                            {
+                                Some(Ok(event))
+                            }
+                            _ => continue,
+                        },
+                        Ok(Some(event @ Event::End(_))) if self.match_depth == Some(self.depth) => {
+                            self.match_depth = None;
+                            Some(Ok(event))
+                        }
+                        Ok(Some(Event::CData(cdata)))
+                            if (self.options.coalesce_text || self.options.cdata_as_text)
+                                && !cdata.is_truncated() =>
+                        {
+                            let range = self.range_for_ptrs(cdata.text.as_bytes().as_ptr_range());
+                            match self.text_event(range, true, false) {
+                                Some(result) => Some(result),
+                                None => continue,
+                            }
+                        }
+                        Ok(Some(event)) => Some(Ok(event)),
+                        Ok(None) => continue,
+                        Err(err) => {
+                            if self.partial && self.is_truncated(start) {
+                                self.buffer.current = start;
+                                return None;
+                            }
+
+                            if self.options.recover {
+                                self.recover_from(start);
+                            } else if self.partial {
+                                self.fail();
+                            }
+                            Some(Err(err))
+                        }
+                    }
+                }
+                Some(_) if self.skipping() => {
+                    self.buffer.current = self
+                        .buffer
+                        .memchr(self.buffer.current, b'<')
+                        .unwrap_or(self.buffer.text.len());
+                    continue;
+                }
+                Some(_) => {
+                    let (end, complete) = match self.buffer.memchr(self.buffer.current, b'<') {
+                        Some(idx) => (idx, true),
+                        // The text may continue in input that is not available yet.
+                        None => (self.buffer.text.len(), !self.partial),
+                    };
+                    let (node_start, split) = match self.text_chunk_end(end, complete) {
+                        Some(chunk_end) => (chunk_end, true),
+                        None if complete => (end, false),
+                        None => return None,
+                    };
+                    let text_range = self.buffer.current..node_start;
+                    self.buffer.current = text_range.end;
+
+                    if self.depth == 0 && !self.options.allow_top_level_text {
+                        // SAFETY: node_start was just acquired from memchr or is at most the length.
+                        //         self.buffer.current can also never be less than the string's length.
+                        if !unsafe { self.buffer.as_bytes().get_unchecked(text_range.clone()) }
+                            .iter()
+                            .copied()
+                            .all(is_whitespace)
+                        {
+                            if !self.options.recover {
+                                self.fail();
+                            }
+                            return Some(Err(Error::new(ErrorKind::TopLevelText, text_range)));
+                        } else {
+                            self.buffer.current = text_range.end;
+                            continue;
+                        }
+                    }
+
+                    match self.text_event(text_range, false, split) {
+                        Some(result) => Some(result),
+                        None => continue,
+                    }
+                }
+                None if self.depth > 0 => {
+                    if self.options.allow_unclosed_tags || self.partial {
+                        return None;
+                    }
+
+                    self.depth = 0;
+                    self.stack.clear();
+                    return Some(Err(Error::new(
+                        ErrorKind::UnclosedElement,
+                        self.buffer.empty_range_here(),
+                    )));
+                }
+                None => None,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Reader;
+
+    macro_rules! unwrap {
+        ($event: expr, Some($($what: tt)*)) => {
+            unwrap!($event.expect("unexpected end of event stream"), $($what)*)
+        };
+        ($event: expr, Ok($what: ident)) => {
+            unwrap!($event.expect("parse error"), $what)
+        };
+        ($event: expr, $what: ident) => {{
+            let e = $event;
+            if let super::Event::$what(r) = e {
+                r
+            } else {
+                panic!(
+                    concat!("mismatched event, expected ", stringify!($what), " got {:?}"),
+                    e
+                )
+            }
+        }};
+    }
+
+    #[test]
+    fn element() {
+        let code =
+            "   <hello attr =  \"value\" 0ther4ttr=\t'val&apos;ue'>con&#x20;ten&#32;t</hello>   ";
+        let mut reader = Reader::new(code);
+
+        {
+            let start = unwrap!(reader.next(), Some(Ok(Start)));
+            assert_eq!(start.name(), "hello");
+
+            let mut attributes = start.attributes();
+            {
+                let attr = attributes.next().unwrap();
+                assert_eq!(attr.name(), "attr");
+                assert_eq!(attr.value(), "value");
+                assert_eq!(attr.raw_value(), "value");
+            }
+            {
+                let attr = attributes.next().unwrap();
+                assert_eq!(attr.name(), "0ther4ttr");
+                assert_eq!(attr.value(), "val'ue");
+                assert_eq!(attr.raw_value(), "val&apos;ue");
+            }
+            assert!(attributes.next().is_none());
+        }
+
+        {
+            let text = unwrap!(reader.next(), Some(Ok(Text)));
+            assert_eq!(text.content(), "con ten t");
+            assert_eq!(text.raw_content(), "con&#x20;ten&#32;t");
+        }
+
+        {
+            let end = unwrap!(reader.next(), Some(Ok(End)));
+            assert_eq!(end.name(), "hello");
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let comment_text = " this is a &comment -- text ";
+        let code = format!("   <!--{comment_text}-->   ");
+        let mut reader = Reader::new(&code);
+
+        let comment = unwrap!(reader.next(), Some(Ok(Comment)));
+        assert_eq!(comment.content(), comment_text);
+    }
+
+    #[test]
+    fn skip_comments() {
+        let code = "<!-- a --><root><!-- b -->text<!----></root>";
+        let mut reader = Reader::with_options(code, super::Options::default().skip_comments(true));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).content(), "text");
+        unwrap!(reader.next(), Some(Ok(End)));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn element_tree() {
+        let code = r#"
+            <tree>
+                <ns:stuff1>one</stuff2>
+                one is &lt; two
+            </not:tree>
+        "#;
+        let mut reader = Reader::new(code);
+
+        {
+            let start = unwrap!(reader.next(), Some(Ok(Start)));
+            assert_eq!(start.prefix(), None);
+            assert_eq!(start.name(), "tree");
+            assert!(start.attributes().next().is_none());
+        }
+
+        {
+            let text = unwrap!(reader.next(), Some(Ok(Text)));
+            assert_eq!(text.raw_content(), "\n                ");
+        }
+
+        {
+            let start = unwrap!(reader.next(), Some(Ok(Start)));
+            assert_eq!(start.prefix(), Some("ns"));
+            assert_eq!(start.name(), "stuff1");
+            assert!(start.attributes().next().is_none());
+            let prefix = start.prefix_position_in(&reader).unwrap();
+            assert_eq!(&reader.buffer.text[prefix], "ns");
+        }
+
+        {
+            let text = unwrap!(reader.next(), Some(Ok(Text)));
+            assert_eq!(text.content(), "one");
+        }
+
+        {
+            let end = unwrap!(reader.next(), Some(Ok(End)));
+            assert_eq!(end.name(), "stuff2");
+        }
+
+        {
+            let text = unwrap!(reader.next(), Some(Ok(Text)));
+            assert_eq!(
+                text.content(),
+                "\n                one is < two\n            "
+            );
+            assert_eq!(
+                text.raw_content(),
+                "\n                one is &lt; two\n            "
+            );
+        }
+
+        {
+            let end = unwrap!(reader.next(), Some(Ok(End)));
+            assert_eq!(end.prefix(), Some("not"));
+            assert_eq!(end.name(), "tree");
+        }
+    }
+
+    #[test]
+    fn cdata() {
+        let content = "this is some cdata < > > & & !!";
+        let code = format!("<![CDATA[{content}]]>");
+        let mut reader = Reader::new(&code);
+
+        {
+            let end = unwrap!(reader.next(), Some(Ok(CData)));
+            assert_eq!(end.content(), content);
+        }
+    }
+
+    #[test]
+    fn doctype_external_id() {
+        let code = concat!(
+            r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "#,
+            r#""http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd" [<!ENTITY a "b">]>"#,
+            r#"<!DOCTYPE note SYSTEM 'note.dtd'><!DOCTYPE root>"#
+        );
+        let mut reader = Reader::new(code);
+
+        let doctype = unwrap!(reader.next(), Some(Ok(Doctype)));
+        assert_eq!(doctype.name(), Some("html"));
+        assert_eq!(
+            doctype.external_id(),
+            Some(super::ExternalId {
+                public_id: Some("-//W3C//DTD XHTML 1.0 Strict//EN"),
+                system_id: Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"),
+            })
+        );
+        assert_eq!(doctype.internal_subset(), Some(r#"<!ENTITY a "b">"#));
+
+        let doctype = unwrap!(reader.next(), Some(Ok(Doctype)));
+        assert_eq!(doctype.name(), Some("note"));
+        assert_eq!(doctype.external_id().unwrap().system_id, Some("note.dtd"));
+
+        let doctype = unwrap!(reader.next(), Some(Ok(Doctype)));
+        assert_eq!(doctype.name(), Some("root"));
+        assert_eq!(doctype.external_id(), None);
+    }
+
+    #[test]
+    fn cdata_as_text() {
+        let code = "<a>x<![CDATA[<&>]]></a>";
+        let mut reader = Reader::with_options(code, super::Options::default().cdata_as_text(true));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        assert_eq!(text.content(), "x");
+        assert!(!text.contains_cdata());
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        assert_eq!(text.content(), "<&>");
+        assert_eq!(text.raw_content(), "<![CDATA[<&>]]>");
+        assert!(text.contains_cdata());
+        unwrap!(reader.next(), Some(Ok(End)));
+    }
+
+    #[test]
+    fn html_void_elements() {
+        let code = "<p>a<br>b<IMG src='x'></IMG><x:br></x:br></p>";
+        let mut reader =
+            Reader::with_options(code, super::Options::default().html_void_elements(true));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Text)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Empty))).name(), "br");
+        unwrap!(reader.next(), Some(Ok(Text)));
+        let img = unwrap!(reader.next(), Some(Ok(Empty)));
+        assert_eq!(img.attributes().next().unwrap().value(), "x");
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(End)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(End))).name(), "p");
+        assert_eq!(reader.depth(), 0);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn attribute_positions() {
+        let code = "<a x:y = \"1\" z='' w=v/>";
+        let mut reader = Reader::with_options(
+            code,
+            super::Options::default().allow_unquoted_attribute_values(true),
+        );
+        let start = unwrap!(reader.next(), Some(Ok(Empty)));
+        let spans = start
+            .attributes()
+            .map(|attr| {
+                (
+                    attr.position_in(&reader),
+                    attr.name_position_in(&reader),
+                    attr.value_position_in(&reader),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let attr = start.attributes().next().unwrap();
+        assert_eq!((attr.prefix(), attr.local_name()), (Some("x"), "y"));
+        assert_eq!(attr.prefix_position_in(&reader), Some(3..4));
+        assert_eq!(attr.local_name_position_in(&reader), 5..6);
+        let attr = start.attributes().nth(1).unwrap();
+        assert_eq!((attr.prefix(), attr.local_name()), (None, "z"));
+        assert_eq!(attr.prefix_position_in(&reader), None);
+        assert_eq!(attr.local_name_position_in(&reader), 13..14);
+
+        assert_eq!(
+            spans,
+            [
+                (3..12, 3..6, 10..11),
+                (13..17, 13..14, 16..16),
+                (18..21, 18..19, 20..21)
+            ]
+        );
+    }
+
+    #[test]
+    fn markup_in_attribute_values() {
+        let mut reader = Reader::new(r#"<a b="x<y" c='>'><d e='<</a>'/></a>"#);
+        let start = unwrap!(reader.next(), Some(Ok(Start)));
+        let values = start
+            .attributes()
+            .map(|attr| attr.raw_value())
+            .collect::<Vec<_>>();
+        assert_eq!(values, ["x<y", ">"]);
+        let start = unwrap!(reader.next(), Some(Ok(Empty)));
+        assert_eq!(start.get_attribute("e").unwrap().value(), "<</a>");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(End))).name(), "a");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn valueless_attributes() {
+        let code = "<input disabled checked = '' a:b value=x/><p hidden>";
+        let mut reader = Reader::with_options(
+            code,
+            super::Options::default()
+                .allow_valueless_attributes(true)
+                .allow_unquoted_attribute_values(true),
+        );
+
+        let start = unwrap!(reader.next(), Some(Ok(Empty)));
+        let attributes = start
+            .attributes()
+            .map(|attr| (attr.name(), attr.raw_value(), attr.has_value()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attributes,
+            [
+                ("disabled", "", false),
+                ("checked", "", true),
+                ("a:b", "", false),
+                ("value", "x", true)
+            ]
+        );
+        let attr = start.attributes().nth(2).unwrap();
+        assert_eq!(attr.quote(), super::AttributeQuote::None);
+        assert_eq!(attr.local_name(), "b");
+        assert_eq!(attr.value_position_in(&reader), 32..32);
+
+        let start = unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(start.get_attribute("hidden").unwrap().value(), "");
+
+        let mut reader = Reader::new("<input disabled>");
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::ExpectedAttributeEq);
+    }
+
+    #[test]
+    fn unquoted_attribute_values() {
+        let code = "<a href=/x/y.html b = 'c' d=e/><f g=>";
+        let mut reader = Reader::with_options(
+            code,
+            super::Options::default().allow_unquoted_attribute_values(true),
+        );
+
+        let start = unwrap!(reader.next(), Some(Ok(Empty)));
+        let attributes = start
+            .attributes()
+            .map(|attr| (attr.name(), attr.raw_value(), attr.quote()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attributes,
+            [
+                ("href", "/x/y.html", super::AttributeQuote::None),
+                ("b", "c", super::AttributeQuote::Single),
+                ("d", "e", super::AttributeQuote::None)
+            ]
+        );
+        assert_eq!(attributes[0].2.as_char(), None);
+        assert_eq!(attributes[1].2.as_char(), Some('\''));
+        assert_eq!(attributes[1].2.to_char(), '\'');
+
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::ExpectedAttributeValue);
+    }
+
+    #[test]
+    fn owned_events() {
+        let code = r#"<a x="1"><?pi content?><![CDATA[c]]></a>"#.to_owned();
+        let events = Reader::with_options(&code, super::Options::default().parse_pi_nodes(true))
+            .map(|event| event.map(super::Event::into_owned))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        drop(code);
+
+        let events = std::thread::spawn(move || events).join().unwrap();
+        let start = unwrap!(events[0].as_event(), Start);
+        assert_eq!(start.attributes().next().unwrap().value(), "1");
+        let pi = unwrap!(events[1].as_event(), PI);
+        assert_eq!(pi.content(), "content");
+        let cdata = unwrap!(events[2].as_event(), CData);
+        assert_eq!(cdata.content(), "c");
+        assert_eq!(unwrap!(events[3].as_event(), End).name(), "a");
+    }
+
+    #[test]
+    fn doctype() {
+        let content = "\tthis is a doctype [with] [many [brackets[[[]]][][]]]\n";
+        let code = format!("<!DOCTYPE {content}>");
+        let mut reader = Reader::new(&code);
+
+        {
+            let end = unwrap!(reader.next(), Some(Ok(Doctype)));
+            assert_eq!(end.content(), content);
+        }
+    }
+
+    #[test]
+    fn processing_instructions() {
+        let code =
+            r#"<?xml version="1.0"?><?xml-stylesheet  href="style.xsl"?><root><?empty?></root>"#;
+
+        let mut reader = Reader::new(code);
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Start))).name(), "root");
+
+        let mut reader = Reader::with_options(code, super::Options::default().parse_pi_nodes(true));
+        {
+            let pi = unwrap!(reader.next(), Some(Ok(PI)));
+            assert_eq!(pi.target(), "xml-stylesheet");
+            assert_eq!(pi.content(), r#"href="style.xsl""#);
+        }
+        unwrap!(reader.next(), Some(Ok(Start)));
+        {
+            let pi = unwrap!(reader.next(), Some(Ok(PI)));
+            assert_eq!(pi.target(), "empty");
+            assert_eq!(pi.content(), "");
+        }
+    }
+
+    #[test]
+    fn declaration() {
+        let code = r#"<?xml version="1.0" encoding='utf-8'  standalone="yes" ?><root/>"#;
+        let mut reader =
+            Reader::with_options(code, super::Options::default().parse_declaration_node(true));
+
+        let decl = unwrap!(reader.next(), Some(Ok(Decl)));
+        assert_eq!(decl.version(), Some("1.0"));
+        assert_eq!(decl.encoding(), Some("utf-8"));
+        assert_eq!(decl.standalone(), Some(true));
+        unwrap!(reader.next(), Some(Ok(Empty)));
+    }
+
+    #[test]
+    fn entity_resolver() {
+        let entities = [("nbsp".to_owned(), "\u{a0}".to_owned())]
+            .into_iter()
+            .collect::<std::collections::HashMap<_, _>>();
+        let mut reader = Reader::new("<a b='&nbsp;&lt;'>&nbsp;</a>");
+        reader.set_entity_resolver(&entities);
+
+        let start = unwrap!(reader.next(), Some(Ok(Start)));
+        let attr = start.attributes().next().unwrap();
+        assert_eq!(attr.value(), "&nbsp;<");
+        assert_eq!(attr.value_in(&reader).unwrap(), "\u{a0}<");
+
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        assert_eq!(text.content_in(&reader).unwrap(), "\u{a0}");
+    }
+
+    #[test]
+    fn unescaped_text() {
+        let mut reader = Reader::with_options(
+            "<a>x &amp; y</a><a>plain</a><a>&lt;<![CDATA[&]]>&bogus;</a>",
+            super::Options::default().coalesce_text(true),
+        );
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        assert_eq!(text.unescaped_text().unwrap(), "x & y");
+        unwrap!(reader.next(), Some(Ok(End)));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        assert!(matches!(
+            text.unescaped_text(),
+            Ok(std::borrow::Cow::Borrowed("plain"))
+        ));
+        unwrap!(reader.next(), Some(Ok(End)));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        assert_eq!(text.unescaped_text().unwrap_err().offset(), 17);
+    }
+
+    #[test]
+    fn unescaped_value() {
+        let mut reader = Reader::new(r#"<a x="1 &lt; 2" y='&#x41;' z="plain" w="a & b"/>"#);
+
+        let start = unwrap!(reader.next(), Some(Ok(Empty)));
+        let mut attributes = start.attributes();
+        assert_eq!(
+            attributes.next().unwrap().unescaped_value().unwrap(),
+            "1 < 2"
+        );
+        assert_eq!(attributes.next().unwrap().unescaped_value().unwrap(), "A");
+        assert!(matches!(
+            attributes.next().unwrap().unescaped_value(),
+            Ok(std::borrow::Cow::Borrowed("plain"))
+        ));
+        let error = attributes.next().unwrap().unescaped_value().unwrap_err();
+        assert_eq!(error.offset(), 2);
+    }
+
+    #[test]
+    fn entity_expansion_limits() {
+        let entities = [("lol".to_owned(), "lollollol".to_owned())]
+            .into_iter()
+            .collect::<std::collections::HashMap<_, _>>();
+        let code = "<a b='&lol;&lol;'>&lol;&lol;&lol;</a>";
+        let mut reader = Reader::with_options(
+            code,
+            super::Options::default()
+                .max_entity_expansions(2)
+                .max_entity_expansion_size(18),
+        );
+        reader.set_entity_resolver(&entities);
+
+        let start = unwrap!(reader.next(), Some(Ok(Start)));
+        let attr = start.attributes().next().unwrap();
+        assert_eq!(attr.value_in(&reader).unwrap(), "lol".repeat(6));
+
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        let error = text.content_in(&reader).unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::EntityExpansionLimit);
+        assert_eq!(error.span(), 28..29);
+    }
+
+    #[test]
+    fn doctype_internal_subset() {
+        let code = r#"<!DOCTYPE root [<!ENTITY a "b"> <!ELEMENT root ANY>]><root/>"#;
+        let mut reader = Reader::new(code);
+
+        let doctype = unwrap!(reader.next(), Some(Ok(Doctype)));
+        assert_eq!(
+            doctype.internal_subset(),
+            Some(r#"<!ENTITY a "b"> <!ELEMENT root ANY>"#)
+        );
+        assert!(matches!(
+            doctype.declarations().next(),
+            Some(crate::dtd::Declaration::Entity(entity)) if entity.name() == "a"
+        ));
+        assert_eq!(doctype.declarations().count(), 2);
+    }
+
+    #[test]
+    fn recover() {
+        let code = "<root><a b=c>x</a><ok/><bad x/></root>";
+        let mut reader = Reader::with_options(code, super::Options::default().recover(true));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind(),
+            super::ErrorKind::ExpectedAttributeValue
+        );
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).raw_content(), "x");
+        unwrap!(reader.next(), Some(Ok(End)));
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind(),
+            super::ErrorKind::ExpectedAttributeEq
+        );
+        // NOTE: The end tag of `a` was matched with `root`, so this one is unmatched.
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn skip_subtree() {
+        let code = r#"<root><skip a="</skip>"><!-- </skip> --><![CDATA[</skip>]]><x><y/></x></skip>text</root>"#;
+        let mut reader = Reader::new(code);
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(reader.skip_subtree().unwrap().unwrap().name(), "skip");
+        assert_eq!(reader.depth(), 1);
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).raw_content(), "text");
+        assert_eq!(reader.skip_subtree().unwrap().unwrap().name(), "root");
+        assert!(reader.next().is_none());
+
+        let options = super::Options::default().html_void_elements(true);
+        let mut reader = Reader::with_options("<p><q></br>text</q></p>", options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(reader.skip_subtree().unwrap().unwrap().name(), "q");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(End))).name(), "p");
+        assert!(reader.next().is_none());
+
+        let mut reader = Reader::with_options("<p><q><br>text</q><after/></p>", options);
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(reader.skip_subtree().unwrap().unwrap().name(), "q");
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(End))).name(), "p");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn subtree() {
+        let mut reader = Reader::with_options(
+            "<root xml:space='preserve'><a> <b>x</b> </a> <c/></root>",
+            super::Options::default().trim_text(true),
+        );
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let mut a = reader.subtree().unwrap();
+        let text = unwrap!(a.next(), Some(Ok(Text)));
+        assert_eq!(text.content(), " ");
+        assert_eq!(text.position_in(&a), 30..31);
+        assert_eq!(unwrap!(a.next(), Some(Ok(Start))).name(), "b");
+        assert_eq!(a.path().collect::<Vec<_>>(), ["root", "a", "b"]);
+        unwrap!(a.next(), Some(Ok(Text)));
+        unwrap!(a.next(), Some(Ok(End)));
+        unwrap!(a.next(), Some(Ok(Text)));
+        assert!(a.next().is_none());
+        assert_eq!(reader.depth(), 1);
+
+        // A peeked event is part of the subtree.
+        let mut reader = Reader::new("<root><a/><b/></root>");
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.peek().cloned(), Some(Ok(Empty)));
+        let mut root = reader.subtree().unwrap();
+        assert_eq!(unwrap!(root.next(), Some(Ok(Empty))).name(), "a");
+        assert_eq!(unwrap!(root.next(), Some(Ok(Empty))).name(), "b");
+        assert!(root.next().is_none());
+        assert!(reader.next().is_none());
+
+        let mut reader = Reader::with_options(
+            "<root><a></b></root>",
+            super::Options::default().validate_closing_tags(true),
+        );
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let mut root = reader.subtree().unwrap();
+        unwrap!(root.next(), Some(Ok(Start)));
+        assert_eq!(
+            root.next().unwrap().unwrap_err().kind(),
+            super::ErrorKind::MismatchedEndTag
+        );
+    }
+
+    #[test]
+    fn elements() {
+        let mut reader = Reader::new(concat!(
+            "<root><record n='1'><record n='nested'/></record>",
+            "<other><record n='2'>text</record></other><record n='3'/></root>"
+        ));
+
+        let mut seen = Vec::new();
+        for result in reader.elements("record") {
+            let (start, content) = result.unwrap();
+            seen.push((
+                start.attributes().next().unwrap().raw_value(),
+                content.count(),
+            ));
+        }
+        assert_eq!(seen, [("1", 1), ("2", 1), ("3", 0)]);
+        assert!(reader.next().is_none());
+
+        let mut reader = Reader::new("<root><record>text");
+        let mut elements = reader.elements("record");
+        let Some(Err(error)) = elements.next() else {
+            panic!()
+        };
+        assert_eq!(error.kind(), super::ErrorKind::UnclosedElement);
+        assert!(elements.next().is_none());
+    }
+
+    #[test]
+    fn child_ranges() {
+        let text = "<root><list><a x='>'><b/></a>text<c/></list><after/></root>";
+        let mut reader = Reader::new(text);
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let mut ranges = reader.child_ranges();
+        assert_eq!(&text[ranges.next().unwrap().unwrap()], "<a x='>'><b/></a>");
+        assert_eq!(&text[ranges.next().unwrap().unwrap()], "<c/>");
+        assert!(ranges.next().is_none());
+        assert!(ranges.next().is_none());
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Empty))).name(), "after");
+
+        let mut reader = Reader::new("<root><a>");
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let mut ranges = reader.child_ranges();
+        assert_eq!(
+            ranges.next().unwrap().unwrap_err().kind(),
+            super::ErrorKind::UnclosedElement
+        );
+    }
+
+    #[test]
+    fn current_lang() {
+        let mut reader = Reader::with_options(
+            "<a xml:lang='en'><b xml:space='preserve' xml:lang=''><c xml:lang='fr'/> </b> </a>",
+            super::Options::default().trim_text(true),
+        );
+
+        assert_eq!(reader.current_lang(), None);
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(reader.current_lang(), Some("en"));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(reader.current_lang(), Some(""));
+        let checkpoint = reader.checkpoint();
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        assert_eq!(reader.current_lang(), Some(""));
+        // The scope of xml:space is still tracked alongside.
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).content(), " ");
+        unwrap!(reader.next(), Some(Ok(End)));
+        assert_eq!(reader.current_lang(), Some("en"));
+        unwrap!(reader.next(), Some(Ok(End)));
+        assert_eq!(reader.current_lang(), None);
+
+        reader.rewind(checkpoint);
+        assert_eq!(reader.current_lang(), Some(""));
+    }
+
+    #[test]
+    fn raw_text_elements() {
+        let options = super::Options::default()
+            .raw_text_elements(["script", "x:style"])
+            .ignore_name_case(true);
+        let mut reader = Reader::with_options(
+            "<html><SCRIPT>a</scripts> <b>&amp;</SCRIPT ><x:style/><x:style>]]></X:Style></html>",
+            options.clone(),
+        );
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        assert!(text.is_raw());
+        assert_eq!(text.content(), "a</scripts> <b>&amp;");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(End))).name(), "SCRIPT");
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).content(), "]]>");
+        unwrap!(reader.next(), Some(Ok(End)));
+        unwrap!(reader.next(), Some(Ok(End)));
+        assert!(reader.next().is_none());
+
+        let mut reader = Reader::with_options("<script></script><script><a>", options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(End)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).content(), "<a>");
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind(),
+            super::ErrorKind::UnclosedElement
+        );
+
+        // Markup inside raw text is not seen by the skip scanners.
+        let mut reader =
+            Reader::with_options("<a><script>x</b>y</script><c/></a>", options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(reader.skip_subtree().unwrap().unwrap().name(), "a");
+        assert!(reader.next().is_none());
+
+        let mut reader = Reader::with_options("<a><script>1<2</script><c/></a>", options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(reader.skip_subtree().unwrap().unwrap().name(), "script");
+        unwrap!(reader.next(), Some(Ok(Empty)));
+
+        let mut reader = Reader::with_options("<a><script>1<2</script><c/></a>", options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert!(matches!(reader.peek(), Some(Ok(super::Event::Start(_)))));
+        assert_eq!(reader.skip_subtree().unwrap().unwrap().name(), "a");
+        assert!(reader.next().is_none());
+
+        let mut reader = Reader::with_options(
+            "<a><script><item id='1'/></script><item id='2'/></a>",
+            options.clone(),
+        );
+        let item = unwrap!(reader.find_element("item").unwrap(), Some(Empty));
+        assert_eq!(item.attributes().next().unwrap().value(), "2");
+
+        let mut reader = Reader::with_options("<a><b><script><b></script></b><b/></a>", options);
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let ranges = reader
+            .child_ranges()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(ranges, [3..30, 30..34]);
+    }
+
+    #[test]
+    fn text_chunks() {
+        let chunks = |code: &str, options: super::Options| {
+            Reader::with_options(code, options.text_chunk_size(3))
+                .filter_map(|event| match event.unwrap() {
+                    super::Event::Text(text) => Some(text.raw_content().to_owned()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let options = super::Options::default();
+        assert_eq!(chunks("<a>żółw</a>", options.clone()), ["ż", "ó", "łw"]);
+        assert_eq!(
+            chunks("<a>&amp;&lt;x&#1234;</a>", options.clone()),
+            ["&amp;", "&lt;", "x", "&#1234;"]
+        );
+        assert_eq!(
+            chunks("<a>ab\r\ncd</a>", options.clone()),
+            ["ab", "\r\nc", "d"]
+        );
+
+        let options = options.trim_text(true).coalesce_text(true);
+        assert_eq!(
+            chunks("<a>       x y       <![CDATA[z]]></a>", options),
+            ["x ", "y  ", "   ", "  <![CDATA[z]]>"]
+        );
+    }
+
+    #[test]
+    fn lazy_attributes() {
+        let code = "<a x='>' y='1' x='2'><b z=\"/>\"/><c/ ></a>";
+        let mut reader = Reader::with_options(
+            code,
+            super::Options::default()
+                .lazy_attributes(true)
+                .forbid_duplicate_attributes(true),
+        );
+
+        let a = unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(a.attributes().count(), 3);
+        let b = unwrap!(reader.next(), Some(Ok(Empty)));
+        assert_eq!(b.attributes().next().unwrap().value(), "/>");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Start))).name(), "c");
+        assert_eq!(reader.depth(), 2);
+
+        // Errors that prevent finding the end of the tag are still reported.
+        let mut reader =
+            Reader::with_options("<a x='>", super::Options::default().lazy_attributes(true));
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind(),
+            super::ErrorKind::UnclosedAttributeValue
+        );
+
+        let options = super::Options::default().forbid_duplicate_attributes(true);
+        let mut reader = Reader::with_options(code, options.clone());
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind(),
+            super::ErrorKind::DuplicateAttribute
+        );
+        let mut reader = Reader::with_options(code, options);
+        assert_eq!(
+            unwrap!(reader.find_element("c").unwrap(), Some(Start)).name(),
+            "c"
+        );
+    }
+
+    #[test]
+    fn only_elements() {
+        let code = concat!(
+            "text<root><skip a='<item>'>x<!-- <item> --><item/></skip>",
+            "<Item n='1'>a<item>b</item><![CDATA[c]]></Item>tail<item n='2'/></root>"
+        );
+        let mut reader = Reader::with_options(
+            code,
+            super::Options::default().only_elements(["Item", "item"]),
+        );
 
-        loop {
-            match self.next().transpose()? {
-                Some(Event::End(end)) if self.depth + 1 == end_depth => return Ok(Some(end)),
-                Some(_) => (),
-                None => return Ok(None),
-            }
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Empty))).name(), "item");
+        assert_eq!(reader.path().collect::<Vec<_>>(), ["root", "skip"]);
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Start))).name(), "Item");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).content(), "a");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Start))).name(), "item");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).content(), "b");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(End))).name(), "item");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(CData))).content(), "c");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(End))).name(), "Item");
+        let item = unwrap!(reader.next(), Some(Ok(Empty)));
+        assert_eq!(item.attributes().next().unwrap().value(), "2");
+        assert!(reader.next().is_none());
+
+        let mut reader = Reader::with_options(
+            "<a><b/><c>1</c></a>",
+            super::Options::default()
+                .only_elements(["C"])
+                .ignore_name_case(true),
+        );
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Start))).name(), "c");
+        let mut subtree = reader.subtree().unwrap();
+        assert_eq!(unwrap!(subtree.next(), Some(Ok(Text))).content(), "1");
+        assert!(reader.next().is_none());
+
+        for code in ["<b", "<a><b c='1'"] {
+            let options = super::Options::default()
+                .only_elements(["b"])
+                .allow_truncated_input(true);
+            let mut reader = Reader::with_options(code, options);
+            let start = unwrap!(reader.next(), Some(Ok(Start)));
+            assert!(start.is_truncated());
+            assert!(reader.next().is_none());
         }
     }
-}
 
-impl<'a> Iterator for Reader<'a> {
-    type Item = Result<Event<'a>, Error>;
+    #[test]
+    fn strict_names() {
+        let options = super::Options::default().strict_names(true);
+        let mut reader = Reader::with_options(
+            "<données xml:lang='fr' _a.b-c='1'><ns:日本/></données>",
+            options.clone(),
+        );
+        assert!(reader.all(|result| result.is_ok()));
+
+        for (code, span) in [
+            ("<1st/>", 1..4),
+            ("<a:-b/>", 1..5),
+            ("<a x\u{D7}='1'/>", 3..6),
+            ("<a></a\u{2000}>", 5..9),
+        ] {
+            let mut reader = Reader::with_options(code, options.clone());
+            let error = reader
+                .find_map(Result::err)
+                .unwrap_or_else(|| panic!("{code}"));
+            assert_eq!(error.kind(), super::ErrorKind::InvalidName);
+            assert_eq!(error.span(), span);
+            assert!(Reader::new(code).all(|result| result.is_ok()));
+        }
+    }
 
-    fn next(&mut self) -> Option<Result<Event<'a>, Error>> {
-        loop {
-            return match self.byte(self.buffer.current) {
-                Some(b'<') => match self.parse_node() {
-                    Ok(Some(event)) => Some(Ok(event)),
-                    Ok(None) => continue,
-                    Err(err) => Some(Err(err)),
-                },
-                Some(_) => {
-                    let node_start = self
-                        .buffer
-                        .memchr(self.buffer.current, b'<')
-                        .unwrap_or(self.buffer.text.len());
-                    let text_range = self.buffer.current..node_start;
-                    self.buffer.current = text_range.end;
+    #[test]
+    fn cancel_token() {
+        let token = super::CancelToken::new();
+        let options = super::Options::default()
+            .recover(true)
+            .collect_errors(true)
+            .cancel_token(token.clone());
+        let mut reader = Reader::with_options("<a><b/><c/></a>", options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+
+        token.cancel();
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::Cancelled);
+        assert_eq!(error.kind().category(), super::ErrorCategory::Cancelled);
+        assert_eq!(error.span(), 3..3);
+        assert!(reader.next().is_none());
+        assert!(reader.errors().is_empty());
+
+        let mut reader = Reader::with_options("<a/>", options);
+        assert_eq!(
+            reader.find_element("a").unwrap_err().kind(),
+            super::ErrorKind::Cancelled
+        );
+        assert!(reader.next().is_none());
+    }
 
-                    if self.depth == 0 && !self.options.allow_top_level_text {
-                        // SAFETY: node_start was just acquired from memchr or is equal to the length.
-                        //         self.buffer.current can also never be less than the string's length.
-                        if !unsafe { self.buffer.as_bytes().get_unchecked(text_range.clone()) }
-                            .iter()
-                            .copied()
-                            .all(is_whitespace)
-                        {
-                            self.set_error_state();
-                            return Some(Err(Error::new(ErrorKind::TopLevelText, text_range)));
-                        } else {
-                            self.buffer.current = text_range.end;
-                            continue;
-                        }
-                    }
+    #[test]
+    fn parse_values() {
+        let mut reader = Reader::new("<a n='&#x31;0' b='yes'> -3&#10;<![CDATA[]]></a>");
+        let a = unwrap!(reader.next(), Some(Ok(Start)));
+        let mut attributes = a.attributes();
+        assert_eq!(attributes.next().unwrap().parse::<u8>().unwrap(), 10);
+        let error = attributes.next().unwrap().parse::<bool>().unwrap_err();
+        assert_eq!(error.value(), "yes");
+        assert_eq!(error.position_in(&reader), 18..21);
+        assert_eq!(
+            error.to_string(),
+            "invalid value \"yes\": provided string was not `true` or `false`"
+        );
+        assert!(std::error::Error::source(&error).is_some());
 
-                    Some(Ok(Event::Text(TextEvent {
-                        // SAFETY: See above
-                        text: unsafe { self.buffer.text.get_unchecked(text_range) },
-                    })))
-                }
-                None if self.depth > 0 => {
-                    if self.options.allow_unclosed_tags {
-                        return None;
-                    }
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        assert_eq!(text.parse::<i32>().unwrap(), -3);
+        assert_eq!(text.parse::<String>().unwrap(), "-3");
+    }
 
-                    self.depth = 0;
-                    return Some(Err(Error::new(
-                        ErrorKind::UnclosedElement,
-                        self.buffer.empty_range_here(),
-                    )));
-                }
-                None => None,
-            };
+    #[test]
+    fn peek() {
+        let mut reader = Reader::new("<root><a><b/></a><c/></root>");
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.peek().cloned(), Some(Ok(Start))).name(), "a");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Start))).name(), "a");
+        assert_eq!(unwrap!(reader.peek().cloned(), Some(Ok(Empty))).name(), "b");
+        assert_eq!(reader.skip_subtree().unwrap().unwrap().name(), "a");
+        assert_eq!(reader.depth(), 1);
+        assert_eq!(unwrap!(reader.peek().cloned(), Some(Ok(Empty))).name(), "c");
+        // The peeked element is part of the skipped subtree.
+        assert_eq!(reader.skip_subtree().unwrap().unwrap().name(), "root");
+        assert_eq!(reader.depth(), 0);
+        assert!(reader.peek().is_none());
+
+        let mut reader = Reader::new("<a><b>x</b>y</a><c/>");
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.peek().cloned(), Some(Ok(Start)));
+        assert_eq!(reader.read_text().unwrap(), "xy");
+        assert_eq!(reader.depth(), 0);
+
+        let mut reader = Reader::new("<a><b>x</b>y</a><c/>");
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.peek().cloned(), Some(Ok(Start)));
+        assert_eq!(reader.skip_to_end().unwrap().unwrap().name(), "a");
+        unwrap!(reader.next(), Some(Ok(Empty)));
+
+        let mut reader = Reader::new("<a><b>x</b></a>");
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Text)));
+        unwrap!(reader.peek().cloned(), Some(Ok(End)));
+        assert_eq!(reader.skip_to_end().unwrap().unwrap().name(), "b");
+        assert_eq!(reader.depth(), 1);
+    }
+
+    #[test]
+    fn truncated_input() {
+        let options = super::Options::default().allow_truncated_input(true);
+
+        let mut reader = Reader::with_options("<a><!-- partial -", options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let comment = unwrap!(reader.next(), Some(Ok(Comment)));
+        assert!(comment.is_truncated());
+        assert_eq!(comment.content(), " partial -");
+        assert!(reader.next().is_none());
+
+        let mut reader =
+            Reader::with_options("<a><![CDATA[x]]", options.clone().coalesce_text(true));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let cdata = unwrap!(reader.next(), Some(Ok(CData)));
+        assert!(cdata.is_truncated());
+        assert_eq!(cdata.content(), "x]]");
+        assert!(reader.next().is_none());
+
+        for (code, text) in [
+            ("<a b='1' c='2", "<a b='1'"),
+            ("<a b='1' c", "<a b='1'"),
+            ("<a b='1' /", "<a b='1'"),
+            ("<a:b", "<a:b"),
+        ] {
+            let mut reader = Reader::with_options(code, options.clone());
+            let start = unwrap!(reader.next(), Some(Ok(Start)));
+            assert!(start.is_truncated());
+            assert!(!start.is_empty());
+            assert_eq!(start.position_in(&reader), 0..text.len());
+            assert!(reader.next().is_none());
         }
+
+        let mut reader = Reader::with_options("<a><!-- x --><b c='/'/></a>", options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert!(!unwrap!(reader.next(), Some(Ok(Comment))).is_truncated());
+        let start = unwrap!(reader.next(), Some(Ok(Empty)));
+        assert!(!start.is_truncated());
+
+        let mut reader = Reader::with_options("<a b='<'c></a>", options);
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind(),
+            super::ErrorKind::ExpectedAttributeEq
+        );
+
+        let mut reader = Reader::new("<a><!-- partial -");
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind(),
+            super::ErrorKind::UnclosedComment
+        );
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::Reader;
+    #[test]
+    fn multiple_roots() {
+        let code = "<record id='1'/>\n<record id='2'>x</record>\n<record id='3'/>\n";
+        let ids = Reader::new(code)
+            .filter_map(|event| match event.unwrap() {
+                super::Event::Start(start) | super::Event::Empty(start)
+                    if start.name() == "record" =>
+                {
+                    Some(start.get_attribute("id").unwrap().raw_value())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["1", "2", "3"]);
+
+        let mut reader = Reader::new("<a/>text<b/>");
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::TopLevelText);
+    }
 
-    macro_rules! unwrap {
-        ($event: expr, Some($($what: tt)*)) => {
-            unwrap!($event.expect("unexpected end of event stream"), $($what)*)
-        };
-        ($event: expr, Ok($what: ident)) => {
-            unwrap!($event.expect("parse error"), $what)
-        };
-        ($event: expr, $what: ident) => {{
-            let e = $event;
-            if let super::Event::$what(r) = e {
-                r
-            } else {
-                panic!(
-                    concat!("mismatched event, expected ", stringify!($what), " got {:?}"),
-                    e
-                )
-            }
-        }};
+    #[test]
+    fn trim_text() {
+        let code = "<root>\n    <a>  text\t</a>\n    <b> </b>\n</root>";
+        let mut reader = Reader::with_options(code, super::Options::default().trim_text(true));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).raw_content(), "text");
+        unwrap!(reader.next(), Some(Ok(End)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(End)));
+        unwrap!(reader.next(), Some(Ok(End)));
+        assert!(reader.next().is_none());
     }
 
     #[test]
-    fn element() {
-        let code =
-            "   <hello attr =  \"value\" 0ther4ttr=\t'val&apos;ue'>con&#x20;ten&#32;t</hello>   ";
-        let mut reader = Reader::new(code);
+    fn xml_space() {
+        let code = concat!(
+            "<root> <pre xml:space='preserve'> a <b> </b>",
+            "<c xml:space='default'> c </c></pre> d </root>"
+        );
+        let mut reader = Reader::with_options(code, super::Options::default().trim_text(true));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).raw_content(), " a ");
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).raw_content(), " ");
+        unwrap!(reader.next(), Some(Ok(End)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).raw_content(), "c");
+        unwrap!(reader.next(), Some(Ok(End)));
+        unwrap!(reader.next(), Some(Ok(End)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).raw_content(), "d");
+        unwrap!(reader.next(), Some(Ok(End)));
+        assert!(reader.next().is_none());
+    }
 
-        {
-            let start = unwrap!(reader.next(), Some(Ok(Start)));
-            assert_eq!(start.name(), "hello");
+    #[test]
+    fn whitespace_text() {
+        let code = "<a> \t<b/>&#x20;<c/>\n<![CDATA[ ]]> <d/><![CDATA[x]]></a>";
+        let mut reader = Reader::with_options(code, super::Options::default().coalesce_text(true));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert!(unwrap!(reader.next(), Some(Ok(Text))).is_whitespace());
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        assert!(!unwrap!(reader.next(), Some(Ok(Text))).is_whitespace());
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        assert!(unwrap!(reader.next(), Some(Ok(Text))).is_whitespace());
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        assert!(!unwrap!(reader.next(), Some(Ok(Text))).is_whitespace());
+    }
 
-            let mut attributes = start.attributes();
-            {
-                let attr = attributes.next().unwrap();
-                assert_eq!(attr.name(), "attr");
-                assert_eq!(attr.value(), "value");
-                assert_eq!(attr.raw_value(), "value");
-            }
-            {
-                let attr = attributes.next().unwrap();
-                assert_eq!(attr.name(), "0ther4ttr");
-                assert_eq!(attr.value(), "val'ue");
-                assert_eq!(attr.raw_value(), "val&apos;ue");
-            }
-            assert!(attributes.next().is_none());
-        }
+    #[test]
+    fn coalesce_text() {
+        let code = "<root>a &amp; <![CDATA[<b>]]>c<!-- --><![CDATA[d]]></root>";
+        let mut reader = Reader::with_options(code, super::Options::default().coalesce_text(true));
 
+        unwrap!(reader.next(), Some(Ok(Start)));
         {
             let text = unwrap!(reader.next(), Some(Ok(Text)));
-            assert_eq!(text.content(), "con ten t");
-            assert_eq!(text.raw_content(), "con&#x20;ten&#32;t");
+            assert_eq!(text.content(), "a & <b>c");
+            assert_eq!(text.raw_content(), "a &amp; <![CDATA[<b>]]>c");
         }
-
+        unwrap!(reader.next(), Some(Ok(Comment)));
         {
-            let end = unwrap!(reader.next(), Some(Ok(End)));
-            assert_eq!(end.name(), "hello");
+            let text = unwrap!(reader.next(), Some(Ok(Text)));
+            assert!(matches!(text.content(), std::borrow::Cow::Borrowed("d")));
         }
+        unwrap!(reader.next(), Some(Ok(End)));
     }
 
     #[test]
-    fn comments() {
-        let comment_text = " this is a &comment -- text ";
-        let code = format!("   <!--{comment_text}-->   ");
-        let mut reader = Reader::new(&code);
+    fn validate_closing_tags() {
+        let code = "<a><b:c></b:c><d></a></d>";
+        let mut reader =
+            Reader::with_options(code, super::Options::default().validate_closing_tags(true));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(End)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::MismatchedEndTag);
+        assert_eq!(error.span(), 19..20);
+        assert_eq!(error.expected_name(), Some("d"));
+        assert_eq!(error.found_name(), Some("a"));
+        assert_eq!(error.opened_at(), Some(15..16));
+        assert!(reader.next().is_none());
+    }
 
-        let comment = unwrap!(reader.next(), Some(Ok(Comment)));
-        assert_eq!(comment.content(), comment_text);
+    #[test]
+    fn from_file() {
+        let path = std::env::temp_dir().join(format!("speedy-xml-file-{}.xml", std::process::id()));
+        let mut buffer = String::new();
+
+        std::fs::write(&path, "\u{FEFF}<root>text</root>").unwrap();
+        let mut reader = Reader::from_file(&path, &mut buffer, super::Options::default()).unwrap();
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).content(), "text");
+
+        std::fs::write(&path, b"\xFE\xFF\0<\0a\0/\0>").unwrap();
+        let mut reader = Reader::from_file(&path, &mut buffer, super::Options::default()).unwrap();
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Empty))).name(), "a");
+
+        std::fs::write(&path, b"<a>\xFF</a>").unwrap();
+        assert!(matches!(
+            Reader::from_file(&path, &mut buffer, super::Options::default()),
+            Err(crate::push::ReadError::Decode(_))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            Reader::from_file(&path, &mut buffer, super::Options::default()),
+            Err(crate::push::ReadError::Io(_))
+        ));
     }
 
     #[test]
-    fn element_tree() {
-        let code = r#"
-            <tree>
-                <ns:stuff1>one</stuff2>
-                one is &lt; two
-            </not:tree>
-        "#;
+    fn normalize_line_endings() {
+        let code = "<a b='1\r\n2&#13;'>x\r\ny\rz<![CDATA[\r\n]]>\u{85}</a>";
+        let options = super::Options::default().normalize_line_endings(true);
+        let mut reader = Reader::with_options(code, options.clone().coalesce_text(true));
+        let start = unwrap!(reader.next(), Some(Ok(Start)));
+        let attr = start.attributes().next().unwrap();
+        assert_eq!(attr.value_in(&reader).unwrap(), "1\n2\r");
+        assert_eq!(attr.value(), "1\r\n2\r");
+        let text = unwrap!(reader.next(), Some(Ok(Text)));
+        assert_eq!(text.content_in(&reader).unwrap(), "x\ny\nz\n\u{85}");
+
+        let mut reader = Reader::with_options(code, options.xml_version_1_1(true));
+        reader.next();
+        assert_eq!(reader.read_text().unwrap(), "x\ny\nz\n\n");
+
         let mut reader = Reader::new(code);
+        reader.next();
+        assert_eq!(reader.read_text().unwrap(), "x\r\ny\rz\r\n\u{85}");
+    }
 
-        {
-            let start = unwrap!(reader.next(), Some(Ok(Start)));
-            assert_eq!(start.prefix(), None);
-            assert_eq!(start.name(), "tree");
-            assert!(start.attributes().next().is_none());
-        }
+    #[test]
+    fn normalize_attribute_values() {
+        let code = "<a b='1\r\n\t2&#10;&#9;' c='&amp;\n'/>";
+        let mut reader = Reader::with_options(
+            code,
+            super::Options::default().normalize_attribute_values(true),
+        );
+        let start = unwrap!(reader.next(), Some(Ok(Empty)));
+        let values = start
+            .attributes()
+            .map(|attr| attr.value_in(&reader).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values, ["1  2\n\t", "& "]);
+        assert_eq!(start.get_attribute("c").unwrap().value(), "&\n");
+    }
 
-        {
-            let text = unwrap!(reader.next(), Some(Ok(Text)));
-            assert_eq!(text.raw_content(), "\n                ");
+    #[test]
+    fn forbid_control_characters() {
+        let code = "<a>\x08<![CDATA[\x1F]]><b x='\x01'/>\t</a>";
+        let mut reader = Reader::new(code);
+        for _ in 0..6 {
+            assert!(reader.next().unwrap().is_ok());
         }
 
-        {
-            let start = unwrap!(reader.next(), Some(Ok(Start)));
-            assert_eq!(start.prefix(), Some("ns"));
-            assert_eq!(start.name(), "stuff1");
-            assert!(start.attributes().next().is_none());
-        }
+        let options = super::Options::default()
+            .forbid_control_characters(true)
+            .recover(true);
+        let mut reader = Reader::with_options(code, options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::InvalidCharacter);
+        assert_eq!(error.span(), 3..4);
+        assert_eq!(reader.next().unwrap().unwrap_err().span(), 13..14);
+        assert_eq!(reader.next().unwrap().unwrap_err().span(), 23..24);
+        unwrap!(reader.next(), Some(Ok(Text)));
+        unwrap!(reader.next(), Some(Ok(End)));
+
+        let mut reader = Reader::with_options(code, options.coalesce_text(true));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(reader.next().unwrap().unwrap_err().span(), 3..4);
+        assert_eq!(reader.next().unwrap().unwrap_err().span(), 23..24);
+    }
 
-        {
-            let text = unwrap!(reader.next(), Some(Ok(Text)));
-            assert_eq!(text.content(), "one");
+    #[test]
+    fn xml_version_1_1() {
+        let code = "<a>\u{7F}\u{85}\u{9F}</a>";
+        let options = super::Options::default().forbid_control_characters(true);
+        let mut reader = Reader::with_options(code, options.clone());
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Text)));
+
+        let mut reader = Reader::with_options(code, options.clone().xml_version_1_1(true));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(reader.next().unwrap().unwrap_err().span(), 3..4);
+
+        let code = "<?xml version='1.1'?><a>\u{85}\u{9F}</a>";
+        let mut reader = Reader::with_options(code, options);
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::InvalidCharacter);
+        assert_eq!(error.span(), 26..28);
+
+        // Malformed declarations are skipped without looking at their pseudo-attributes.
+        for code in ["<?xml version=?><a/>", "<?xml v='1?><a/>"] {
+            let mut reader = Reader::new(code);
+            unwrap!(reader.next(), Some(Ok(Empty)));
+            assert!(reader.next().is_none());
+
+            let options = super::Options::default().parse_declaration_node(true);
+            let mut reader = Reader::with_options(code, options);
+            assert!(reader.next().unwrap().is_err());
         }
+    }
 
-        {
-            let end = unwrap!(reader.next(), Some(Ok(End)));
-            assert_eq!(end.name(), "stuff2");
-        }
+    #[test]
+    fn error_codes() {
+        use super::{ErrorCategory, ErrorKind};
+
+        assert_eq!(ErrorKind::UnclosedComment.code(), "unclosed-comment");
+        assert_eq!(ErrorKind::UnclosedComment.category(), ErrorCategory::Syntax);
+        assert_eq!(
+            ErrorKind::MismatchedEndTag.category(),
+            ErrorCategory::Structure
+        );
+        assert_eq!(
+            ErrorKind::EntityExpansionLimit.category(),
+            ErrorCategory::Limit
+        );
 
-        {
-            let text = unwrap!(reader.next(), Some(Ok(Text)));
-            assert_eq!(
-                text.content(),
-                "\n                one is < two\n            "
-            );
-            assert_eq!(
-                text.raw_content(),
-                "\n                one is &lt; two\n            "
-            );
-        }
+        let error = crate::encoding::DecodeError::InvalidUtf16(3);
+        assert_eq!(error.code(), "invalid-utf16");
+        assert_eq!(error.category(), ErrorCategory::Encoding);
+    }
 
-        {
-            let end = unwrap!(reader.next(), Some(Ok(End)));
-            assert_eq!(end.prefix(), Some("not"));
-            assert_eq!(end.name(), "tree");
-        }
+    #[test]
+    fn error_snippet() {
+        let code = "<a>\r\n\tżółw <b cd></b>\r\n</a>";
+        let error = Reader::new(code).find_map(Result::err).unwrap();
+        assert_eq!(error.line_column(code), (2, 10));
+        assert_eq!(
+            error.display_with_source(code).to_string(),
+            concat!(
+                "error: expected `=` after attribute name\n",
+                " --> 2:10\n",
+                "  |\n",
+                "2 | \tżółw <b cd></b>\n",
+                "  |          ^^\n"
+            )
+        );
     }
 
     #[test]
-    fn cdata() {
-        let content = "this is some cdata < > > & & !!";
-        let code = format!("<![CDATA[{content}]]>");
-        let mut reader = Reader::new(&code);
+    fn forbid_doctype() {
+        let code = "<!DOCTYPE a [<!ENTITY b \"c\">]><a>&b;</a>";
+        let mut reader = Reader::with_options(code, super::Options::default().forbid_doctype(true));
+
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::DoctypeForbidden);
+        assert_eq!(error.span(), 0..9);
+        assert!(reader.next().is_none());
+    }
 
-        {
-            let end = unwrap!(reader.next(), Some(Ok(CData)));
-            assert_eq!(end.content(), content);
-        }
+    #[test]
+    fn ignore_name_case() {
+        let code = r#"<HTML><Body x="1" X="2"></body></html>"#;
+        let options = super::Options::default()
+            .validate_closing_tags(true)
+            .ignore_name_case(true);
+        let mut reader = Reader::with_options(code, options.clone());
+
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Start))).name(), "HTML");
+        unwrap!(reader.next(), Some(Ok(Start)));
+        assert_eq!(unwrap!(reader.next(), Some(Ok(End))).name(), "body");
+        unwrap!(reader.next(), Some(Ok(End)));
+        assert!(reader.next().is_none());
+
+        let mut reader = Reader::with_options(code, options.forbid_duplicate_attributes(true));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::DuplicateAttribute);
+        assert_eq!(error.span(), 18..19);
+
+        let options = super::Options::default()
+            .ignore_name_case(true)
+            .forbid_duplicate_attributes(true);
+        let mut reader = Reader::with_options("<a Y='1' z='2' y='3'/>", options);
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::DuplicateAttribute);
+        assert_eq!(error.span(), 15..16);
+
+        let mut reader =
+            Reader::with_options(code, super::Options::default().ignore_name_case(true));
+        let Ok(Some(super::Event::Start(body))) = reader.find_element("body") else {
+            panic!()
+        };
+        assert_eq!(body.name(), "Body");
     }
 
     #[test]
-    fn doctype() {
-        let content = "\tthis is a doctype [with] [many [brackets[[[]]][][]]]\n";
-        let code = format!("<!DOCTYPE {content}>");
-        let mut reader = Reader::new(&code);
+    fn forbid_duplicate_attributes() {
+        let code = r#"<a x="1" y:x="2"/><b x='1' y="2" x="3"/>"#;
+        let mut reader = Reader::with_options(
+            code,
+            super::Options::default().forbid_duplicate_attributes(true),
+        );
 
-        {
-            let end = unwrap!(reader.next(), Some(Ok(Doctype)));
-            assert_eq!(end.content(), content);
-        }
+        unwrap!(reader.next(), Some(Ok(Empty)));
+
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::DuplicateAttribute);
+        assert_eq!(error.span(), 33..34);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn max_depth() {
+        let code = "<a><b><c/></b><b><c><d/></c></b></a>";
+        let mut reader = Reader::with_options(code, super::Options::default().max_depth(3));
+
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        unwrap!(reader.next(), Some(Ok(End)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Start)));
+
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::MaxDepthExceeded);
+        assert_eq!(&code[error.span()], "d");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn attribute_limits() {
+        let options = super::Options::default()
+            .max_attributes(2)
+            .max_attribute_value_length(3);
+
+        let mut reader =
+            Reader::with_options("<a x='1' y='123'/><b x='1' y='2' z='3'/>", options.clone());
+        unwrap!(reader.next(), Some(Ok(Empty)));
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::TooManyAttributes);
+        assert_eq!(error.span(), 33..34);
+
+        let mut reader = Reader::with_options("<a x='1234'/>", options);
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::AttributeValueTooLong);
+        assert_eq!(error.span(), 6..10);
+    }
+
+    #[test]
+    fn collect_errors() {
+        let code = "<a>x<b c/>\n<d e=/>\n</a>y";
+        let options = super::Options::default().recover(true).collect_errors(true);
+        let mut reader = Reader::with_options(code, options);
+        unwrap!(reader.next(), Some(Ok(Start)));
+        let checkpoint = reader.checkpoint();
+        assert!(reader.by_ref().all(|event| event.is_ok()));
+        let errors = reader
+            .errors()
+            .iter()
+            .map(|error| (error.kind(), error.line_column(code).0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                (super::ErrorKind::ExpectedAttributeEq, 1),
+                (super::ErrorKind::ExpectedAttributeValue, 2),
+                (super::ErrorKind::TopLevelText, 3)
+            ]
+        );
+
+        // Readers can be rewound after they returned None.
+        assert!(reader.next().is_none());
+        reader.rewind(checkpoint);
+        assert!(reader.errors().is_empty());
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).raw_content(), "x");
+        assert_eq!(unwrap!(reader.next(), Some(Ok(Text))).raw_content(), "\n");
+        assert_eq!(reader.take_errors().len(), 1);
+        assert!(reader.errors().is_empty());
+
+        let options = super::Options::default().collect_errors(true);
+        let mut reader = Reader::with_options(code, options);
+        unwrap!(reader.next(), Some(Ok(Start)));
+        unwrap!(reader.next(), Some(Ok(Text)));
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+        assert!(reader.errors().is_empty());
+    }
+
+    #[test]
+    fn event_limits() {
+        let code = "<a><b/>text<c x='1'/></a>";
+
+        let options = super::Options::default().max_total_events(3);
+        let mut reader = Reader::with_options(code, options.clone().recover(true));
+        assert_eq!(reader.by_ref().take(3).filter(Result::is_ok).count(), 3);
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::TooManyEvents);
+        assert_eq!(error.span(), 11..21);
+        assert!(reader.next().is_none());
+
+        let mut reader = Reader::with_options(code, options);
+        let checkpoint = reader.checkpoint();
+        assert_eq!(reader.by_ref().take(3).filter(Result::is_ok).count(), 3);
+        reader.rewind(checkpoint);
+        assert_eq!(reader.by_ref().take(3).filter(Result::is_ok).count(), 3);
+
+        let mut reader = Reader::with_options(code, super::Options::default().max_event_size(8));
+        assert_eq!(reader.by_ref().take(3).filter(Result::is_ok).count(), 3);
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), super::ErrorKind::EventTooLarge);
+        assert_eq!(error.span(), 11..21);
+        assert!(reader.next().is_none());
     }
 }
@@ -0,0 +1,125 @@
+//! A SAX-style push interface layered on top of the pull reader.
+//!
+//! Implement [`Handler`] and pass it to [`drive`] or [`Reader::drive`](crate::Reader::drive)
+//! to receive callbacks for every event in a document.
+
+use crate::reader::{
+    CDataEvent, CommentEvent, DeclEvent, DoctypeEvent, Error, Event, PIEvent, StartEvent, TextEvent,
+};
+
+/// Callbacks invoked by [`drive`] for the events of a document.
+///
+/// All methods do nothing by default.
+#[allow(unused_variables)]
+pub trait Handler<'a> {
+    /// Called for every start tag and every empty tag.
+    fn start_element(&mut self, start: StartEvent<'a>) {}
+
+    /// Called for every end tag and right after [`Self::start_element`] for empty tags.
+    fn end_element(&mut self, prefix: Option<&'a str>, name: &'a str) {}
+
+    /// Called for text content.
+    fn text(&mut self, text: TextEvent<'a>) {}
+
+    /// Called for cdata sections.
+    fn cdata(&mut self, cdata: CDataEvent<'a>) {}
+
+    /// Called for comments.
+    fn comment(&mut self, comment: CommentEvent<'a>) {}
+
+    /// Called for doctype declarations.
+    fn doctype(&mut self, doctype: DoctypeEvent<'a>) {}
+
+    /// Called for processing instructions.
+    fn processing_instruction(&mut self, pi: PIEvent<'a>) {}
+
+    /// Called for the XML declaration.
+    fn declaration(&mut self, decl: DeclEvent<'a>) {}
+}
+
+/// Reads all `events` and invokes the corresponding callbacks of `handler`.
+///
+/// # Examples
+///
+/// ```
+/// # use speedy_xml::{reader::StartEvent, sax::Handler, Reader};
+/// struct Depth(usize, usize);
+///
+/// impl<'a> Handler<'a> for Depth {
+///     fn start_element(&mut self, _: StartEvent<'a>) {
+///         self.0 += 1;
+///         self.1 = self.1.max(self.0);
+///     }
+///
+///     fn end_element(&mut self, _: Option<&'a str>, _: &'a str) {
+///         self.0 -= 1;
+///     }
+/// }
+///
+/// let mut handler = Depth(0, 0);
+/// Reader::new("<a><b><c/></b></a>").drive(&mut handler).unwrap();
+/// assert_eq!(handler.1, 3);
+/// ```
+///
+/// # Errors
+///
+/// Returns the first error encountered, callbacks for events before it will already have
+/// been invoked.
+pub fn drive<'a>(
+    events: impl IntoIterator<Item = Result<Event<'a>, Error>>,
+    handler: &mut impl Handler<'a>,
+) -> Result<(), Error> {
+    for event in events {
+        match event? {
+            Event::Start(start) => handler.start_element(start),
+            Event::Empty(start) => {
+                handler.start_element(start);
+                handler.end_element(start.prefix(), start.name());
+            }
+            Event::End(end) => handler.end_element(end.prefix(), end.name()),
+            Event::Text(text) => handler.text(text),
+            Event::CData(cdata) => handler.cdata(cdata),
+            Event::Comment(comment) => handler.comment(comment),
+            Event::Doctype(doctype) => handler.doctype(doctype),
+            Event::PI(pi) => handler.processing_instruction(pi),
+            Event::Decl(decl) => handler.declaration(decl),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{drive, Handler};
+    use crate::reader::{ErrorKind, Reader, StartEvent, TextEvent};
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl<'a> Handler<'a> for Recorder {
+        fn start_element(&mut self, start: StartEvent<'a>) {
+            self.0.push(format!("<{}>", start.name()));
+        }
+
+        fn end_element(&mut self, _prefix: Option<&'a str>, name: &'a str) {
+            self.0.push(format!("</{name}>"));
+        }
+
+        fn text(&mut self, text: TextEvent<'a>) {
+            self.0.push(text.content().into_owned());
+        }
+    }
+
+    #[test]
+    fn callbacks() {
+        let mut recorder = Recorder::default();
+        drive(Reader::new("<a>x<!-- c --><b/></a>"), &mut recorder).unwrap();
+        assert_eq!(recorder.0, ["<a>", "x", "<b>", "</b>", "</a>"]);
+
+        let mut recorder = Recorder::default();
+        let error = drive(Reader::new("<a><b></a"), &mut recorder).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnclosedEndTag);
+        assert_eq!(recorder.0, ["<a>", "<b>"]);
+    }
+}
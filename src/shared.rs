@@ -0,0 +1,146 @@
+//! Parsing of reference-counted [`Bytes`] buffers.
+//!
+//! A [`SharedDocument`] keeps a handle to its buffer, so events can be turned into
+//! [`SharedEvent`]s that outlive the reader without copying their text. This module is
+//! only available with the `bytes` feature.
+
+use std::borrow::Cow;
+
+use bytes::Bytes;
+
+use crate::{
+    encoding::{self, DecodeError},
+    reader::Event,
+};
+
+/// A document backed by a reference-counted [`Bytes`] buffer.
+///
+/// Documents in UTF-8 are parsed directly from the buffer, documents in other encodings
+/// are decoded into a new buffer when the document is created.
+///
+/// # Examples
+///
+/// ```
+/// # use bytes::Bytes;
+/// # use speedy_xml::{reader::{Event, Options}, shared::SharedDocument, Reader};
+/// let document = SharedDocument::new(Bytes::from_static(b"<a>text</a>")).unwrap();
+/// let events = Reader::from_shared(&document, Options::default())
+///     .map(|event| event.map(|event| document.share(event)))
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+/// drop(document);
+///
+/// let Event::Text(text) = events[1].as_event() else { panic!() };
+/// assert_eq!(text.content(), "text");
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedDocument {
+    /// The text of the document, without any byte order mark.
+    bytes: Bytes,
+}
+
+impl SharedDocument {
+    /// Checks that `bytes` is valid in its detected encoding and creates a document out of it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not valid in its detected encoding, see
+    /// [`encoding::decode`].
+    pub fn new(bytes: Bytes) -> Result<Self, DecodeError> {
+        let bytes = match encoding::decode(&bytes)? {
+            Cow::Borrowed(text) => bytes.slice_ref(text.as_bytes()),
+            Cow::Owned(text) => Bytes::from(text),
+        };
+
+        Ok(Self { bytes })
+    }
+
+    /// Returns the text of this document.
+    pub fn text(&self) -> &str {
+        // SAFETY: The buffer was validated in new and Bytes are immutable.
+        unsafe { std::str::from_utf8_unchecked(&self.bytes) }
+    }
+
+    /// Returns the buffer holding the text of this document.
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    /// Turns `event` into a [`SharedEvent`] that keeps a handle to this document's buffer
+    /// instead of borrowing from it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `event` was not read from this document.
+    pub fn share(&self, event: Event<'_>) -> SharedEvent {
+        SharedEvent {
+            bytes: self.bytes.slice_ref(event.text().as_bytes()),
+            event: event.with_text(""),
+        }
+    }
+}
+
+/// An [`Event`] that shares its text with a [`SharedDocument`], created with
+/// [`SharedDocument::share`].
+#[derive(Debug, Clone)]
+pub struct SharedEvent {
+    bytes: Bytes,
+    /// The original event with its text replaced by an empty string.
+    event: Event<'static>,
+}
+
+impl SharedEvent {
+    /// Returns the event borrowing from this shared event.
+    ///
+    /// The `position_in` methods of the returned event must not be used, since its text
+    /// is no longer part of any reader's buffer.
+    pub fn as_event(&self) -> Event<'_> {
+        // SAFETY: The bytes are a slice of a validated document that covers a whole event,
+        //         which always starts and ends on a character boundary.
+        self.event
+            .with_text(unsafe { std::str::from_utf8_unchecked(&self.bytes) })
+    }
+
+    /// Returns the buffer holding the raw text of this event.
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::SharedDocument;
+    use crate::{
+        reader::{Event, Options},
+        Reader,
+    };
+
+    #[test]
+    fn share_events() {
+        let buffer = Bytes::from_static("\u{FEFF}<root a='1'>té&amp;xt</root>".as_bytes());
+        let document = SharedDocument::new(buffer.clone()).unwrap();
+        assert_eq!(document.text(), "<root a='1'>té&amp;xt</root>");
+
+        let events = Reader::from_shared(&document, Options::default())
+            .map(|event| document.share(event.unwrap()))
+            .collect::<Vec<_>>();
+        drop(document);
+
+        let Event::Start(start) = events[0].as_event() else {
+            panic!()
+        };
+        assert_eq!(start.attributes().next().unwrap().value(), "1");
+        let Event::Text(text) = events[1].as_event() else {
+            panic!()
+        };
+        assert_eq!(text.content(), "té&xt");
+        // The text was not copied out of the original buffer.
+        assert_eq!(events[1].as_bytes().as_ptr(), buffer[15..].as_ptr());
+
+        let document = SharedDocument::new(Bytes::from_static(b"\xFF\xFE<\0a\0/\0>\0")).unwrap();
+        assert_eq!(document.text(), "<a/>");
+        assert!(SharedDocument::new(Bytes::from_static(b"<a>\xFF</a>")).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! A blocking reader that pulls its input from an [`io::Read`](std::io::Read) source.
+
+use std::io::Read;
+
+use crate::{
+    push::{Driver, Next, ReadError},
+    reader::{Event, Options},
+};
+
+/// An XML reader that reads its input from a [`Read`] source.
+///
+/// Input is read in chunks as needed and parsed with a [`PushParser`](crate::push::PushParser), so events are
+/// exactly the same as those produced by [`Reader`](crate::Reader) for the same document.
+///
+/// The input is not buffered beyond what is needed to parse the next event, so wrapping the
+/// source in a [`BufReader`](std::io::BufReader) is not necessary.
+///
+/// With the `flate2` feature enabled, gzip-compressed input is detected and decompressed
+/// transparently.
+///
+/// # Examples
+///
+/// ```
+/// # use speedy_xml::{reader::Event, stream::StreamReader};
+/// let mut reader = StreamReader::new(&b"<root><child/></root>"[..]);
+/// let mut buf = Vec::new();
+/// let mut names = Vec::new();
+///
+/// while let Some(event) = reader.read_event_into(&mut buf) {
+///     if let Event::Start(start) | Event::Empty(start) = event.unwrap() {
+///         names.push(start.name().to_owned());
+///     }
+/// }
+/// assert_eq!(names, ["root", "child"]);
+/// ```
+pub struct StreamReader<R> {
+    source: R,
+    driver: Driver,
+}
+
+impl<R: Read> StreamReader<R> {
+    /// Creates a new reader over `source`.
+    pub fn new(source: R) -> Self {
+        Self::with_options(source, Options::default())
+    }
+
+    /// Creates a new reader over `source` with the provided [`Options`].
+    pub fn with_options(source: R, options: Options) -> Self {
+        Self {
+            source,
+            driver: Driver::new(options),
+        }
+    }
+
+    /// Sets a callback that is invoked after every chunk read from the source with the total
+    /// number of bytes read so far and `total`, which should be the size of the source if
+    /// it is known.
+    ///
+    /// Bytes are counted as they are read from the source, so for compressed input the
+    /// compressed size is reported.
+    pub fn set_progress_callback(
+        &mut self,
+        total: Option<u64>,
+        callback: impl FnMut(u64, Option<u64>) + Send + 'static,
+    ) {
+        self.driver.set_progress_callback(total, callback);
+    }
+
+    /// Returns the next event, reading more input from the source if necessary.
+    ///
+    /// The text of the event is copied into `buf`, replacing its previous contents, so the
+    /// returned event only borrows from `buf`. Reusing the same buffer for every call avoids
+    /// allocating for each event.
+    ///
+    /// Returns `None` once the end of the document has been reached or after an error other
+    /// than an I/O error has been returned. Spans of parse errors are relative to the start
+    /// of the input.
+    pub fn read_event_into<'b>(
+        &mut self,
+        buf: &'b mut Vec<u8>,
+    ) -> Option<Result<Event<'b>, ReadError>> {
+        loop {
+            match self.driver.next() {
+                Next::Event(event, range) => {
+                    buf.clear();
+                    buf.extend_from_slice(self.driver.attach(event, range).text().as_bytes());
+                    // SAFETY: The bytes were just copied from a str.
+                    let text = unsafe { std::str::from_utf8_unchecked(buf) };
+                    return Some(Ok(event.with_text(text)));
+                }
+                Next::Error(error) => return Some(Err(error)),
+                Next::Done => return None,
+                Next::NeedInput => (),
+            }
+
+            let read = self.source.read(self.driver.chunk());
+            if let Err(error) = self.driver.consume(read) {
+                return Some(Err(error));
+            }
+        }
+    }
+
+    /// Returns a reference to the underlying source.
+    pub fn get_ref(&self) -> &R {
+        &self.source
+    }
+
+    /// Consumes this reader and returns the underlying source.
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StreamReader;
+    use crate::{
+        push::ReadError,
+        reader::{ErrorKind, Event},
+    };
+
+    #[test]
+    fn read_event_into() {
+        let code = "<a x='1'>zażółć<b/></a>";
+        let mut reader = StreamReader::new(std::io::Read::chain(
+            &code.as_bytes()[..6],
+            &code.as_bytes()[6..],
+        ));
+        let mut buf = Vec::new();
+
+        let Some(Ok(Event::Start(start))) = reader.read_event_into(&mut buf) else {
+            panic!()
+        };
+        assert_eq!(start.attributes().next().unwrap().value(), "1");
+        let Some(Ok(Event::Text(text))) = reader.read_event_into(&mut buf) else {
+            panic!()
+        };
+        assert_eq!(text.content(), "zażółć");
+        assert_eq!(buf, "zażółć".as_bytes());
+        assert!(matches!(
+            reader.read_event_into(&mut buf),
+            Some(Ok(Event::Empty(_)))
+        ));
+        assert!(matches!(
+            reader.read_event_into(&mut buf),
+            Some(Ok(Event::End(_)))
+        ));
+        assert!(reader.read_event_into(&mut buf).is_none());
+
+        let mut reader = StreamReader::new(&b"<a>"[..]);
+        assert!(matches!(
+            reader.read_event_into(&mut buf),
+            Some(Ok(Event::Start(_)))
+        ));
+        let Some(Err(ReadError::Parse(error))) = reader.read_event_into(&mut buf) else {
+            panic!()
+        };
+        assert_eq!(error.kind(), ErrorKind::UnclosedElement);
+        assert!(reader.read_event_into(&mut buf).is_none());
+    }
+
+    #[test]
+    fn progress() {
+        let code = b"<root><a/>text</root>";
+        let mut reader = StreamReader::new(std::io::Read::chain(&code[..6], &code[6..]));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        reader.set_progress_callback(Some(code.len() as u64), move |read, total| {
+            sender.send((read, total)).unwrap();
+        });
+
+        let mut buf = Vec::new();
+        assert!(matches!(
+            reader.read_event_into(&mut buf),
+            Some(Ok(Event::Start(_)))
+        ));
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [(6, Some(21))]);
+        while let Some(event) = reader.read_event_into(&mut buf) {
+            event.unwrap();
+        }
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [(21, Some(21))]);
+    }
+
+    #[test]
+    fn decode_error() {
+        let source = std::io::Read::chain(&b"<a>"[..], &b"\xff<b/></a>"[..]);
+        let mut reader = StreamReader::new(source);
+        let mut buf = Vec::new();
+        assert!(matches!(
+            reader.read_event_into(&mut buf),
+            Some(Ok(Event::Start(_)))
+        ));
+        assert!(matches!(
+            reader.read_event_into(&mut buf),
+            Some(Err(ReadError::Decode(_)))
+        ));
+        assert!(reader.read_event_into(&mut buf).is_none());
+        assert!(reader.read_event_into(&mut buf).is_none());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gzip() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(b"<root>text</root>").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut reader = StreamReader::new(&compressed[..]);
+        let mut buf = Vec::new();
+        let mut count = 0;
+        while let Some(event) = reader.read_event_into(&mut buf) {
+            event.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, 3);
+
+        let mut reader = StreamReader::new(&compressed[..compressed.len() / 2]);
+        let error = loop {
+            match reader.read_event_into(&mut buf) {
+                Some(Ok(_)) => (),
+                Some(Err(error)) => break error,
+                None => panic!(),
+            }
+        };
+        assert!(matches!(error, ReadError::Io(_)));
+        assert!(reader.read_event_into(&mut buf).is_none());
+
+        let options = crate::reader::Options::default().disable_gzip_detection(true);
+        let mut reader = StreamReader::with_options(&compressed[..], options);
+        assert!(matches!(
+            reader.read_event_into(&mut buf),
+            Some(Err(ReadError::Decode(_)))
+        ));
+    }
+}
@@ -0,0 +1,332 @@
+//! A low-level tokenizer that splits a document into lexical tokens.
+//!
+//! Unlike [`Reader`], the [`Tokenizer`] never fails and never discards
+//! any input, concatenating the text of all tokens yields the original document. This
+//! makes it suitable for tools like syntax highlighters that have to deal with
+//! incomplete or malformed documents.
+//!
+//! # Stability
+//!
+//! The set of [`TokenKind`]s and the exact boundaries between tokens are not considered
+//! part of the stable API and may change in minor releases.
+
+use std::{collections::VecDeque, fmt::Debug, ops::Range};
+
+use crate::{
+    lut::is_whitespace,
+    reader::{EndEvent, Event, Options, Reader, StartEvent},
+};
+
+/// The kind of a [`Token`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TokenKind {
+    /// Character data outside of markup.
+    Text,
+    /// An entity or character reference like `&amp;` or `&#x20;` in character data.
+    EntityReference,
+    /// The `<` starting a start or empty element tag.
+    TagOpen,
+    /// The `</` starting an end tag.
+    EndTagOpen,
+    /// The `>` ending a tag.
+    TagClose,
+    /// The `/>` ending an empty element tag.
+    EmptyTagClose,
+    /// An element or attribute name, including its prefix.
+    Name,
+    /// The `=` between an attribute name and its value.
+    Equals,
+    /// A quoted attribute value, including the quotes.
+    AttributeValue,
+    /// Whitespace inside of a tag.
+    Whitespace,
+    /// A comment, including the `<!--` and `-->` delimiters.
+    Comment,
+    /// A cdata section, including the `<![CDATA[` and `]]>` delimiters.
+    CData,
+    /// A doctype declaration, including its internal subset.
+    Doctype,
+    /// A processing instruction or XML declaration, including the `<?` and `?>` delimiters.
+    ProcessingInstruction,
+    /// Anything that could not be classified.
+    Unknown,
+}
+
+/// A single token produced by a [`Tokenizer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    start: usize,
+}
+
+impl<'a> Token<'a> {
+    /// Returns the kind of this token.
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    /// Returns the text of this token.
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// Returns the span of this token in the tokenized document.
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.start + self.text.len()
+    }
+}
+
+/// An iterator over the [`Token`]s of a document.
+///
+/// Markup is recognized by a lenient [`Reader`], so tokens follow the same rules as
+/// events. Input that the reader skips over because it is malformed is yielded as
+/// [`TokenKind::Unknown`].
+#[derive(Clone)]
+pub struct Tokenizer<'a> {
+    text: &'a str,
+    reader: Reader<'a>,
+    /// The end of the last token that was split off an event.
+    current: usize,
+    /// Tokens of the last event that were not yielded yet.
+    pending: VecDeque<Token<'a>>,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Creates a new tokenizer over `text`.
+    pub fn new(text: &'a str) -> Self {
+        let options = Options::default()
+            .recover(true)
+            .allow_top_level_text(true)
+            .allow_unmatched_closing_tags(true)
+            .allow_unclosed_tags(true)
+            .allow_truncated_input(true)
+            .parse_pi_nodes(true)
+            .parse_declaration_node(true);
+
+        Self {
+            text,
+            reader: Reader::with_options(text, options),
+            current: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Splits the next `len` bytes off as a token of `kind`.
+    fn push(&mut self, kind: TokenKind, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        let start = self.current;
+        self.current += len;
+        self.pending.push_back(Token {
+            kind,
+            text: &self.text[start..self.current],
+            start,
+        });
+    }
+
+    fn push_event(&mut self, event: Event<'a>) {
+        let start = event.text().as_ptr().addr() - self.text.as_ptr().addr();
+        self.push(TokenKind::Unknown, start - self.current);
+
+        let kind = match event {
+            Event::Start(start) | Event::Empty(start) => return self.push_start_tag(start),
+            Event::End(end) => return self.push_end_tag(end),
+            Event::Text(_) => return self.push_text(event.text()),
+            Event::CData(_) => TokenKind::CData,
+            Event::Comment(_) => TokenKind::Comment,
+            Event::Doctype(_) => TokenKind::Doctype,
+            Event::PI(_) | Event::Decl(_) => TokenKind::ProcessingInstruction,
+        };
+        self.push(kind, event.text().len());
+    }
+
+    fn push_start_tag(&mut self, start: StartEvent<'a>) {
+        self.push(TokenKind::TagOpen, 1);
+        self.push(
+            TokenKind::Name,
+            prefixed_name_len(start.prefix(), start.name()),
+        );
+
+        for attribute in start.attributes() {
+            self.push(TokenKind::Whitespace, attribute.leading_whitespace().len());
+            self.push(TokenKind::Name, attribute.name().len());
+            if !attribute.has_value() {
+                continue;
+            }
+
+            let quotes = attribute.quote().as_char().map_or(0, |_| 2);
+            let value_len = attribute.raw_value().len() + quotes;
+            let separator =
+                &attribute.raw()[attribute.name().len()..attribute.raw().len() - value_len];
+            let equals = separator.find('=').unwrap_or(separator.len());
+            self.push(TokenKind::Whitespace, equals);
+            self.push(TokenKind::Equals, 1);
+            self.push(
+                TokenKind::Whitespace,
+                separator.len().saturating_sub(equals + 1),
+            );
+            self.push(TokenKind::AttributeValue, value_len);
+        }
+
+        self.push(TokenKind::Whitespace, start.trailing_whitespace().len());
+        if start.raw().ends_with("/>") {
+            self.push(TokenKind::EmptyTagClose, 2);
+        } else if start.raw().ends_with('>') {
+            self.push(TokenKind::TagClose, 1);
+        }
+    }
+
+    fn push_end_tag(&mut self, end: EndEvent<'a>) {
+        let name_len = prefixed_name_len(end.prefix(), end.name());
+        let text = Event::End(end).text();
+        self.push(TokenKind::EndTagOpen, 2);
+        self.push(TokenKind::Name, name_len);
+        let close = usize::from(text.ends_with('>'));
+        self.push(TokenKind::Whitespace, text.len() - 2 - name_len - close);
+        self.push(TokenKind::TagClose, close);
+    }
+
+    /// Splits character data into text and entity references.
+    fn push_text(&mut self, text: &'a str) {
+        let mut rest = text.as_bytes();
+        while !rest.is_empty() {
+            let reference_end = rest[1..]
+                .iter()
+                .position(|&b| b == b';' || b == b'&' || is_whitespace(b))
+                .map(|idx| idx + 1);
+
+            let (kind, len) = match reference_end {
+                Some(end) if rest[0] == b'&' && rest[end] == b';' && end > 1 => {
+                    (TokenKind::EntityReference, end + 1)
+                }
+                _ => (
+                    TokenKind::Text,
+                    memchr::memchr(b'&', &rest[1..]).map_or(rest.len(), |idx| idx + 1),
+                ),
+            };
+            self.push(kind, len);
+            rest = &rest[len..];
+        }
+    }
+}
+
+/// Returns the length of a prefixed name made up of `prefix` and `name`.
+fn prefixed_name_len(prefix: Option<&str>, name: &str) -> usize {
+    prefix.map_or(0, |prefix| prefix.len() + 1) + name.len()
+}
+
+impl Debug for Tokenizer<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tokenizer")
+            .field("current", &self.current)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && self.current < self.text.len() {
+            match self.reader.next() {
+                Some(Ok(event)) => self.push_event(event),
+                // The input the error was raised for is yielded as unknown tokens later.
+                Some(Err(_)) => (),
+                None => self.push(TokenKind::Unknown, self.text.len() - self.current),
+            }
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+impl std::iter::FusedIterator for Tokenizer<'_> {}
+
+#[cfg(test)]
+mod test {
+    use super::{TokenKind::*, Tokenizer};
+
+    #[test]
+    fn tokens() {
+        let code = concat!(
+            r#"<?xml version="1.0"?><!DOCTYPE a [<!ENTITY b "c">]>"#,
+            r#"<a:b x = 'y' z="&amp;"><!-- c --><![CDATA[<]]>1 &lt; 2 & 3</a:b ><e/><f"#
+        );
+        let tokens = Tokenizer::new(code)
+            .map(|token| (token.kind(), token.text()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                (ProcessingInstruction, r#"<?xml version="1.0"?>"#),
+                (Doctype, r#"<!DOCTYPE a [<!ENTITY b "c">]>"#),
+                (TagOpen, "<"),
+                (Name, "a:b"),
+                (Whitespace, " "),
+                (Name, "x"),
+                (Whitespace, " "),
+                (Equals, "="),
+                (Whitespace, " "),
+                (AttributeValue, "'y'"),
+                (Whitespace, " "),
+                (Name, "z"),
+                (Equals, "="),
+                (AttributeValue, r#""&amp;""#),
+                (TagClose, ">"),
+                (Comment, "<!-- c -->"),
+                (CData, "<![CDATA[<]]>"),
+                (Text, "1 "),
+                (EntityReference, "&lt;"),
+                (Text, " 2 "),
+                (Text, "& 3"),
+                (EndTagOpen, "</"),
+                (Name, "a:b"),
+                (Whitespace, " "),
+                (TagClose, ">"),
+                (TagOpen, "<"),
+                (Name, "e"),
+                (EmptyTagClose, "/>"),
+                (TagOpen, "<"),
+                (Name, "f"),
+            ]
+        );
+
+        assert_eq!(
+            Tokenizer::new(code)
+                .map(|token| token.text())
+                .collect::<String>(),
+            code
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let code = " <a b><!x><c\n/>&bad &;</d>";
+        let tokens = Tokenizer::new(code)
+            .map(|token| (token.kind(), token.text()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            [
+                (Text, " "),
+                (Unknown, "<a b><!x>"),
+                (TagOpen, "<"),
+                (Name, "c"),
+                (Whitespace, "\n"),
+                (EmptyTagClose, "/>"),
+                (Text, "&bad "),
+                (Text, "&;"),
+                (EndTagOpen, "</"),
+                (Name, "d"),
+                (TagClose, ">"),
+            ]
+        );
+        assert_eq!(tokens.iter().map(|token| token.1).collect::<String>(), code);
+    }
+}
@@ -1,15 +1,25 @@
 //! A simple XML writer.
 
 use std::{
+    borrow::Cow,
+    convert::Infallible,
     fmt::{Debug, Display},
     io::Write,
+    ops::{Deref, DerefMut},
 };
 
 use crate::{
-    escape::{comment_escape, content_escape},
-    lut::{is_invalid_attribute_name, is_invalid_name},
+    escape::{
+        attribute_value_escape, canonical_attribute_value_escape, canonical_content_escape,
+        comment_escape, content_escape, control_escape, non_ascii_escape,
+        normalize_attribute_value, normalize_line_endings, single_quoted_attribute_value_escape,
+        unescape, unescape_normalized,
+    },
+    lut::{is_invalid_attribute_name, is_invalid_name, is_ncname, is_whitespace},
+    namespace::XML_NAMESPACE,
     reader::{
-        self, AttributeEvent, AttributeQuote, CDataEvent, CommentEvent, DoctypeEvent, TextEvent,
+        self, AttributeEvent, AttributeQuote, CDataEvent, CommentEvent, DeclEvent, DoctypeEvent,
+        PIEvent, TextEvent,
     },
 };
 
@@ -19,13 +29,312 @@ use crate::{
 pub struct Options {
     /// Whether to ignore all calls to [`Writer::write_comment`] and [`Writer::write_raw_comment`]
     pub omit_comments: bool,
+    /// Whether control characters in text and attribute values passed to [`Writer::write_text`]
+    /// and [`Writer::write_attribute`] should be escaped as character references, as required
+    /// in XML 1.1 documents. Null bytes cannot be escaped and are rejected with
+    /// [`Error::InvalidValue`].
+    pub xml_version_1_1: bool,
+    /// Whether characters above `U+007F` in text and attribute values passed to
+    /// [`Writer::write_text`] and [`Writer::write_attribute`] should be escaped as character
+    /// references like `&#xE9;`, so that the output is pure ASCII for consumers that
+    /// mishandle UTF-8.
+    ///
+    /// Names, comments and processing instructions are still written as they are passed in.
+    pub escape_non_ascii: bool,
+    /// Whether element and attribute names should be checked against the `Name` production
+    /// of the XML specification, instead of only rejecting characters that would break the
+    /// markup.
+    ///
+    /// The prefix and the local part of prefixed names are checked separately.
+    pub validate_names: bool,
+    /// Whether [`Writer::write_end`] should fail if the end tag does not match the innermost
+    /// open element and [`Writer::finish`] should fail if any elements are still open.
+    pub check_end_tags: bool,
+    /// The string to indent markup with once per nesting level, every tag, comment and
+    /// other markup is put on its own line if set. It must only consist of whitespace.
+    ///
+    /// Elements containing text are written as is, without adding any whitespace inside
+    /// of them, and whitespace-only text passed to [`Writer::write_event`] is dropped.
+    pub indent: Option<String>,
+    /// The quote character used by [`Writer::write_attribute`] and other methods that
+    /// escape attribute values, double quotes by default.
+    ///
+    /// With [`AttributeQuote::None`] values are written unquoted, writing values that can't
+    /// be represented without quotes, like empty ones or ones ending with a `/`, will fail.
+    ///
+    /// **Warning:** Unquoted attribute values are not allowed in XML, documents written with
+    /// [`AttributeQuote::None`] are only accepted by lenient parsers like HTML ones or
+    /// [`Reader`](crate::Reader) with
+    /// [`allow_unquoted_attribute_values`](crate::reader::Options::allow_unquoted_attribute_values)
+    /// enabled.
+    pub attribute_quote: AttributeQuote,
+    /// How empty tags written by [`Writer::write_empty`] are closed.
+    pub empty_tag: EmptyTag,
+    /// The line break written before indented markup if [`Self::indent`] is set.
+    ///
+    /// Line breaks inside of text and other content are written as they are passed in.
+    pub newline: Newline,
+    /// Whether to write [Canonical XML 1.0](https://www.w3.org/TR/xml-c14n), so that
+    /// logically equivalent documents are written as identical bytes, for example to sign
+    /// or hash them.
+    ///
+    /// Namespace declarations are written first and dropped if the same binding is already
+    /// in scope, the other attributes are sorted by namespace URI and local name. Attribute
+    /// values are always double quoted, text and attribute values are escaped in canonical
+    /// form and cdata sections are written as text. Empty elements are expanded into a
+    /// start and an end tag, XML and document type declarations are dropped and
+    /// [`Self::indent`], [`Self::empty_tag`], [`Self::attribute_quote`] and
+    /// [`Self::escape_non_ascii`] are ignored. Comments are kept unless
+    /// [`Self::omit_comments`] is enabled.
+    ///
+    /// Content passed to [`Writer::write_raw_text`] and [`Writer::write_raw_comment`] is
+    /// still written as is and must already be in canonical form.
+    pub canonical: bool,
+}
+
+impl Options {
+    /// Applies [`control_escape`] and [`non_ascii_escape`] to already escaped `text` if
+    /// enabled.
+    ///
+    /// Fails with [`Error::InvalidValue`] if `text` contains a null byte in XML 1.1 mode.
+    fn escape_chars<'s>(&self, mut text: Cow<'s, str>) -> Result<Cow<'s, str>, Error> {
+        if self.xml_version_1_1 {
+            if let Cow::Owned(escaped) = control_escape(&text).map_err(|_| Error::InvalidValue)? {
+                text = Cow::Owned(escaped);
+            }
+        }
+
+        if self.escape_non_ascii {
+            if let Cow::Owned(escaped) = non_ascii_escape(&text) {
+                text = Cow::Owned(escaped);
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// Returns the function escaping text content.
+    fn text_escape(&self) -> fn(&str) -> Cow<'_, str> {
+        if self.canonical {
+            canonical_content_escape
+        } else {
+            content_escape
+        }
+    }
+
+    /// Returns the function escaping attribute values quoted with [`Self::attribute_quote`].
+    fn value_escape(&self) -> fn(&str) -> Cow<'_, str> {
+        match self.attribute_quote {
+            AttributeQuote::Single => single_quoted_attribute_value_escape,
+            AttributeQuote::Double | AttributeQuote::None => attribute_value_escape,
+        }
+    }
+}
+
+/// A style of closing empty tags for [`Options::empty_tag`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyTag {
+    /// A self-closing tag, `<a/>`.
+    #[default]
+    SelfClosing,
+    /// A self-closing tag with a space before the slash, `<a />`, as recommended for
+    /// compatibility with HTML parsers.
+    SelfClosingSpaced,
+    /// A start tag immediately followed by an end tag, `<a></a>`.
+    Expanded,
+}
+
+/// A line break style for [`Options::newline`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Newline {
+    /// A line feed, `\n`.
+    #[default]
+    Lf,
+    /// A carriage return followed by a line feed, `\r\n`.
+    CrLf,
+    /// No line breaks, which also disables indentation.
+    None,
+}
+
+impl Newline {
+    /// Returns the characters of this line break.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+            Newline::None => "",
+        }
+    }
 }
 
+/// The capacity the output buffer of a [`Writer`] is shrunk to after writing large content.
+const BUFFER_CAPACITY: usize = 8 * 1024;
+
 /// An XML writer.
+///
+/// Start tags are buffered until they are closed by writing some other content, so
+/// [`Writer::finish`] or [`Writer::flush`] has to be called before dropping the writer.
 pub struct Writer<W: Write> {
     writer: W,
+    /// Output that was not passed to `writer` yet, see [`Writer::commit`].
+    buffer: Vec<u8>,
     options: Options,
     depth_and_flags: u32,
+    /// What was written last, used for indentation.
+    last: Last,
+    /// The depth of the outermost element that contains text, inside which no indentation
+    /// is added.
+    mixed_depth: Option<u32>,
+    /// The prefixed names of all open elements, stored in a single buffer.
+    names: String,
+    name_starts: Vec<usize>,
+    /// Where the name of the empty tag being written starts in `names` if it is expanded.
+    empty_name_start: usize,
+    /// Present if [`Options::canonical`] is enabled.
+    canonical: Option<Canonical>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Last {
+    Nothing,
+    StartTag,
+    Markup,
+}
+
+/// The state of a [`Writer`] writing Canonical XML, see [`Options::canonical`].
+#[derive(Default)]
+struct Canonical {
+    /// The attributes of the start tag being written, they are sorted and written once the
+    /// tag is closed.
+    attributes: Vec<CanonicalAttribute>,
+    /// The namespace declarations that were written and the number of open elements they
+    /// are in scope for, the default namespace is stored with an empty prefix.
+    bindings: Vec<(String, String, usize)>,
+    /// Whether the document element was started, markup outside of it is put on its own
+    /// line.
+    after_root: bool,
+}
+
+struct CanonicalAttribute {
+    /// The prefix, `xmlns` for namespace declarations including the default one.
+    prefix: Option<String>,
+    name: String,
+    /// The unescaped value.
+    value: String,
+}
+
+impl Canonical {
+    fn push_attribute(&mut self, prefix: Option<&str>, name: &str, value: Cow<str>) {
+        let (prefix, name) = match prefix {
+            None if name == "xmlns" => (Some("xmlns"), ""),
+            _ => (prefix, name),
+        };
+
+        self.attributes.push(CanonicalAttribute {
+            prefix: prefix.map(str::to_owned),
+            name: name.to_owned(),
+            value: value.into_owned(),
+        });
+    }
+
+    /// Returns the namespace URI bound to `prefix` by a written namespace declaration.
+    fn lookup(&self, prefix: &str) -> Option<&str> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(bound, ..)| bound == prefix)
+            .map(|(_, uri, _)| uri.as_str())
+    }
+
+    /// Writes the attributes of a start tag in canonical order into `buffer`, the start tag
+    /// is the innermost of `depth` open elements.
+    fn write_attributes(&mut self, depth: usize, buffer: &mut Vec<u8>) {
+        let (mut namespaces, mut attributes): (Vec<_>, Vec<_>) = self
+            .attributes
+            .drain(..)
+            .partition(|attr| attr.prefix.as_deref() == Some("xmlns"));
+
+        namespaces.sort_by(|a, b| a.name.cmp(&b.name));
+        namespaces.retain(|attr| {
+            let superfluous = match self.lookup(&attr.name) {
+                Some(uri) => uri == attr.value,
+                None => attr.name.is_empty() && attr.value.is_empty(),
+            };
+            if !superfluous {
+                self.bindings
+                    .push((attr.name.clone(), attr.value.clone(), depth));
+            }
+            !superfluous
+        });
+
+        let mut keyed = attributes
+            .drain(..)
+            .map(|attr| {
+                let uri = match attr.prefix.as_deref() {
+                    None => "",
+                    Some("xml") => XML_NAMESPACE,
+                    Some(prefix) => self.lookup(prefix).unwrap_or_default(),
+                };
+                (uri.to_owned(), attr)
+            })
+            .collect::<Vec<_>>();
+        keyed.sort_by(|(a_uri, a), (b_uri, b)| (a_uri, &a.name).cmp(&(b_uri, &b.name)));
+
+        let attributes = namespaces.iter().chain(keyed.iter().map(|(_, attr)| attr));
+        for attr in attributes {
+            buffer.extend_from_slice(b" ");
+            match attr.prefix.as_deref() {
+                Some("xmlns") if attr.name.is_empty() => buffer.extend_from_slice(b"xmlns"),
+                Some(prefix) => {
+                    buffer.extend_from_slice(prefix.as_bytes());
+                    buffer.extend_from_slice(b":");
+                    buffer.extend_from_slice(attr.name.as_bytes());
+                }
+                None => buffer.extend_from_slice(attr.name.as_bytes()),
+            }
+            buffer.extend_from_slice(b"=\"");
+            buffer.extend_from_slice(canonical_attribute_value_escape(&attr.value).as_bytes());
+            buffer.extend_from_slice(b"\"");
+        }
+    }
+
+    /// Removes the namespace declarations that went out of scope after closing elements
+    /// until `depth` remain open.
+    fn pop(&mut self, depth: usize) {
+        while self
+            .bindings
+            .last()
+            .is_some_and(|&(.., bound)| bound > depth)
+        {
+            self.bindings.pop();
+        }
+    }
+}
+
+/// Checks that an escaped attribute `value` can be written with the `quote` character.
+fn check_attribute_value(quote: AttributeQuote, value: &str) -> Result<(), Error> {
+    let invalid = match quote.as_char() {
+        None => {
+            // A trailing slash would be read as part of `/>` closing an empty tag.
+            value.is_empty()
+                || value.ends_with('/')
+                || value
+                    .bytes()
+                    .any(|b| is_whitespace(b) || [b'\0', b'>', b'\'', b'"'].contains(&b))
+        }
+        Some(quote) => value.contains(['\0', quote]),
+    };
+
+    if invalid {
+        return Err(Error::InvalidAttributeValue);
+    }
+
+    Ok(())
+}
+
+/// Returns the error reported when a formatting trait implementation fails.
+fn formatter_error() -> Error {
+    Error::Io(std::io::Error::other("formatter error"))
 }
 
 /// An error that can occur while writing XML.
@@ -33,9 +342,11 @@ pub struct Writer<W: Write> {
 /// This is either caused by passing an incorrectly escaped string to
 /// a `write_raw_*` method, writing an attribute with an invalid name or outside of a start tag, or by an underlying I/O error.
 pub enum Error {
-    /// An invalid prefix was passed to [`Writer::write_start`].
+    /// An invalid prefix was passed to [`Writer::write_start`], [`Writer::write_empty`] or
+    /// [`Writer::write_end`].
     InvalidElementPrefix,
-    /// An invalid name was passed to [`Writer::write_start`].
+    /// An invalid name was passed to [`Writer::write_start`], [`Writer::write_empty`] or
+    /// [`Writer::write_end`].
     InvalidElementName,
     /// An invalid name was passed to [`Writer::write_attribute`] or [`Writer::write_raw_attribute`].
     InvalidAttributeName,
@@ -43,11 +354,35 @@ pub enum Error {
     InvalidAttributeValue,
     /// Either [`Writer::write_attribute`] or [`Writer::write_raw_attribute`] was called outside a start tag context.
     AttributeOutsideTag,
+    /// [`Writer::write_end`] or [`Writer::write_end_auto`] was called while no element was
+    /// open.
+    NoOpenElement,
+    /// An end tag not matching the innermost open element was passed to [`Writer::write_end`]
+    /// while [`Options::check_end_tags`] is enabled.
+    MismatchedEndTag,
+    /// [`Writer::finish`] was called with elements still open while
+    /// [`Options::check_end_tags`] is enabled.
+    UnclosedElement,
+    /// An invalid or reserved target was passed to [`Writer::write_pi`].
+    InvalidPITarget,
+    /// A string containing `?>` was passed to [`Writer::write_pi`].
+    InvalidPIContent,
+    /// An invalid version or encoding was passed to [`Writer::write_declaration`].
+    InvalidDeclaration,
+    /// [`Writer::write_declaration`] was called after something else was written.
+    DeclarationNotFirst,
+    /// An invalid or reserved binding was passed to
+    /// [`NsWriter::declare`](crate::namespace::NsWriter::declare), or an element in no
+    /// namespace was written while declaring a default namespace.
+    InvalidNamespace,
+    /// An invalid name or identifier was passed to [`Writer::write_doctype`].
+    InvalidDoctype,
     /// Improperly escaped content was passed to [`Writer::write_raw_comment`] or [`Writer::write_raw_text`].
     ImproperlyEscaped,
     /// A string containing `]]>` was passed to [`Writer::write_cdata`].
     InvalidCData,
-    /// A string containing a null byte was passed to [`Writer::write_raw_comment`] or [`Writer::write_raw_text`].
+    /// A string containing a null byte was passed to [`Writer::write_raw_comment`] or [`Writer::write_raw_text`],
+    /// or to a method escaping text or attribute values while [`Options::xml_version_1_1`] is enabled.
     InvalidValue,
     /// An I/O error occured.
     Io(std::io::Error),
@@ -76,6 +411,15 @@ impl Display for Error {
             Error::InvalidAttributeName => "invalid attribute name",
             Error::InvalidAttributeValue => "invalid attribute value",
             Error::AttributeOutsideTag => "attributes are only allowed inside tags",
+            Error::NoOpenElement => "no open element to close",
+            Error::MismatchedEndTag => "end tag does not match the open element",
+            Error::UnclosedElement => "elements are still open",
+            Error::InvalidPITarget => "invalid processing instruction target",
+            Error::InvalidPIContent => "processing instruction content cannot contain `?>`",
+            Error::InvalidDeclaration => "invalid xml declaration",
+            Error::DeclarationNotFirst => "the xml declaration must come before anything else",
+            Error::InvalidNamespace => "invalid namespace declaration",
+            Error::InvalidDoctype => "invalid doctype declaration",
             Error::ImproperlyEscaped => "improperly escaped content",
             Error::InvalidCData => "cdata content cannot contain `]]>`",
             Error::InvalidValue => "value contains null byte",
@@ -98,31 +442,164 @@ impl<W: Write> Writer<W> {
     }
 
     /// Creates a new [`Writer`] that will write into `writer` with the specified options.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`Options::indent`] contains anything other than whitespace.
     #[inline]
-    pub fn with_options(writer: W, options: Options) -> Self {
+    pub fn with_options(writer: W, mut options: Options) -> Self {
+        assert!(
+            options
+                .indent
+                .as_ref()
+                .is_none_or(|indent| indent.bytes().all(is_whitespace)),
+            "Options::indent must only contain whitespace"
+        );
+        if options.canonical {
+            options.indent = None;
+            options.empty_tag = EmptyTag::Expanded;
+            options.attribute_quote = AttributeQuote::Double;
+            options.escape_non_ascii = false;
+        }
+        let canonical = options.canonical.then(Canonical::default);
+
         Self {
             writer,
+            buffer: Vec::new(),
             options,
             depth_and_flags: 0,
+            last: Last::Nothing,
+            mixed_depth: None,
+            names: String::new(),
+            name_starts: Vec::new(),
+            empty_name_start: 0,
+            canonical,
+        }
+    }
+
+    /// Returns whether `name` contains a byte rejected by `is_invalid` or is not a valid XML
+    /// name if [`Options::validate_names`] is enabled.
+    pub(crate) fn is_invalid(&self, name: &str, is_invalid: fn(u8) -> bool) -> bool {
+        name.bytes().any(is_invalid) || (self.options.validate_names && !is_ncname(name))
+    }
+
+    fn depth(&self) -> u32 {
+        self.depth_and_flags >> 2
+    }
+
+    /// Starts a new line indented to the current depth before markup if enabled with
+    /// [`Options::indent`].
+    fn write_indent(&mut self, end_tag: bool) {
+        let Some(indent) = &self.options.indent else {
+            return;
+        };
+        if self.options.newline == Newline::None
+            || self.mixed_depth.is_some()
+            || self.last == Last::Nothing
+            || (end_tag && self.last == Last::StartTag)
+        {
+            return;
         }
+
+        self.buffer
+            .extend_from_slice(self.options.newline.as_str().as_bytes());
+        for _ in 0..self.depth() - u32::from(end_tag) {
+            self.buffer.extend_from_slice(indent.as_bytes());
+        }
+    }
+
+    /// Records that text was written at the current depth.
+    fn mark_text(&mut self) {
+        self.mixed_depth.get_or_insert(self.depth());
     }
 
-    fn in_empty_tag(&self) -> bool {
+    /// Returns the number of elements that were opened and not closed yet.
+    pub(crate) fn open_elements(&self) -> usize {
+        self.name_starts.len()
+    }
+
+    pub(crate) fn in_empty_tag(&self) -> bool {
         self.depth_and_flags & 0b10 > 0
     }
 
-    fn ensure_tag_closed(&mut self) -> Result<(), std::io::Error> {
+    fn ensure_tag_closed(&mut self) {
         if self.depth_and_flags & 1 > 0 {
+            let depth = self.name_starts.len() + usize::from(self.in_empty_tag());
+            if let Some(canonical) = &mut self.canonical {
+                canonical.write_attributes(depth, &mut self.buffer);
+                canonical.pop(self.name_starts.len());
+            }
+
             if self.in_empty_tag() {
-                self.writer.write_all(b"/>")?;
-                self.depth_and_flags += 0b001;
+                match self.options.empty_tag {
+                    EmptyTag::SelfClosing => self.buffer.extend_from_slice(b"/>"),
+                    EmptyTag::SelfClosingSpaced => self.buffer.extend_from_slice(b" />"),
+                    EmptyTag::Expanded => {
+                        self.buffer.extend_from_slice(b"></");
+                        self.buffer
+                            .extend_from_slice(&self.names.as_bytes()[self.empty_name_start..]);
+                        self.buffer.extend_from_slice(b">");
+                        self.names.truncate(self.empty_name_start);
+                    }
+                }
+                self.depth_and_flags -= 0b011;
             } else {
-                self.writer.write_all(b">")?;
+                self.buffer.extend_from_slice(b">");
                 self.depth_and_flags += 0b011;
             }
         }
+    }
 
-        Ok(())
+    /// Writes the buffered output into the underlying writer, unless a start tag is still
+    /// being written.
+    ///
+    /// Output is buffered so that every call issues at most one write to the underlying
+    /// writer, and start tags are written together with all of their attributes.
+    fn commit(&mut self) -> std::io::Result<()> {
+        if self.depth_and_flags & 1 > 0 || self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let result = self.writer.write_all(&self.buffer);
+        self.buffer.clear();
+        self.buffer.shrink_to(BUFFER_CAPACITY);
+        result
+    }
+
+    /// Prepares for writing markup like a comment, which is put on its own line outside of
+    /// the document element in Canonical XML.
+    fn start_markup(&mut self) {
+        self.ensure_tag_closed();
+        self.write_indent(false);
+        self.last = Last::Markup;
+
+        if self
+            .canonical
+            .as_ref()
+            .is_some_and(|canonical| canonical.after_root && self.depth() == 0)
+        {
+            self.buffer.extend_from_slice(b"\n");
+        }
+    }
+
+    /// Finishes writing markup started with [`Self::start_markup`].
+    fn end_markup(&mut self) -> std::io::Result<()> {
+        if self
+            .canonical
+            .as_ref()
+            .is_some_and(|canonical| !canonical.after_root && self.depth() == 0)
+        {
+            self.buffer.extend_from_slice(b"\n");
+        }
+
+        self.commit()
+    }
+
+    /// Records that an element is being started in Canonical XML.
+    fn start_canonical_element(&mut self) {
+        if let Some(canonical) = &mut self.canonical {
+            canonical.after_root = true;
+        }
     }
 
     /// Writes a start tag with the specified `prefix` and `name` into the writer.
@@ -131,24 +608,33 @@ impl<W: Write> Writer<W> {
     ///
     /// Returns an error if the prefix or name is invalid or an underlying I/O error occurs.
     pub fn write_start(&mut self, prefix: Option<&str>, name: &str) -> Result<(), Error> {
-        if prefix.is_some_and(|pfx| pfx.bytes().any(is_invalid_name)) {
+        if prefix.is_some_and(|pfx| self.is_invalid(pfx, is_invalid_name)) {
             return Err(Error::InvalidElementPrefix);
         }
 
-        if name.bytes().any(is_invalid_name) {
+        if self.is_invalid(name, is_invalid_name) {
             return Err(Error::InvalidElementName);
         }
 
-        self.ensure_tag_closed()?;
+        self.ensure_tag_closed();
+        self.write_indent(false);
+        self.start_canonical_element();
+        self.last = Last::StartTag;
 
         self.depth_and_flags += 0b1;
-        // TODO: write_all_vectored
-        self.writer.write_all(b"<")?;
+        self.buffer.extend_from_slice(b"<");
         if let Some(prefix) = prefix {
-            self.writer.write_all(prefix.as_bytes())?;
-            self.writer.write_all(b":")?;
+            self.buffer.extend_from_slice(prefix.as_bytes());
+            self.buffer.extend_from_slice(b":");
         }
-        self.writer.write_all(name.as_bytes())?;
+        self.buffer.extend_from_slice(name.as_bytes());
+
+        self.name_starts.push(self.names.len());
+        if let Some(prefix) = prefix {
+            self.names.push_str(prefix);
+            self.names.push(':');
+        }
+        self.names.push_str(name);
 
         Ok(())
     }
@@ -159,20 +645,35 @@ impl<W: Write> Writer<W> {
     ///
     /// Returns an error if the prefix or name is invalid or an underlying I/O error occurs.
     pub fn write_empty(&mut self, prefix: Option<&str>, name: &str) -> Result<(), Error> {
-        if name.bytes().any(is_invalid_name) {
+        if prefix.is_some_and(|pfx| self.is_invalid(pfx, is_invalid_name)) {
+            return Err(Error::InvalidElementPrefix);
+        }
+
+        if self.is_invalid(name, is_invalid_name) {
             return Err(Error::InvalidElementName);
         }
 
-        self.ensure_tag_closed()?;
+        self.ensure_tag_closed();
+        self.write_indent(false);
+        self.start_canonical_element();
+        self.last = Last::Markup;
 
         self.depth_and_flags += 0b11;
-        // TODO: write_all_vectored
-        self.writer.write_all(b"<")?;
+        self.buffer.extend_from_slice(b"<");
         if let Some(prefix) = prefix {
-            self.writer.write_all(prefix.as_bytes())?;
-            self.writer.write_all(b":")?;
+            self.buffer.extend_from_slice(prefix.as_bytes());
+            self.buffer.extend_from_slice(b":");
+        }
+        self.buffer.extend_from_slice(name.as_bytes());
+
+        if self.options.empty_tag == EmptyTag::Expanded {
+            self.empty_name_start = self.names.len();
+            if let Some(prefix) = prefix {
+                self.names.push_str(prefix);
+                self.names.push(':');
+            }
+            self.names.push_str(name);
         }
-        self.writer.write_all(name.as_bytes())?;
 
         Ok(())
     }
@@ -193,32 +694,58 @@ impl<W: Write> Writer<W> {
         quote: AttributeQuote,
         value: &str,
     ) -> Result<(), Error> {
+        self.write_raw_prefixed_attribute(None, name, quote, value)
+    }
+
+    /// Checks that an attribute with the specified `prefix` and `name` can be written.
+    pub(crate) fn check_attribute(&self, prefix: Option<&str>, name: &str) -> Result<(), Error> {
         if self.depth_and_flags & 1 == 0 {
             return Err(Error::AttributeOutsideTag);
         }
 
-        if name.bytes().any(is_invalid_attribute_name) {
+        self.check_attribute_name(prefix, name)
+    }
+
+    fn check_attribute_name(&self, prefix: Option<&str>, name: &str) -> Result<(), Error> {
+        if prefix.is_some_and(|pfx| self.is_invalid(pfx, is_invalid_attribute_name))
+            || self.is_invalid(name, is_invalid_attribute_name)
+        {
             return Err(Error::InvalidAttributeName);
         }
 
-        let quote = quote as u8;
-        if name.bytes().any(|b| [b'\0', quote].contains(&b)) {
-            return Err(Error::InvalidAttributeValue);
+        Ok(())
+    }
+
+    fn write_raw_prefixed_attribute(
+        &mut self,
+        prefix: Option<&str>,
+        name: &str,
+        quote: AttributeQuote,
+        value: &str,
+    ) -> Result<(), Error> {
+        self.check_attribute(prefix, name)?;
+        check_attribute_value(quote, value)?;
+
+        if let Some(canonical) = &mut self.canonical {
+            canonical.push_attribute(prefix, name, unescape(value));
+            return Ok(());
         }
 
-        self.writer.write_all(b" ")?;
-        self.writer.write_all(name.as_bytes())?;
-        self.writer.write_all(b"=")?;
-        self.writer.write_all(&[quote])?;
-        self.writer.write_all(value.as_bytes())?;
-        self.writer.write_all(&[quote])?;
+        self.buffer.extend_from_slice(b" ");
+        if let Some(prefix) = prefix {
+            self.buffer.extend_from_slice(prefix.as_bytes());
+            self.buffer.extend_from_slice(b":");
+        }
+        self.buffer.extend_from_slice(name.as_bytes());
+        self.buffer.extend_from_slice(b"=");
+        self.write_quoted(quote, value)?;
 
         Ok(())
     }
 
-    /// Writes an attribute with the specified `prefix` and `name` into the writer.
+    /// Writes an attribute with the specified `name` into the writer.
     ///
-    /// The attribute will use the double quote as the quote character.
+    /// The attribute will use the quote character set in [`Options::attribute_quote`].
     ///
     /// Must only be called in the context of a start tag, i.e. after a successful [`Self::write_start`], [`Self::write_empty`], [`Self::write_raw_attribute`], or [`Self::write_attribute`].
     ///
@@ -226,44 +753,246 @@ impl<W: Write> Writer<W> {
     ///
     /// Returns an error if the name is invalid or an underlying I/O error occurs.
     pub fn write_attribute(&mut self, name: &str, value: &str) -> Result<(), Error> {
-        let escaped = content_escape(value);
-        self.write_raw_attribute(name, AttributeQuote::Double, &escaped)
+        self.write_prefixed_attribute(None, name, value)
     }
 
-    /// Writes an end tag with the specified `prefix` and `name` into the writer.
+    /// Writes an attribute with the specified `prefix` and `name` into the writer, like
+    /// [`Self::write_attribute`].
     ///
     /// # Errors
     ///
     /// Returns an error if the prefix or name is invalid or an underlying I/O error occurs.
+    pub fn write_prefixed_attribute(
+        &mut self,
+        prefix: Option<&str>,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        let escaped = self
+            .options
+            .escape_chars(self.options.value_escape()(value))?;
+        self.write_raw_prefixed_attribute(prefix, name, self.options.attribute_quote, &escaped)
+    }
+
+    /// Writes an attribute with the specified `name` and the integer `value` into the writer.
+    ///
+    /// This is only available with the `itoa` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is invalid or an underlying I/O error occurs.
+    #[cfg(feature = "itoa")]
+    pub fn write_attribute_int(
+        &mut self,
+        name: &str,
+        value: impl itoa::Integer,
+    ) -> Result<(), Error> {
+        self.write_raw_attribute(
+            name,
+            self.options.attribute_quote,
+            itoa::Buffer::new().format(value),
+        )
+    }
+
+    /// Writes an attribute with the specified `name` and the floating point `value` into the
+    /// writer, formatted like with [`Self::write_text_float`].
+    ///
+    /// This is only available with the `ryu` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is invalid or an underlying I/O error occurs.
+    #[cfg(feature = "ryu")]
+    pub fn write_attribute_float(
+        &mut self,
+        name: &str,
+        value: impl ryu::Float,
+    ) -> Result<(), Error> {
+        self.write_raw_attribute(
+            name,
+            self.options.attribute_quote,
+            ryu::Buffer::new().format(value),
+        )
+    }
+
+    /// Writes an attribute with the specified `name` and a value formatted from `args` into
+    /// the writer, like [`Self::write_attribute`] but without formatting the value into a
+    /// temporary string first.
+    ///
+    /// # Notes
+    ///
+    /// Unlike [`Self::write_attribute`] this function does not check for null bytes in the
+    /// value, unless [`Options::attribute_quote`] is [`AttributeQuote::None`] in which
+    /// case the value is formatted into a temporary string to validate it. The value is
+    /// also formatted into a temporary string if [`Options::canonical`] is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::Writer;
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.write_empty(None, "point").unwrap();
+    /// writer.write_attribute_fmt("at", format_args!("{}<{}", 1, 2)).unwrap();
+    /// assert_eq!(writer.finish().unwrap(), br#"<point at="1&lt;2"/>"#);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is invalid, a formatting trait implementation returned
+    /// an error or an underlying I/O error occurs.
+    pub fn write_attribute_fmt(
+        &mut self,
+        name: &str,
+        args: std::fmt::Arguments,
+    ) -> Result<(), Error> {
+        if let Some(value) = args.as_str() {
+            return self.write_attribute(name, value);
+        }
+
+        let quote = match self.options.attribute_quote.as_char() {
+            Some(quote) if self.canonical.is_none() => quote as u8,
+            _ => {
+                let mut value = String::new();
+                std::fmt::write(&mut value, args).map_err(|_| formatter_error())?;
+                return self.write_attribute(name, &value);
+            }
+        };
+
+        self.check_attribute(None, name)?;
+
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(b" ");
+        self.buffer.extend_from_slice(name.as_bytes());
+        self.buffer.extend_from_slice(&[b'=', quote]);
+        if let Err(error) = self.write_escaped_fmt(self.options.value_escape(), args) {
+            // Don't leave a partially written attribute behind.
+            self.buffer.truncate(start);
+            return Err(error);
+        }
+        self.buffer.push(quote);
+
+        Ok(())
+    }
+
+    /// Writes an end tag with the specified `prefix` and `name` into the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the prefix or name is invalid, no element is open, it does not
+    /// match the innermost open element while [`Options::check_end_tags`] is enabled or an
+    /// underlying I/O error occurs.
     pub fn write_end(&mut self, prefix: Option<&str>, name: &str) -> Result<(), Error> {
-        if prefix.is_some_and(|pfx| pfx.bytes().any(is_invalid_name)) {
+        if prefix.is_some_and(|pfx| self.is_invalid(pfx, is_invalid_name)) {
             return Err(Error::InvalidElementPrefix);
         }
 
-        if name.bytes().any(is_invalid_name) {
+        if self.is_invalid(name, is_invalid_name) {
             return Err(Error::InvalidElementName);
         }
 
-        self.ensure_tag_closed()?;
+        let Some(&start) = self.name_starts.last() else {
+            return Err(Error::NoOpenElement);
+        };
 
-        // TODO: write_all_vectored
-        self.writer.write_all(b"</")?;
+        if self.options.check_end_tags {
+            // The name of a pending expanded empty tag is not part of the open element's name.
+            let end = if self.in_empty_tag() && self.options.empty_tag == EmptyTag::Expanded {
+                self.empty_name_start
+            } else {
+                self.names.len()
+            };
+            let open = &self.names[start..end];
+            let matches = match prefix {
+                Some(prefix) => {
+                    open.strip_prefix(prefix)
+                        .and_then(|rest| rest.strip_prefix(':'))
+                        == Some(name)
+                }
+                None => open == name,
+            };
+            if !matches {
+                return Err(Error::MismatchedEndTag);
+            }
+        }
+
+        self.ensure_tag_closed();
+        self.write_indent(true);
+        self.last = Last::Markup;
+
+        self.buffer.extend_from_slice(b"</");
         if let Some(prefix) = prefix {
-            self.writer.write_all(prefix.as_bytes())?;
-            self.writer.write_all(b":")?;
+            self.buffer.extend_from_slice(prefix.as_bytes());
+            self.buffer.extend_from_slice(b":");
         }
-        self.writer.write_all(name.as_bytes())?;
-        self.writer.write_all(b">")?;
+        self.buffer.extend_from_slice(name.as_bytes());
+        self.buffer.extend_from_slice(b">");
 
-        self.depth_and_flags -= 0b100;
+        self.pop_element();
+        self.commit()?;
+        Ok(())
+    }
 
+    /// Writes an end tag for the most recently opened element that was not closed yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::Writer;
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.write_start(Some("x"), "a").unwrap();
+    /// writer.write_start(None, "b").unwrap();
+    /// writer.write_end_auto().unwrap();
+    /// writer.write_end_auto().unwrap();
+    /// assert!(writer.write_end_auto().is_err());
+    /// assert_eq!(writer.finish().unwrap(), b"<x:a><b></b></x:a>");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no element is open or an underlying I/O error occurs.
+    pub fn write_end_auto(&mut self) -> Result<(), Error> {
+        let Some(&start) = self.name_starts.last() else {
+            return Err(Error::NoOpenElement);
+        };
+
+        self.ensure_tag_closed();
+        self.write_indent(true);
+        self.last = Last::Markup;
+
+        self.buffer.extend_from_slice(b"</");
+        self.buffer
+            .extend_from_slice(&self.names.as_bytes()[start..]);
+        self.buffer.extend_from_slice(b">");
+
+        self.pop_element();
+        self.commit()?;
         Ok(())
     }
 
+    /// Records that the innermost open element was closed.
+    fn pop_element(&mut self) {
+        self.depth_and_flags -= 0b100;
+        if self.mixed_depth.is_some_and(|depth| depth > self.depth()) {
+            self.mixed_depth = None;
+        }
+        if let Some(start) = self.name_starts.pop() {
+            self.names.truncate(start);
+        }
+        if let Some(canonical) = &mut self.canonical {
+            canonical.pop(self.name_starts.len());
+        }
+    }
+
     fn write_raw_text_unchecked(&mut self, text: &str) -> std::io::Result<()> {
-        self.ensure_tag_closed()?;
+        self.ensure_tag_closed();
+        // Canonical XML drops whitespace outside of the document element.
+        if self.canonical.is_some() && self.depth() == 0 && text.bytes().all(is_whitespace) {
+            return self.commit();
+        }
+        self.mark_text();
 
-        self.writer.write_all(text.as_bytes())
+        self.buffer.extend_from_slice(text.as_bytes());
+        self.commit()
     }
 
     /// Writes text content into the writer.
@@ -287,22 +1016,135 @@ impl<W: Write> Writer<W> {
     ///
     /// # Notes
     ///
-    /// Currently this function does not check for null bytes in the string. This may change in a future release.
+    /// Currently this function only checks for null bytes in the string if
+    /// [`Options::xml_version_1_1`] is enabled. This may change in a future release.
     ///
     /// # Errors
     ///
-    /// Returns an error if an underlying I/O error occurs.
+    /// Returns an error if `content` contains a null byte while [`Options::xml_version_1_1`]
+    /// is enabled or an underlying I/O error occurs.
     pub fn write_text(&mut self, content: &str) -> Result<(), Error> {
-        let escaped = content_escape(content);
+        let escaped = self
+            .options
+            .escape_chars(self.options.text_escape()(content))?;
         self.write_raw_text_unchecked(&escaped).map_err(Into::into)
     }
 
+    /// Writes text content formatted from `args` into the writer, like [`Self::write_text`]
+    /// but without formatting it into a temporary string first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::Writer;
+    /// let mut writer = Writer::new(Vec::new());
+    /// let (count, name) = (3, "<b>");
+    /// writer
+    ///     .element(None, "a")
+    ///     .content(|writer| writer.write_text_fmt(format_args!("{count} x {name}")))
+    ///     .unwrap();
+    /// assert_eq!(writer.finish().unwrap(), b"<a>3 x &lt;b></a>");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a formatting trait implementation returned an error or an
+    /// underlying I/O error occurs.
+    pub fn write_text_fmt(&mut self, args: std::fmt::Arguments) -> Result<(), Error> {
+        if let Some(content) = args.as_str() {
+            return self.write_text(content);
+        } else if self.canonical.is_some() {
+            return self.write_text(&args.to_string());
+        }
+
+        self.ensure_tag_closed();
+        self.mark_text();
+
+        let start = self.buffer.len();
+        if let Err(error) = self.write_escaped_fmt(content_escape, args) {
+            self.buffer.truncate(start);
+            return Err(error);
+        }
+        self.commit()?;
+        Ok(())
+    }
+
+    /// Writes the integer `value` as text content into the writer.
+    ///
+    /// This is only available with the `itoa` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an underlying I/O error occurs.
+    #[cfg(feature = "itoa")]
+    pub fn write_text_int(&mut self, value: impl itoa::Integer) -> Result<(), Error> {
+        self.write_raw_text_unchecked(itoa::Buffer::new().format(value))
+            .map_err(Into::into)
+    }
+
+    /// Writes the floating point `value` as text content into the writer, in the shortest
+    /// form that parses back to the same value.
+    ///
+    /// Non-finite values are written as `NaN`, `inf` and `-inf`.
+    ///
+    /// This is only available with the `ryu` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an underlying I/O error occurs.
+    #[cfg(feature = "ryu")]
+    pub fn write_text_float(&mut self, value: impl ryu::Float) -> Result<(), Error> {
+        self.write_raw_text_unchecked(ryu::Buffer::new().format(value))
+            .map_err(Into::into)
+    }
+
+    /// Writes `args` into the underlying writer, escaping the formatted text with `escape`
+    /// as it is produced.
+    fn write_escaped_fmt(
+        &mut self,
+        escape: fn(&str) -> Cow<'_, str>,
+        args: std::fmt::Arguments,
+    ) -> Result<(), Error> {
+        struct Escaper<'w> {
+            buffer: &'w mut Vec<u8>,
+            options: &'w Options,
+            escape: fn(&str) -> Cow<'_, str>,
+            error: Option<Error>,
+        }
+
+        impl std::fmt::Write for Escaper<'_> {
+            fn write_str(&mut self, text: &str) -> std::fmt::Result {
+                match self.options.escape_chars((self.escape)(text)) {
+                    Ok(escaped) => {
+                        self.buffer.extend_from_slice(escaped.as_bytes());
+                        Ok(())
+                    }
+                    Err(error) => {
+                        self.error = Some(error);
+                        Err(std::fmt::Error)
+                    }
+                }
+            }
+        }
+
+        let mut escaper = Escaper {
+            buffer: &mut self.buffer,
+            options: &self.options,
+            escape,
+            error: None,
+        };
+        std::fmt::write(&mut escaper, args)
+            .map_err(|_| escaper.error.take().unwrap_or_else(formatter_error))
+    }
+
     fn write_cdata_unchecked(&mut self, text: &str) -> std::io::Result<()> {
-        self.ensure_tag_closed()?;
+        self.ensure_tag_closed();
+        self.mark_text();
 
-        self.writer.write_all(b"<![CDATA[")?;
-        self.writer.write_all(text.as_bytes())?;
-        self.writer.write_all(b"]]>")
+        self.buffer.extend_from_slice(b"<![CDATA[");
+        self.buffer.extend_from_slice(text.as_bytes());
+        self.buffer.extend_from_slice(b"]]>");
+        self.commit()
     }
 
     /// Writes cdata into the writer.
@@ -319,17 +1161,20 @@ impl<W: Write> Writer<W> {
             return Err(Error::InvalidCData);
         }
 
+        if self.canonical.is_some() {
+            return self.write_text(text);
+        }
+
         self.write_cdata_unchecked(text).map_err(Into::into)
     }
 
     fn write_raw_comment_unchecked(&mut self, text: &str) -> std::io::Result<()> {
-        self.ensure_tag_closed()?;
+        self.start_markup();
 
-        self.writer.write_all(b"<!--")?;
-        self.writer.write_all(text.as_bytes())?;
-        self.writer.write_all(b"-->")?;
-
-        Ok(())
+        self.buffer.extend_from_slice(b"<!--");
+        self.buffer.extend_from_slice(text.as_bytes());
+        self.buffer.extend_from_slice(b"-->");
+        self.end_markup()
     }
 
     /// Writes a comment into the writer.
@@ -371,70 +1216,423 @@ impl<W: Write> Writer<W> {
         Ok(())
     }
 
-    /// Writes a comment into the writer.
-    ///
-    /// # Notes
+    /// Writes an XML declaration like `<?xml version="1.0" encoding="UTF-8"?>` into the
+    /// writer, `standalone` is written as `yes` or `no` if present.
     ///
-    /// Currently this function does not check for null bytes in the string. This may change in a future release.
+    /// Declaring version `1.1` enables [`Options::xml_version_1_1`].
     ///
     /// # Errors
     ///
-    /// Returns an error if an underlying I/O error occurs.
-    pub fn write_attribute_event(&mut self, attr: &AttributeEvent) -> Result<(), Error> {
-        if self.depth_and_flags & 1 == 0 {
-            return Err(Error::AttributeOutsideTag);
+    /// Returns an error if anything was written before, the version or encoding is invalid
+    /// or an underlying I/O error occurs.
+    pub fn write_declaration(
+        &mut self,
+        version: &str,
+        encoding: Option<&str>,
+        standalone: Option<bool>,
+    ) -> Result<(), Error> {
+        if self.last != Last::Nothing || self.mixed_depth.is_some() {
+            return Err(Error::DeclarationNotFirst);
+        }
+
+        let valid_version = version
+            .strip_prefix("1.")
+            .is_some_and(|minor| !minor.is_empty() && minor.bytes().all(|b| b.is_ascii_digit()));
+        let valid_encoding = |encoding: &str| {
+            encoding
+                .bytes()
+                .next()
+                .is_some_and(|b| b.is_ascii_alphabetic())
+                && encoding
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"._-".contains(&b))
+        };
+        if !valid_version || encoding.is_some_and(|encoding| !valid_encoding(encoding)) {
+            return Err(Error::InvalidDeclaration);
+        } else if self.options.canonical {
+            return Ok(());
         }
 
-        self.writer.write_all(b" ")?;
-        self.writer.write_all(attr.name().as_bytes())?;
-        self.writer.write_all(b"=")?;
-        self.writer.write_all(&[attr.quote() as u8])?;
-        self.writer.write_all(attr.raw_value().as_bytes())?;
-        self.writer.write_all(&[attr.quote() as u8])?;
+        self.last = Last::Markup;
+        if version == "1.1" {
+            self.options.xml_version_1_1 = true;
+        }
+
+        self.buffer.extend_from_slice(b"<?xml version=\"");
+        self.buffer.extend_from_slice(version.as_bytes());
+        if let Some(encoding) = encoding {
+            self.buffer.extend_from_slice(b"\" encoding=\"");
+            self.buffer.extend_from_slice(encoding.as_bytes());
+        }
+        if let Some(standalone) = standalone {
+            self.buffer.extend_from_slice(b"\" standalone=\"");
+            self.buffer
+                .extend_from_slice(if standalone { b"yes" } else { b"no" });
+        }
+        self.buffer.extend_from_slice(b"\"?>");
+        self.commit()?;
+
+        Ok(())
+    }
+
+    /// Writes a document type declaration like
+    /// `<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "xhtml1-strict.dtd">` into
+    /// the writer.
+    ///
+    /// The system identifier is quoted with `'` if it contains a `"`, a public identifier
+    /// must be accompanied by a system identifier.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is invalid, the public identifier is given without a
+    /// system identifier or contains characters not allowed in public identifiers, the
+    /// system identifier contains both kinds of quotes or an underlying I/O error occurs.
+    pub fn write_doctype(
+        &mut self,
+        name: &str,
+        public_id: Option<&str>,
+        system_id: Option<&str>,
+    ) -> Result<(), Error> {
+        if name.is_empty()
+            || name
+                .split(':')
+                .any(|part| self.is_invalid(part, is_invalid_name))
+        {
+            return Err(Error::InvalidDoctype);
+        }
+
+        let is_pubid_char =
+            |b: u8| b.is_ascii_alphanumeric() || b" \r\n-'()+,./:=?;!*#@$_%".contains(&b);
+        if public_id.is_some_and(|id| system_id.is_none() || !id.bytes().all(is_pubid_char)) {
+            return Err(Error::InvalidDoctype);
+        }
+
+        let system_quote = match system_id {
+            Some(id) if id.contains('"') => {
+                if id.contains('\'') {
+                    return Err(Error::InvalidDoctype);
+                }
+                b"'"
+            }
+            _ => b"\"",
+        };
+
+        if self.options.canonical {
+            return Ok(());
+        }
+
+        self.ensure_tag_closed();
+        self.write_indent(false);
+        self.last = Last::Markup;
+
+        self.buffer.extend_from_slice(b"<!DOCTYPE ");
+        self.buffer.extend_from_slice(name.as_bytes());
+        if let Some(public_id) = public_id {
+            self.buffer.extend_from_slice(b" PUBLIC \"");
+            self.buffer.extend_from_slice(public_id.as_bytes());
+            self.buffer.extend_from_slice(b"\"");
+        } else if system_id.is_some() {
+            self.buffer.extend_from_slice(b" SYSTEM");
+        }
+        if let Some(system_id) = system_id {
+            self.buffer.extend_from_slice(b" ");
+            self.buffer.extend_from_slice(system_quote);
+            self.buffer.extend_from_slice(system_id.as_bytes());
+            self.buffer.extend_from_slice(system_quote);
+        }
+        self.buffer.extend_from_slice(b">");
+        self.commit()?;
+
+        Ok(())
+    }
+
+    /// Writes a processing instruction like `<?xml-stylesheet href="style.xsl"?>` into the
+    /// writer, `content` is separated from the `target` by a space unless it is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the target is invalid or reserved, like `xml`, the content
+    /// contains `?>` or an underlying I/O error occurs.
+    pub fn write_pi(&mut self, target: &str, content: &str) -> Result<(), Error> {
+        if target.is_empty()
+            || target.eq_ignore_ascii_case("xml")
+            || target
+                .split(':')
+                .any(|part| self.is_invalid(part, is_invalid_name))
+        {
+            return Err(Error::InvalidPITarget);
+        }
+
+        if memchr::memmem::find(content.as_bytes(), b"?>").is_some() {
+            return Err(Error::InvalidPIContent);
+        }
+
+        self.start_markup();
+
+        self.buffer.extend_from_slice(b"<?");
+        self.buffer.extend_from_slice(target.as_bytes());
+        if !content.is_empty() {
+            self.buffer.extend_from_slice(b" ");
+            self.buffer.extend_from_slice(content.as_bytes());
+        }
+        self.buffer.extend_from_slice(b"?>");
+        self.end_markup()?;
+
+        Ok(())
+    }
+
+    /// Writes a comment into the writer.
+    ///
+    /// # Notes
+    ///
+    /// Currently this function does not check for null bytes in the string. This may change in a future release.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an underlying I/O error occurs.
+    pub fn write_attribute_event(&mut self, attr: &AttributeEvent) -> Result<(), Error> {
+        if self.depth_and_flags & 1 == 0 {
+            return Err(Error::AttributeOutsideTag);
+        }
+
+        if let Some(canonical) = &mut self.canonical {
+            let raw = attr.raw_value();
+            let value = unescape_normalized(raw, normalize_attribute_value(raw), |text| {
+                Ok::<_, Infallible>(unescape(text))
+            })
+            .unwrap_or_else(|never| match never {});
+            canonical.push_attribute(attr.prefix(), attr.local_name(), value);
+            return Ok(());
+        }
+
+        self.buffer.extend_from_slice(b" ");
+        self.buffer.extend_from_slice(attr.name().as_bytes());
+        if attr.has_value() {
+            self.buffer.extend_from_slice(b"=");
+            self.write_quoted(attr.quote(), attr.raw_value())?;
+        }
+
+        Ok(())
+    }
+
+    fn write_quoted(&mut self, quote: AttributeQuote, value: &str) -> Result<(), Error> {
+        let quote: &[u8] = match quote {
+            AttributeQuote::Single => b"'",
+            AttributeQuote::Double => b"\"",
+            AttributeQuote::None => b"",
+        };
+
+        self.buffer.extend_from_slice(quote);
+        self.buffer.extend_from_slice(value.as_bytes());
+        self.buffer.extend_from_slice(quote);
 
         Ok(())
     }
 
     /// Writes an event into the writer.
     ///
+    /// The event's original text is copied verbatim, including the whitespace between the
+    /// attributes of start tags. Only end tags are normalized.
+    ///
+    /// If [`Options::canonical`] is enabled the event's content is unescaped and written
+    /// in canonical form instead, after normalizing line endings and attribute values.
+    ///
     /// # Errors
     ///
     /// Returns an error if an underlying I/O error occurs.
     pub fn write_event(&mut self, event: &reader::Event) -> Result<(), Error> {
+        if self.canonical.is_some() {
+            return self.write_canonical_event(event);
+        }
+
         match event {
             reader::Event::Start(start) | reader::Event::Empty(start) => {
-                if start.is_empty() {
+                // Empty events are not necessarily self-closing, see Options::html_void_elements.
+                if matches!(event, reader::Event::Empty(_)) {
                     self.write_empty(start.prefix(), start.name())?;
                 } else {
                     self.write_start(start.prefix(), start.name())?;
                 }
 
+                // Attributes are copied verbatim to preserve the original formatting.
                 for attr in start.attributes() {
-                    self.write_attribute_event(&attr)?;
+                    self.buffer
+                        .extend_from_slice(attr.leading_whitespace().as_bytes());
+                    self.buffer.extend_from_slice(attr.raw().as_bytes());
                 }
+                self.buffer
+                    .extend_from_slice(start.trailing_whitespace().as_bytes());
 
                 Ok(())
             }
             reader::Event::End(end) => self.write_end(end.prefix(), end.name()),
+            reader::Event::Text(text) if self.options.indent.is_some() && text.is_whitespace() => {
+                Ok(())
+            }
+            &reader::Event::CData(CDataEvent { text })
+            | &reader::Event::Text(TextEvent { text, .. }) => {
+                self.ensure_tag_closed();
+                self.mark_text();
+
+                self.buffer.extend_from_slice(text.as_bytes());
+                self.commit()?;
+
+                Ok(())
+            }
             &reader::Event::Comment(CommentEvent { text })
-            | &reader::Event::CData(CDataEvent { text })
             | &reader::Event::Doctype(DoctypeEvent { text })
-            | &reader::Event::Text(TextEvent { text }) => {
-                self.ensure_tag_closed()?;
+            | &reader::Event::PI(PIEvent { text, .. })
+            | &reader::Event::Decl(DeclEvent { text }) => {
+                self.ensure_tag_closed();
+                self.write_indent(false);
+                self.last = Last::Markup;
 
-                self.writer.write_all(text.as_bytes())?;
+                self.buffer.extend_from_slice(text.as_bytes());
+                self.commit()?;
 
                 Ok(())
             }
         }
     }
 
+    fn write_canonical_event(&mut self, event: &reader::Event) -> Result<(), Error> {
+        match event {
+            reader::Event::Start(start) | reader::Event::Empty(start) => {
+                if matches!(event, reader::Event::Empty(_)) {
+                    self.write_empty(start.prefix(), start.name())?;
+                } else {
+                    self.write_start(start.prefix(), start.name())?;
+                }
+
+                for attr in start.attributes() {
+                    self.write_attribute_event(&attr)?;
+                }
+
+                Ok(())
+            }
+            reader::Event::End(end) => self.write_end(end.prefix(), end.name()),
+            reader::Event::Text(text) => {
+                let content = text
+                    .unescape_with(
+                        |text| {
+                            unescape_normalized(text, normalize_line_endings(text), |text| {
+                                Ok::<_, Infallible>(unescape(text))
+                            })
+                        },
+                        normalize_line_endings,
+                    )
+                    .unwrap_or_else(|never| match never {});
+                self.write_text(&content)
+            }
+            reader::Event::CData(cdata) => {
+                self.write_text(&normalize_line_endings(cdata.content()))
+            }
+            reader::Event::Comment(comment) => {
+                if !self.options.omit_comments {
+                    self.write_raw_comment_unchecked(&normalize_line_endings(comment.content()))?;
+                }
+
+                Ok(())
+            }
+            reader::Event::PI(pi) => {
+                self.write_pi(pi.target(), &normalize_line_endings(pi.content()))
+            }
+            reader::Event::Doctype(_) | reader::Event::Decl(_) => Ok(()),
+        }
+    }
+
+    /// Returns a builder that writes an element with the specified `prefix` and `name`, its
+    /// attributes and its content in one call.
+    ///
+    /// Nothing is written until one of the builder's terminal methods is called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::Writer;
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer
+    ///     .element(None, "list")
+    ///     .attr("kind", "a & b")
+    ///     .content(|writer| {
+    ///         writer.element(None, "item").attr("id", "1").text("first")?;
+    ///         writer.element(None, "item").attr("id", "2").empty()
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     writer.finish().unwrap(),
+    ///     br#"<list kind="a &amp; b"><item id="1">first</item><item id="2"/></list>"#
+    /// );
+    /// ```
+    pub fn element<'w>(
+        &'w mut self,
+        prefix: Option<&'w str>,
+        name: &'w str,
+    ) -> ElementBuilder<'w, W> {
+        ElementBuilder {
+            writer: self,
+            prefix,
+            name,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Writes a start tag like [`Self::write_start`] and returns a guard that writes the
+    /// matching end tag when it is dropped or [`ElementGuard::end`] is called.
+    ///
+    /// The guard dereferences to this writer, so attributes and content can be written
+    /// through it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use speedy_xml::{writer::Error, Writer};
+    /// fn write_items(writer: &mut Writer<Vec<u8>>, items: &[&str]) -> Result<(), Error> {
+    ///     let mut list = writer.start_element(None, "list")?;
+    ///     for item in items {
+    ///         if item.is_empty() {
+    ///             return Err(Error::InvalidValue);
+    ///         }
+    ///         let mut element = list.start_element(None, "item")?;
+    ///         element.write_text(item)?;
+    ///     }
+    ///     list.end()
+    /// }
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// assert!(write_items(&mut writer, &["a", ""]).is_err());
+    /// assert_eq!(writer.finish().unwrap(), b"<list><item>a</item></list>");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the prefix or name is invalid or an underlying I/O error occurs.
+    pub fn start_element(
+        &mut self,
+        prefix: Option<&str>,
+        name: &str,
+    ) -> Result<ElementGuard<'_, W>, Error> {
+        let depth = self.name_starts.len();
+        self.write_start(prefix, name)?;
+        Ok(ElementGuard {
+            writer: self,
+            depth,
+            open: true,
+        })
+    }
+
     /// Returns a reference to the underlying writer.
+    ///
+    /// A start tag that is still being written is buffered and was not passed to the
+    /// underlying writer yet.
     pub fn inner_ref(&self) -> &W {
         &self.writer
     }
 
     /// Returns a mutable reference to the underlying writer.
+    ///
+    /// A start tag that is still being written is buffered and was not passed to the
+    /// underlying writer yet, call [`Self::flush`] before writing into it directly.
     pub fn inner_mut(&mut self) -> &mut W {
         &mut self.writer
     }
@@ -443,9 +1641,20 @@ impl<W: Write> Writer<W> {
     ///
     /// # Errors
     ///
-    /// Returns an error if an underlying I/O error occurred.
+    /// Returns an error if an underlying I/O error occurred, or an error of kind
+    /// [`InvalidInput`](std::io::ErrorKind::InvalidInput) wrapping
+    /// [`Error::UnclosedElement`] if elements are still open while
+    /// [`Options::check_end_tags`] is enabled.
     pub fn finish(mut self) -> std::io::Result<W> {
-        self.ensure_tag_closed()?;
+        self.ensure_tag_closed();
+        self.commit()?;
+
+        if self.options.check_end_tags && !self.name_starts.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                Error::UnclosedElement,
+            ));
+        }
 
         Ok(self.writer)
     }
@@ -456,12 +1665,199 @@ impl<W: Write> Writer<W> {
     ///
     /// Returns an error if an underlying I/O error occurred.
     pub fn flush(&mut self) -> std::io::Result<()> {
-        self.ensure_tag_closed()?;
+        self.ensure_tag_closed();
+        self.commit()?;
 
         self.writer.flush()
     }
 }
 
+/// An adapter that allows a [`Writer`] to write into a [`std::fmt::Write`] implementor,
+/// like a [`String`].
+///
+/// Every write passed to this adapter must consist of whole UTF-8 sequences, which is
+/// always the case for writes made by a [`Writer`], otherwise an
+/// [`InvalidData`](std::io::ErrorKind::InvalidData) error is returned.
+///
+/// # Examples
+///
+/// ```
+/// # use speedy_xml::{writer::FmtWriter, Writer};
+/// let mut writer = Writer::new(FmtWriter::new(String::new()));
+/// writer.element(None, "a").text("x & y").unwrap();
+/// let text: String = writer.finish().unwrap().into_inner();
+/// assert_eq!(text, "<a>x &amp; y</a>");
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct FmtWriter<F: std::fmt::Write> {
+    inner: F,
+}
+
+impl<F: std::fmt::Write> FmtWriter<F> {
+    /// Creates a new adapter writing into `inner`.
+    pub fn new(inner: F) -> Self {
+        Self { inner }
+    }
+
+    /// Returns a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &F {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped writer.
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F: std::fmt::Write> Write for FmtWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = std::str::from_utf8(buf)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        self.inner.write_str(text).map_err(std::io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A builder for a whole element, created by [`Writer::element`].
+#[must_use = "the element is only written by calling `empty`, `text` or `content`"]
+pub struct ElementBuilder<'w, W: Write> {
+    writer: &'w mut Writer<W>,
+    prefix: Option<&'w str>,
+    name: &'w str,
+    attributes: Vec<(&'w str, &'w str)>,
+}
+
+impl<'w, W: Write> ElementBuilder<'w, W> {
+    /// Adds an attribute, its value will be escaped like with [`Writer::write_attribute`].
+    pub fn attr(mut self, name: &'w str, value: &'w str) -> Self {
+        self.attributes.push((name, value));
+        self
+    }
+
+    /// Checks that all attributes can be written, so that nothing is written if one of them
+    /// is invalid.
+    fn check_attributes(&self) -> Result<(), Error> {
+        let options = &self.writer.options;
+        for &(name, value) in &self.attributes {
+            self.writer.check_attribute_name(None, name)?;
+            let escaped = options.escape_chars(options.value_escape()(value))?;
+            check_attribute_value(options.attribute_quote, &escaped)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_attributes(&mut self) -> Result<(), Error> {
+        for &(name, value) in &self.attributes {
+            self.writer.write_attribute(name, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the element as an empty tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a name is invalid or an underlying I/O error occurs.
+    pub fn empty(mut self) -> Result<(), Error> {
+        self.check_attributes()?;
+        self.writer.write_empty(self.prefix, self.name)?;
+        self.write_attributes()
+    }
+
+    /// Writes the element with `text` as its content.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a name is invalid or an underlying I/O error occurs.
+    pub fn text(self, text: &str) -> Result<(), Error> {
+        self.content(|writer| writer.write_text(text))
+    }
+
+    /// Writes the start tag, calls `content` to write the element's content and writes the
+    /// end tag, returning the value returned by `content`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a name is invalid, `content` returns an error or an underlying
+    /// I/O error occurs. The end tag is not written if `content` fails.
+    pub fn content<T>(
+        mut self,
+        content: impl FnOnce(&mut Writer<W>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        self.check_attributes()?;
+        self.writer.write_start(self.prefix, self.name)?;
+        self.write_attributes()?;
+        let value = content(self.writer)?;
+        self.writer.write_end(self.prefix, self.name)?;
+        Ok(value)
+    }
+}
+
+/// A guard that closes an element when dropped, created by [`Writer::start_element`].
+///
+/// Any elements opened inside of the element and not closed yet are closed as well.
+pub struct ElementGuard<'w, W: Write> {
+    writer: &'w mut Writer<W>,
+    /// The number of elements that were open before this one.
+    depth: usize,
+    open: bool,
+}
+
+impl<W: Write> ElementGuard<'_, W> {
+    fn close(&mut self) -> Result<(), Error> {
+        self.open = false;
+        while self.writer.name_starts.len() > self.depth {
+            self.writer.write_end_auto()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the end tag of the element.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an underlying I/O error occurs.
+    pub fn end(mut self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl<W: Write> Deref for ElementGuard<'_, W> {
+    type Target = Writer<W>;
+
+    fn deref(&self) -> &Self::Target {
+        self.writer
+    }
+}
+
+impl<W: Write> DerefMut for ElementGuard<'_, W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.writer
+    }
+}
+
+impl<W: Write> Drop for ElementGuard<'_, W> {
+    fn drop(&mut self) {
+        if self.open {
+            // Errors can't be reported here, use ElementGuard::end to handle them.
+            _ = self.close();
+        }
+    }
+}
+
 #[test]
 fn reader_writer_roundtrip() {
     const CASES: &[&str] = &[
@@ -469,13 +1865,18 @@ fn reader_writer_roundtrip() {
         "<some xml='text'/>",
         r#"more stuff<then a_tag="here">with content and <![CDATA[value]]></end>"#,
         "text <!-- something with comments --> text text",
+        "<a  b = 'c'\n\td=\"e\"  f=g h\n>text</a>",
+        "<a\r\n/><b />",
     ];
 
     for &input in CASES {
         let mut writer = Writer::new(std::io::Cursor::new(Vec::new()));
         let mut reader = reader::Reader::with_options(
             input,
-            reader::Options::default().allow_top_level_text(true),
+            reader::Options::default()
+                .allow_top_level_text(true)
+                .allow_unquoted_attribute_values(true)
+                .allow_valueless_attributes(true),
         );
 
         while let Some(event) = reader.next().transpose().unwrap() {
@@ -488,3 +1889,794 @@ fn reader_writer_roundtrip() {
         assert_eq!(std::str::from_utf8(&result).unwrap(), input)
     }
 }
+
+#[test]
+fn indent() {
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            indent: Some("  ".into()),
+            ..Options::default()
+        },
+    );
+    writer.write_comment("top").unwrap();
+    writer.write_start(None, "root").unwrap();
+    writer.write_empty(None, "empty").unwrap();
+    writer.write_start(None, "leaf").unwrap();
+    writer.write_end(None, "leaf").unwrap();
+    writer.write_start(None, "p").unwrap();
+    writer.write_text("mixed ").unwrap();
+    writer.write_start(None, "b").unwrap();
+    writer.write_text("content").unwrap();
+    writer.write_end(None, "b").unwrap();
+    writer.write_end(None, "p").unwrap();
+    writer.write_start(None, "nested").unwrap();
+    writer.write_start(None, "title").unwrap();
+    writer.write_text("text").unwrap();
+    writer.write_end(None, "title").unwrap();
+    writer.write_end(None, "nested").unwrap();
+    writer.write_end(None, "root").unwrap();
+
+    assert_eq!(
+        std::str::from_utf8(&writer.finish().unwrap()).unwrap(),
+        concat!(
+            "<!--top-->\n",
+            "<root>\n",
+            "  <empty/>\n",
+            "  <leaf></leaf>\n",
+            "  <p>mixed <b>content</b></p>\n",
+            "  <nested>\n",
+            "    <title>text</title>\n",
+            "  </nested>\n",
+            "</root>"
+        )
+    );
+
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            indent: Some("\t".into()),
+            ..Options::default()
+        },
+    );
+    for event in reader::Reader::new("<a>\n <b>x</b>\n\n<!--c--></a>") {
+        writer.write_event(&event.unwrap()).unwrap();
+    }
+    assert_eq!(
+        writer.finish().unwrap(),
+        b"<a>\n\t<b>x</b>\n\t<!--c-->\n</a>"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Options::indent must only contain whitespace")]
+fn non_whitespace_indent() {
+    Writer::with_options(
+        Vec::new(),
+        Options {
+            indent: Some(" -".into()),
+            ..Options::default()
+        },
+    );
+}
+
+#[test]
+fn empty_tag_depth() {
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            indent: Some(" ".into()),
+            check_end_tags: true,
+            ..Options::default()
+        },
+    );
+    writer.write_start(None, "a").unwrap();
+    writer.write_empty(None, "b").unwrap();
+    writer.write_empty(None, "c").unwrap();
+    writer.write_start(None, "d").unwrap();
+    writer.write_end(None, "d").unwrap();
+    writer.write_end(None, "a").unwrap();
+    assert!(matches!(writer.write_end_auto(), Err(Error::NoOpenElement)));
+    assert_eq!(
+        writer.finish().unwrap(),
+        b"<a>\n <b/>\n <c/>\n <d></d>\n</a>"
+    );
+}
+
+#[test]
+fn newline() {
+    for (newline, expected) in [
+        (Newline::Lf, "<a>\n <b/>\n</a>"),
+        (Newline::CrLf, "<a>\r\n <b/>\r\n</a>"),
+        (Newline::None, "<a><b/></a>"),
+    ] {
+        let mut writer = Writer::with_options(
+            Vec::new(),
+            Options {
+                indent: Some(" ".into()),
+                newline,
+                ..Options::default()
+            },
+        );
+        writer.write_start(None, "a").unwrap();
+        writer.write_empty(None, "b").unwrap();
+        writer.write_end(None, "a").unwrap();
+        assert_eq!(writer.finish().unwrap(), expected.as_bytes());
+    }
+}
+
+#[test]
+fn validate_names() {
+    let mut writer = Writer::new(Vec::new());
+    assert!(matches!(
+        writer.write_empty(Some("a b>"), "x"),
+        Err(Error::InvalidElementPrefix)
+    ));
+
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            validate_names: true,
+            ..Options::default()
+        },
+    );
+    assert!(matches!(
+        writer.write_start(Some("1a"), "x"),
+        Err(Error::InvalidElementPrefix)
+    ));
+    assert!(matches!(
+        writer.write_empty(Some("1a"), "x"),
+        Err(Error::InvalidElementPrefix)
+    ));
+    assert!(matches!(
+        writer.write_empty(None, "a:x"),
+        Err(Error::InvalidElementName)
+    ));
+    writer.write_empty(Some("a"), "x").unwrap();
+    assert_eq!(writer.finish().unwrap(), b"<a:x/>");
+}
+
+#[test]
+fn write_end_auto() {
+    let mut writer = Writer::new(Vec::new());
+    for event in reader::Reader::new("<a><b:c><d/>").map_while(Result::ok) {
+        writer.write_event(&event).unwrap();
+    }
+    writer.write_text("x").unwrap();
+    writer.write_end_auto().unwrap();
+    writer.write_start(None, "e").unwrap();
+    writer.write_end(None, "e").unwrap();
+    writer.write_end_auto().unwrap();
+    assert!(matches!(writer.write_end_auto(), Err(Error::NoOpenElement)));
+    assert_eq!(writer.finish().unwrap(), b"<a><b:c><d/>x</b:c><e></e></a>");
+}
+
+#[test]
+fn element_builder() {
+    let mut writer = Writer::new(Vec::new());
+    let count = writer
+        .element(Some("x"), "root")
+        .content(|writer| {
+            for id in 0..2 {
+                writer
+                    .element(None, "item")
+                    .attr("id", &id.to_string())
+                    .text("<")?;
+            }
+            Ok(2)
+        })
+        .unwrap();
+    assert_eq!(count, 2);
+    assert!(matches!(
+        writer.element(None, "a").attr("b c", "").empty(),
+        Err(Error::InvalidAttributeName)
+    ));
+    assert!(matches!(
+        writer
+            .element(None, "a")
+            .attr("b", "")
+            .attr("c", "\0")
+            .text(""),
+        Err(Error::InvalidAttributeValue)
+    ));
+    assert_eq!(
+        writer.finish().unwrap(),
+        br#"<x:root><item id="0">&lt;</item><item id="1">&lt;</item></x:root>"#
+    );
+}
+
+#[test]
+fn element_guard() {
+    let mut writer = Writer::new(Vec::new());
+    {
+        let mut a = writer.start_element(None, "a").unwrap();
+        a.write_attribute("x", "1").unwrap();
+        // Elements left open inside the guard are closed too.
+        a.write_start(Some("p"), "b").unwrap();
+        a.write_empty(None, "c").unwrap();
+    }
+    let b = writer.start_element(None, "d").unwrap();
+    b.end().unwrap();
+    assert_eq!(
+        writer.finish().unwrap(),
+        br#"<a x="1"><p:b><c/></p:b></a><d></d>"#
+    );
+}
+
+#[test]
+fn write_pi() {
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_pi("xml-stylesheet", r#"href="x.xsl" type="text/xsl""#)
+        .unwrap();
+    writer.write_start(None, "a").unwrap();
+    writer.write_pi("ns:empty", "").unwrap();
+    writer.write_end(None, "a").unwrap();
+    for (target, content) in [("XML", ""), ("", "x"), ("a b", ""), ("a?", "")] {
+        assert!(matches!(
+            writer.write_pi(target, content),
+            Err(Error::InvalidPITarget)
+        ));
+    }
+    assert!(matches!(
+        writer.write_pi("pi", "a ?> b"),
+        Err(Error::InvalidPIContent)
+    ));
+
+    let output = writer.finish().unwrap();
+    assert_eq!(
+        output,
+        br#"<?xml-stylesheet href="x.xsl" type="text/xsl"?><a><?ns:empty?></a>"#
+    );
+    let mut reader = reader::Reader::with_options(
+        std::str::from_utf8(&output).unwrap(),
+        reader::Options::default().parse_pi_nodes(true),
+    );
+    let Some(Ok(reader::Event::PI(pi))) = reader.next() else {
+        panic!()
+    };
+    assert_eq!(pi.target(), "xml-stylesheet");
+    assert_eq!(pi.content(), r#"href="x.xsl" type="text/xsl""#);
+}
+
+#[test]
+fn write_declaration() {
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            indent: Some("".into()),
+            ..Options::default()
+        },
+    );
+    writer
+        .write_declaration("1.1", Some("UTF-8"), Some(true))
+        .unwrap();
+    assert!(matches!(
+        writer.write_declaration("1.0", None, None),
+        Err(Error::DeclarationNotFirst)
+    ));
+    writer.write_start(None, "a").unwrap();
+    writer.write_text("\u{1}").unwrap();
+    writer.write_end(None, "a").unwrap();
+    assert_eq!(
+        writer.finish().unwrap(),
+        b"<?xml version=\"1.1\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<a>&#x1;</a>"
+    );
+
+    for (version, encoding) in [("2.0", None), ("1.", None), ("1.0", Some("8bit"))] {
+        let mut writer = Writer::new(Vec::new());
+        assert!(matches!(
+            writer.write_declaration(version, encoding, None),
+            Err(Error::InvalidDeclaration)
+        ));
+    }
+
+    let mut writer = Writer::new(Vec::new());
+    writer.write_text(" ").unwrap();
+    assert!(matches!(
+        writer.write_declaration("1.0", None, Some(false)),
+        Err(Error::DeclarationNotFirst)
+    ));
+}
+
+#[test]
+fn write_doctype() {
+    let mut writer = Writer::new(Vec::new());
+    writer.write_declaration("1.0", None, None).unwrap();
+    writer
+        .write_doctype(
+            "html",
+            Some("-//W3C//DTD XHTML 1.0 Strict//EN"),
+            Some("xhtml1-strict.dtd"),
+        )
+        .unwrap();
+    writer.write_doctype("a", None, Some("say \"hi\"")).unwrap();
+    writer.write_doctype("svg:svg", None, None).unwrap();
+    writer.write_empty(None, "html").unwrap();
+    let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+    assert_eq!(
+        output,
+        concat!(
+            r#"<?xml version="1.0"?>"#,
+            r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "xhtml1-strict.dtd">"#,
+            r#"<!DOCTYPE a SYSTEM 'say "hi"'>"#,
+            r#"<!DOCTYPE svg:svg>"#,
+            r#"<html/>"#
+        )
+    );
+
+    let doctypes = crate::Reader::new(&output)
+        .filter_map(|event| match event.unwrap() {
+            reader::Event::Doctype(doctype) => Some((
+                doctype.name(),
+                doctype.external_id().map(|id| (id.public_id, id.system_id)),
+            )),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        doctypes,
+        [
+            (
+                Some("html"),
+                Some((
+                    Some("-//W3C//DTD XHTML 1.0 Strict//EN"),
+                    Some("xhtml1-strict.dtd")
+                ))
+            ),
+            (Some("a"), Some((None, Some("say \"hi\"")))),
+            (Some("svg:svg"), None),
+        ]
+    );
+
+    for (name, public_id, system_id) in [
+        ("", None, None),
+        ("a b", None, None),
+        ("a", Some("id"), None),
+        ("a", Some("{id}"), Some("x")),
+        ("a", None, Some("'\"")),
+    ] {
+        let mut writer = Writer::new(Vec::new());
+        assert!(matches!(
+            writer.write_doctype(name, public_id, system_id),
+            Err(Error::InvalidDoctype)
+        ));
+    }
+}
+
+#[test]
+fn fmt_writer() {
+    let mut writer = Writer::with_options(
+        FmtWriter::new(String::new()),
+        Options {
+            indent: Some("\t".into()),
+            ..Options::default()
+        },
+    );
+    writer.write_start(None, "r\u{E9}sum\u{E9}").unwrap();
+    writer.write_attribute("lang", "\u{65E5}\u{672C}").unwrap();
+    writer.write_empty(None, "a").unwrap();
+    writer.write_end_auto().unwrap();
+    assert_eq!(
+        writer.finish().unwrap().into_inner(),
+        "<r\u{E9}sum\u{E9} lang=\"\u{65E5}\u{672C}\">\n\t<a/>\n</r\u{E9}sum\u{E9}>"
+    );
+
+    let mut writer = FmtWriter::new(String::new());
+    assert_eq!(
+        writer.write(b"\xE9").unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+    assert!(writer.into_inner().is_empty());
+}
+
+#[test]
+fn attribute_quote() {
+    let mut writer = Writer::new(Vec::new());
+    writer.write_empty(None, "a").unwrap();
+    writer.write_attribute("b", "\"x\" & 'y'").unwrap();
+    assert_eq!(
+        writer.finish().unwrap(),
+        br#"<a b="&quot;x&quot; &amp; 'y'"/>"#
+    );
+
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            attribute_quote: AttributeQuote::Single,
+            ..Options::default()
+        },
+    );
+    writer
+        .element(None, "a")
+        .attr("b", "\"x\" & 'y'")
+        .empty()
+        .unwrap();
+    assert_eq!(
+        writer.finish().unwrap(),
+        br#"<a b='"x" &amp; &apos;y&apos;'/>"#
+    );
+
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            attribute_quote: AttributeQuote::None,
+            ..Options::default()
+        },
+    );
+    writer.write_empty(None, "a").unwrap();
+    writer.write_attribute("b", "x&y").unwrap();
+    for value in ["x y", "", "x/", "x>"] {
+        assert!(matches!(
+            writer.write_attribute("c", value),
+            Err(Error::InvalidAttributeValue)
+        ));
+    }
+    writer.write_attribute("d", "x/y").unwrap();
+    assert_eq!(writer.finish().unwrap(), b"<a b=x&amp;y d=x/y/>");
+}
+
+#[test]
+fn escape_non_ascii() {
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            escape_non_ascii: true,
+            xml_version_1_1: true,
+            ..Options::default()
+        },
+    );
+    writer.write_start(None, "café").unwrap();
+    writer.write_attribute("a", "«é»").unwrap();
+    writer
+        .write_attribute_fmt("b", format_args!("{}\u{85}", 'ü'))
+        .unwrap();
+    assert!(matches!(
+        writer.write_attribute_fmt("c", format_args!("{}\0", 1)),
+        Err(Error::InvalidValue)
+    ));
+    writer.write_text("naïve & \u{1F600}\x01").unwrap();
+    assert!(matches!(writer.write_text("\0"), Err(Error::InvalidValue)));
+    assert!(matches!(
+        writer.write_text_fmt(format_args!("{}\0", 'x')),
+        Err(Error::InvalidValue)
+    ));
+    writer.write_text_fmt(format_args!("{}", '€')).unwrap();
+    writer.write_comment("é").unwrap();
+    writer.write_end_auto().unwrap();
+    assert_eq!(
+        std::str::from_utf8(&writer.finish().unwrap()).unwrap(),
+        "<café a=\"&#xAB;&#xE9;&#xBB;\" b=\"&#xFC;&#x85;\">na&#xEF;ve &amp; &#x1F600;&#x1;&#x20AC;<!--é--></café>"
+    );
+}
+
+#[test]
+fn empty_tag() {
+    for (empty_tag, expected) in [
+        (EmptyTag::SelfClosing, r#"<a><b/><x:c d="1"/></a>"#),
+        (EmptyTag::SelfClosingSpaced, r#"<a><b /><x:c d="1" /></a>"#),
+        (EmptyTag::Expanded, r#"<a><b></b><x:c d="1"></x:c></a>"#),
+    ] {
+        let mut writer = Writer::with_options(
+            Vec::new(),
+            Options {
+                empty_tag,
+                ..Options::default()
+            },
+        );
+        writer.write_start(None, "a").unwrap();
+        writer.write_empty(None, "b").unwrap();
+        writer
+            .element(Some("x"), "c")
+            .attr("d", "1")
+            .empty()
+            .unwrap();
+        writer.write_end_auto().unwrap();
+        assert_eq!(writer.finish().unwrap(), expected.as_bytes());
+    }
+
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            empty_tag: EmptyTag::Expanded,
+            indent: Some(" ".into()),
+            ..Options::default()
+        },
+    );
+    writer.write_empty(None, "a").unwrap();
+    assert_eq!(writer.finish().unwrap(), b"<a></a>");
+}
+
+#[test]
+fn check_end_tags() {
+    let options = Options {
+        check_end_tags: true,
+        ..Options::default()
+    };
+
+    let mut writer = Writer::with_options(Vec::new(), options.clone());
+    assert!(matches!(
+        writer.write_end(None, "a"),
+        Err(Error::NoOpenElement)
+    ));
+    writer.write_start(Some("x"), "a").unwrap();
+    writer.write_start(None, "b").unwrap();
+    for (prefix, name) in [
+        (None, "a"),
+        (Some("x"), "b"),
+        (Some("b"), "b"),
+        (None, "bb"),
+    ] {
+        assert!(matches!(
+            writer.write_end(prefix, name),
+            Err(Error::MismatchedEndTag)
+        ));
+    }
+    writer.write_end(None, "b").unwrap();
+    writer.write_end(Some("x"), "a").unwrap();
+    assert_eq!(writer.finish().unwrap(), b"<x:a><b></b></x:a>");
+
+    let mut writer = Writer::with_options(Vec::new(), options);
+    writer.write_start(None, "a").unwrap();
+    writer.write_empty(None, "b").unwrap();
+    let error = writer.finish().unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(matches!(
+        error.into_inner().unwrap().downcast_ref(),
+        Some(Error::UnclosedElement)
+    ));
+
+    for canonical in [false, true] {
+        let mut writer = Writer::with_options(
+            Vec::new(),
+            Options {
+                check_end_tags: true,
+                empty_tag: EmptyTag::Expanded,
+                canonical,
+                ..Options::default()
+            },
+        );
+        writer.write_start(None, "a").unwrap();
+        writer.write_empty(Some("x"), "b").unwrap();
+        assert!(matches!(
+            writer.write_end(Some("x"), "b"),
+            Err(Error::MismatchedEndTag)
+        ));
+        writer.write_end(None, "a").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"<a><x:b></x:b></a>");
+    }
+
+    // Mismatched end tags are written by default, but unbalanced ones are not.
+    let mut writer = Writer::new(Vec::new());
+    writer.write_start(None, "a").unwrap();
+    writer.write_end(None, "b").unwrap();
+    assert!(matches!(
+        writer.write_end(None, "a"),
+        Err(Error::NoOpenElement)
+    ));
+    writer.write_empty(None, "c").unwrap();
+    assert!(matches!(
+        writer.write_end(None, "c"),
+        Err(Error::NoOpenElement)
+    ));
+    assert_eq!(writer.finish().unwrap(), b"<a></b><c/>");
+}
+
+#[test]
+fn write_fmt() {
+    struct Failing;
+
+    impl Display for Failing {
+        fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            Err(std::fmt::Error)
+        }
+    }
+
+    let mut writer = Writer::with_options(
+        Vec::new(),
+        Options {
+            attribute_quote: AttributeQuote::Single,
+            xml_version_1_1: true,
+            ..Options::default()
+        },
+    );
+    writer.write_start(None, "a").unwrap();
+    let quote = '\'';
+    writer
+        .write_attribute_fmt("b", format_args!("{quote}{}\"&", 1.5))
+        .unwrap();
+    writer
+        .write_attribute_fmt("c", format_args!("plain"))
+        .unwrap();
+    writer
+        .write_text_fmt(format_args!("{}&{}", "<x>", '\u{1}'))
+        .unwrap();
+    assert!(matches!(
+        writer.write_text_fmt(format_args!("{}", Failing)),
+        Err(Error::Io(_))
+    ));
+    writer.write_start(None, "e").unwrap();
+    assert!(matches!(
+        writer.write_attribute_fmt("f", format_args!("x<{}", Failing)),
+        Err(Error::Io(_))
+    ));
+    writer.write_end_auto().unwrap();
+    writer.write_end(None, "a").unwrap();
+    assert!(matches!(
+        writer.write_attribute_fmt("d", format_args!("{}", 1)),
+        Err(Error::AttributeOutsideTag)
+    ));
+    assert_eq!(
+        String::from_utf8(writer.finish().unwrap()).unwrap(),
+        r#"<a b='&apos;1.5"&amp;' c='plain'>&lt;x>&amp;&#x1;<e></e></a>"#
+    );
+}
+
+#[cfg(all(feature = "itoa", feature = "ryu"))]
+#[test]
+fn write_numbers() {
+    let mut writer = Writer::new(Vec::new());
+    writer.write_start(None, "a").unwrap();
+    writer.write_attribute_int("i", -42i64).unwrap();
+    writer.write_attribute_int("u", u128::MAX).unwrap();
+    writer.write_attribute_float("f", 0.1f32).unwrap();
+    writer.write_attribute_float("n", f64::NAN).unwrap();
+    writer.write_text_int(7u8).unwrap();
+    writer.write_text(" ").unwrap();
+    writer.write_text_float(-1e300f64).unwrap();
+    writer.write_end_auto().unwrap();
+    assert!(matches!(
+        writer.write_attribute_int("x", 1),
+        Err(Error::AttributeOutsideTag)
+    ));
+    assert_eq!(
+        String::from_utf8(writer.finish().unwrap()).unwrap(),
+        r#"<a i="-42" u="340282366920938463463374607431768211455" f="0.1" n="NaN">7 -1e300</a>"#
+    );
+}
+
+#[test]
+fn buffered_writes() {
+    struct CountingWriter {
+        output: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut writer = Writer::with_options(
+        CountingWriter {
+            output: Vec::new(),
+            writes: 0,
+        },
+        Options {
+            indent: Some("  ".into()),
+            ..Options::default()
+        },
+    );
+    writer.write_start(Some("x"), "root").unwrap();
+    writer.write_attribute("a", "1").unwrap();
+    writer.write_attribute("b", "2").unwrap();
+    assert_eq!(writer.inner_ref().writes, 0);
+    writer.write_empty(None, "child").unwrap();
+    writer.write_attribute("c", "3").unwrap();
+    assert_eq!(writer.inner_ref().writes, 0);
+    writer.write_comment("comment").unwrap();
+    assert_eq!(writer.inner_ref().writes, 1);
+    writer.write_end_auto().unwrap();
+    assert_eq!(writer.inner_ref().writes, 2);
+
+    writer.write_start(None, "open").unwrap();
+    writer.flush().unwrap();
+    assert_eq!(writer.inner_ref().writes, 3);
+    writer.write_text(&"long text ".repeat(2000)).unwrap();
+    assert!(writer.buffer.capacity() <= BUFFER_CAPACITY);
+
+    let output = writer.finish().unwrap().output;
+    assert!(output.starts_with(
+        b"<x:root a=\"1\" b=\"2\">\n  <child c=\"3\"/>\n  <!--comment-->\n</x:root>\n<open>long text"
+    ));
+}
+
+#[test]
+fn canonical() {
+    const CASES: &[(&str, &str)] = &[
+        (
+            "<?xml version=\"1.0\"?>\r\n\r\n<?xml-stylesheet   href=\"doc.xsl\"\r\n   type=\"text/xsl\"   ?>\r\n\r\n<!DOCTYPE doc SYSTEM \"doc.dtd\">\r\n\r\n<doc>Hello, world!<!-- Comment 1 --></doc>\r\n\r\n<?pi-without-data     ?>\r\n\r\n<!-- Comment 2 -->\r\n\r\n<!-- Comment 3 -->",
+            "<?xml-stylesheet href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n<doc>Hello, world!<!-- Comment 1 --></doc>\n<?pi-without-data?>\n<!-- Comment 2 -->\n<!-- Comment 3 -->",
+        ),
+        (
+            r#"<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = "elem3"   id="elem3"   />
+   <e5 a:attr="out" b:attr="sorted" attr2="all" attr="I'm"
+      xmlns:b="http://www.ietf.org"
+      xmlns:a="http://www.w3.org"
+      xmlns="http://example.org"/>
+   <e6 xmlns="" xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="" xmlns:a="http://www.w3.org">
+            <e9 xmlns="" xmlns:a="http://www.ietf.org" xml:lang="en" z="1"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#,
+            r#"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id="elem3" name="elem3"></e3>
+   <e5 xmlns="http://example.org" xmlns:a="http://www.w3.org" xmlns:b="http://www.ietf.org" attr="I'm" attr2="all" b:attr="sorted" a:attr="out"></e5>
+   <e6 xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="">
+            <e9 xmlns:a="http://www.ietf.org" z="1" xml:lang="en"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#,
+        ),
+        (
+            "<doc>\r\n<text>First line&#x0d;&#10;Second line</text>\r\n<compute><![CDATA[value>\"0\" && value<\"10\" ?\"valid\":\"error\"]]></compute>\r\n<norm attr=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>\r\n</doc>",
+            "<doc>\n<text>First line&#xD;\nSecond line</text>\n<compute>value&gt;\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"</compute>\n<norm attr=\" '    &#xD;&#xA;&#x9;   ' \"></norm>\n</doc>",
+        ),
+    ];
+
+    let options = Options {
+        canonical: true,
+        indent: Some("  ".into()),
+        ..Options::default()
+    };
+    for &(input, expected) in CASES {
+        let mut writer = Writer::with_options(Vec::new(), options.clone());
+        let reader_options = reader::Options::default().parse_pi_nodes(true);
+        for event in reader::Reader::with_options(input, reader_options) {
+            writer.write_event(&event.unwrap()).unwrap();
+        }
+
+        assert_eq!(
+            std::str::from_utf8(&writer.finish().unwrap()).unwrap(),
+            expected
+        );
+    }
+
+    let mut writer = Writer::with_options(Vec::new(), options);
+    writer.write_declaration("1.0", None, None).unwrap();
+    writer.write_comment("first").unwrap();
+    writer.write_start(Some("x"), "a").unwrap();
+    writer.write_attribute("b", "\"<\t>\"").unwrap();
+    writer
+        .write_attribute_fmt("a", format_args!("{}", 1))
+        .unwrap();
+    writer
+        .write_prefixed_attribute(Some("xmlns"), "x", "urn:x")
+        .unwrap();
+    writer
+        .write_prefixed_attribute(Some("x"), "a", "2")
+        .unwrap();
+    writer.write_empty(Some("x"), "b").unwrap();
+    writer
+        .write_raw_attribute("xmlns", AttributeQuote::Single, "")
+        .unwrap();
+    writer
+        .write_prefixed_attribute(Some("xmlns"), "x", "urn:x")
+        .unwrap();
+    writer.write_cdata("]> &\r").unwrap();
+    writer.write_end_auto().unwrap();
+    writer.write_text("\n").unwrap();
+    writer.write_pi("last", "").unwrap();
+    assert_eq!(
+        std::str::from_utf8(&writer.finish().unwrap()).unwrap(),
+        "<!--first-->\n<x:a xmlns:x=\"urn:x\" a=\"1\" b=\"&quot;&lt;&#x9;>&quot;\" x:a=\"2\"><x:b></x:b>]&gt; &amp;&#xD;</x:a>\n<?last?>"
+    );
+}